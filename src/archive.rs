@@ -0,0 +1,811 @@
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    ffi::OsString,
+    fmt, fs,
+    io::{self, BufReader, Cursor, Read, Seek, SeekFrom},
+    path::{Component, Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use chrono::{Local, NaiveDate, TimeZone};
+use flate2::read::{DeflateDecoder, MultiGzDecoder};
+use tar::EntryType as TarEntryType;
+use zip::extra_fields::ExtraField;
+
+use crate::{
+    file_system::{DirEntry, FileReader, FileSystem, FileType, Metadata, Rewindable},
+    platform::{self, OsError},
+    readonly,
+};
+
+/// How many symlinks resolving a path inside an archive follows before giving up, as Linux does
+const MAX_LINKS: usize = 40;
+
+/// The archive formats whose contents can be listed like a directory
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Tar,
+    /// A gzip compressed tar archive, `.tar.gz` or `.tgz`
+    TarGz,
+    Zip,
+}
+
+impl Format {
+    /// Recognizes an archive by its file name's extension
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path whose last component is checked
+    ///
+    /// # Returns
+    ///
+    /// The archive's format, or None if the name is not an archive's
+    pub fn from_name(path: &Path) -> Option<Format> {
+        let name = path.file_name()?.to_string_lossy().to_ascii_lowercase();
+        if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Some(Format::TarGz)
+        } else if name.ends_with(".tar") {
+            Some(Format::Tar)
+        } else if name.ends_with(".zip") {
+            Some(Format::Zip)
+        } else {
+            None
+        }
+    }
+}
+
+/// Finds the archive a path lies in, so `backup.zip/docs` names the `docs` directory
+/// inside `backup.zip`
+///
+/// # Arguments
+///
+/// * `path` - The path to look at, possibly an archive itself
+///
+/// # Returns
+///
+/// The archive file's path and format, or None if no part of the path is an archive file
+pub fn find(path: &Path) -> Option<(&Path, Format)> {
+    path.ancestors().find_map(|ancestor| {
+        let format = Format::from_name(ancestor)?;
+        fs::metadata(ancestor).ok()?.is_file().then_some((ancestor, format))
+    })
+}
+
+/// An entry inside an archive
+#[derive(Debug)]
+struct Node {
+    metadata: Metadata,
+    /// Where the entry points to, if it is a symlink
+    target: Option<PathBuf>,
+    /// The names of a directory's entries, in the order the archive stores them
+    children: Vec<OsString>,
+    /// Where a regular file's contents are stored, if they can be read
+    data: Option<Data>,
+}
+
+/// Where a file's contents lie in the archive
+#[derive(Clone, Copy, Debug)]
+struct Data {
+    /// The offset of the stored bytes, into the decompressed stream for `.tar.gz`
+    offset: u64,
+    /// How many bytes are stored
+    len: u64,
+    /// Whether the bytes are deflate compressed, as zip entries usually are
+    deflated: bool,
+}
+
+/// A zip or tar archive's contents, read as a directory tree rooted at the archive's path
+///
+/// The whole index is read when the archive is opened, so listing is served from
+/// memory. Directories the archive leaves out are filled in, and entries whose
+/// names climb out of the archive with `..` are skipped.
+#[derive(Debug)]
+pub struct ArchiveFileSystem {
+    /// The archive file's path, which the root of its contents is listed as
+    root: PathBuf,
+    format: Format,
+    /// Every entry, by its path inside the archive; the root is the empty path
+    nodes: BTreeMap<PathBuf, Node>,
+    /// The metadata directories the archive does not describe are given
+    directory: Metadata,
+    /// The whole decompressed stream of a `.tar.gz`, once a file's contents are read
+    decompressed: Decompressed,
+}
+
+/// The decompressed bytes of a `.tar.gz`, read by the first [`FileSystem::open`] and
+/// shared by every file opened after it
+#[derive(Default)]
+struct Decompressed(Mutex<Option<Arc<[u8]>>>);
+
+impl Decompressed {
+    /// Fetches the decompressed bytes, decompressing the archive on first use
+    ///
+    /// # Arguments
+    ///
+    /// * `root` - The archive file
+    fn get(&self, root: &Path) -> io::Result<Arc<[u8]>> {
+        let mut cached = self.0.lock().unwrap_or_else(|err| err.into_inner());
+        if let Some(bytes) = &*cached {
+            return Ok(Arc::clone(bytes));
+        }
+        let mut bytes = Vec::new();
+        MultiGzDecoder::new(BufReader::new(readonly::open_file(root)?)).read_to_end(&mut bytes)?;
+        let bytes: Arc<[u8]> = bytes.into();
+        *cached = Some(Arc::clone(&bytes));
+        Ok(bytes)
+    }
+}
+
+impl fmt::Debug for Decompressed {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let len = self.0.lock().unwrap_or_else(|err| err.into_inner()).as_ref().map(|bytes| bytes.len());
+        f.debug_tuple("Decompressed").field(&len).finish()
+    }
+}
+
+/// One file's contents inside the decompressed stream of a `.tar.gz`
+struct Stored {
+    archive: Arc<[u8]>,
+    start: usize,
+    end: usize,
+}
+
+impl AsRef<[u8]> for Stored {
+    fn as_ref(&self) -> &[u8] {
+        &self.archive[self.start..self.end]
+    }
+}
+
+impl ArchiveFileSystem {
+    /// Opens an archive and reads the index of its entries
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The archive file
+    /// * `format` - How the archive is stored
+    ///
+    /// # Returns
+    ///
+    /// The archive's contents, or an error if it cannot be read or is not a valid archive
+    pub fn open(path: &Path, format: Format) -> io::Result<Self> {
+        let file = readonly::open_file(path)?;
+        let archive = Metadata::from(file.metadata()?);
+        // Directories take the archive's owner and time, and can be entered wherever it can be read
+        let permissions = archive.mode & 0o777;
+        let directory = Metadata {
+            file_type: FileType::Directory,
+            mode: type_bits(FileType::Directory) | permissions | (permissions & 0o444) >> 2,
+            len: 0,
+            blocks: 0,
+            nlink: 1,
+            ino: 0,
+            ..archive
+        };
+        let mut archive = ArchiveFileSystem {
+            root: path.to_path_buf(),
+            format,
+            nodes: BTreeMap::new(),
+            directory: directory.clone(),
+            decompressed: Decompressed::default(),
+        };
+        archive.insert(PathBuf::new(), directory, None, None);
+        match format {
+            Format::Tar => archive.read_tar(BufReader::new(file))?,
+            Format::TarGz => archive.read_tar(MultiGzDecoder::new(BufReader::new(file)))?,
+            Format::Zip => archive.read_zip(BufReader::new(file))?,
+        }
+        Ok(archive)
+    }
+
+    /// Indexes the entries of a tar archive
+    fn read_tar(&mut self, reader: impl Read) -> io::Result<()> {
+        let mut archive = tar::Archive::new(reader);
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            // PAX records stand in for header fields that are too small, and carry times to the nanosecond
+            let pax: Vec<(String, String)> = match entry.pax_extensions()? {
+                Some(extensions) => extensions
+                    .filter_map(|extension| {
+                        let extension = extension.ok()?;
+                        Some((extension.key().ok()?.to_string(), extension.value().ok()?.to_string()))
+                    })
+                    .collect(),
+                None => Vec::new(),
+            };
+            let pax_value = |key: &str| pax.iter().find(|(name, _)| name == key).map(|(_, value)| value.as_str());
+            let Some(path) = inner_path(&entry.path()?) else {
+                continue;
+            };
+            let header = entry.header();
+            let target = entry.link_name()?.map(|target| target.into_owned());
+            let file_type = match header.entry_type() {
+                TarEntryType::Regular | TarEntryType::Continuous | TarEntryType::GNUSparse => FileType::File,
+                TarEntryType::Directory => FileType::Directory,
+                TarEntryType::Symlink => FileType::Symlink,
+                TarEntryType::Fifo => FileType::Fifo,
+                TarEntryType::Char => FileType::CharDevice,
+                TarEntryType::Block => FileType::BlockDevice,
+                TarEntryType::Link => {
+                    // A hard link shares its target's metadata and contents, inode number included
+                    if let Some((metadata, data)) = target.as_deref().and_then(inner_path).and_then(|target| self.nodes.get(&target)).map(|node| (node.metadata.clone(), node.data)) {
+                        self.insert(path, metadata, None, data);
+                    }
+                    continue;
+                }
+                _ => continue,
+            };
+            // GNU headers have room for the access and change times, left 0 when not stored
+            let gnu_time = |time: Option<io::Result<u64>>| time.and_then(Result::ok).filter(|&time| time != 0).map(|time| UNIX_EPOCH + Duration::from_secs(time));
+            let gnu = header.as_gnu();
+            // Header fields PAX records stand in for may be left empty, so they are only read when needed
+            let modified = match pax_value("mtime").and_then(pax_time) {
+                Some(modified) => modified,
+                None => UNIX_EPOCH + Duration::from_secs(header.mtime()?),
+            };
+            let uid = match pax_value("uid").and_then(|uid| uid.parse().ok()) {
+                Some(uid) => uid,
+                None => header.uid()? as u32,
+            };
+            let gid = match pax_value("gid").and_then(|gid| gid.parse().ok()) {
+                Some(gid) => gid,
+                None => header.gid()? as u32,
+            };
+            let accessed = pax_value("atime").and_then(pax_time).or_else(|| gnu_time(gnu.map(|gnu| gnu.atime())));
+            let changed = pax_value("ctime").and_then(pax_time).or_else(|| gnu_time(gnu.map(|gnu| gnu.ctime())));
+            let name = |pax: Option<&str>, stored: Option<&[u8]>| {
+                let name = pax.map(str::to_string).or_else(|| stored.map(|name| String::from_utf8_lossy(name).into_owned()));
+                name.filter(|name| !name.is_empty())
+            };
+            let len = match (file_type, &target) {
+                // Like lstat, a symlink's size is the length of the path it holds
+                (FileType::Symlink, Some(target)) => target.as_os_str().len() as u64,
+                _ => entry.size(),
+            };
+            let metadata = Metadata {
+                file_type,
+                mode: type_bits(file_type) | header.mode()? & 0o7777,
+                len,
+                blocks: len.div_ceil(512),
+                nlink: 1,
+                uid,
+                gid,
+                owner: name(pax_value("uname"), header.username_bytes()),
+                group: name(pax_value("gname"), header.groupname_bytes()),
+                modified: Some(modified),
+                accessed,
+                changed,
+                ..Default::default()
+            };
+            // Sparse files are stored in pieces, so only plain ones can be read
+            let data = matches!(header.entry_type(), TarEntryType::Regular | TarEntryType::Continuous).then(|| Data {
+                offset: entry.raw_file_position(),
+                len: entry.size(),
+                deflated: false,
+            });
+            self.insert(path, metadata, target.filter(|_| file_type == FileType::Symlink), data);
+        }
+        // Hard links share an inode, and each name it goes by counts as a link
+        let mut links: HashMap<u64, u64> = HashMap::new();
+        for node in self.nodes.values().filter(|node| !node.metadata.is_dir()) {
+            *links.entry(node.metadata.ino).or_default() += 1;
+        }
+        for node in self.nodes.values_mut().filter(|node| !node.metadata.is_dir()) {
+            node.metadata.nlink = links[&node.metadata.ino];
+        }
+        Ok(())
+    }
+
+    /// Indexes the entries of a zip archive
+    ///
+    /// Owners come from the Info-ZIP Unix extra field; entries without one are shown as
+    /// owned by whoever runs the listing, as extracting the archive would leave them.
+    /// Extended timestamp and NTFS extra fields give the times in UTC, to the second or
+    /// better, where the entry's own time is local and to two seconds.
+    fn read_zip(&mut self, reader: impl Read + io::Seek) -> io::Result<()> {
+        let mut archive = zip::ZipArchive::new(reader)?;
+        for index in 0..archive.len() {
+            let mut file = archive.by_index(index)?;
+            let Some(path) = file.enclosed_name().as_deref().and_then(inner_path) else {
+                continue;
+            };
+            let mode = file.unix_mode().unwrap_or(if file.is_dir() { 0o040755 } else { 0o100644 });
+            let file_type = if file.is_dir() { FileType::Directory } else { type_of(mode) };
+            let target = if file_type == FileType::Symlink {
+                let mut target = Vec::new();
+                file.read_to_end(&mut target)?;
+                Some(PathBuf::from(platform::os_string_from_bytes(target)))
+            } else {
+                None
+            };
+            // Zip times are the local time of whoever made the archive
+            let modified = file.last_modified().and_then(|time| {
+                let time = NaiveDate::from_ymd_opt(time.year().into(), time.month().into(), time.day().into())?.and_hms_opt(time.hour().into(), time.minute().into(), time.second().into())?;
+                Local.from_local_datetime(&time).earliest().map(SystemTime::from)
+            });
+            let (mut modified, mut accessed, mut created) = (modified, None, None);
+            for field in file.extra_data_fields() {
+                match field {
+                    ExtraField::ExtendedTimestamp(times) => {
+                        let time = |time: Option<u32>| time.map(|time| UNIX_EPOCH + Duration::from_secs(time.into()));
+                        modified = time(times.mod_time()).or(modified);
+                        accessed = time(times.ac_time()).or(accessed);
+                        created = time(times.cr_time()).or(created);
+                    }
+                    ExtraField::Ntfs(times) => {
+                        modified = ntfs_time(times.mtime()).or(modified);
+                        accessed = ntfs_time(times.atime()).or(accessed);
+                        created = ntfs_time(times.ctime()).or(created);
+                    }
+                }
+            }
+            let (uid, gid) = file.extra_data().and_then(unix_owner).unwrap_or((platform::current_uid(), platform::current_gid()));
+            let metadata = Metadata {
+                file_type,
+                mode: type_bits(file_type) | mode & 0o7777,
+                len: file.size(),
+                blocks: file.compressed_size().div_ceil(512),
+                nlink: 1,
+                uid,
+                gid,
+                modified,
+                accessed,
+                created,
+                ..Default::default()
+            };
+            // Encrypted entries and other compression methods cannot be read
+            let data = (file_type == FileType::File && !file.encrypted())
+                .then(|| match file.compression() {
+                    zip::CompressionMethod::Stored => Some(false),
+                    zip::CompressionMethod::Deflated => Some(true),
+                    _ => None,
+                })
+                .flatten()
+                .map(|deflated| Data { offset: file.data_start(), len: file.compressed_size(), deflated });
+            self.insert(path, metadata, target, data);
+        }
+        Ok(())
+    }
+
+    /// Adds an entry, filling in any of its parent directories the archive has not described
+    ///
+    /// An entry stored twice keeps its place but takes the later copy's metadata, as
+    /// extracting the archive would leave it.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The entry's path inside the archive
+    /// * `metadata` - The entry's metadata; an inode number of 0 is replaced with a new one
+    /// * `target` - Where the entry points to, if it is a symlink
+    /// * `data` - Where a regular file's contents are stored
+    fn insert(&mut self, path: PathBuf, mut metadata: Metadata, target: Option<PathBuf>, data: Option<Data>) {
+        metadata.dev = self.directory.dev;
+        if let Some(node) = self.nodes.get_mut(&path) {
+            metadata.ino = node.metadata.ino;
+            node.metadata = metadata;
+            node.target = target;
+            node.data = data;
+            return;
+        }
+        if let (Some(parent), Some(name)) = (path.parent(), path.file_name()) {
+            if !self.nodes.contains_key(parent) {
+                self.insert(parent.to_path_buf(), self.directory.clone(), None, None);
+            }
+            if let Some(parent) = self.nodes.get_mut(parent) {
+                parent.children.push(name.to_os_string());
+            }
+        }
+        if metadata.ino == 0 {
+            metadata.ino = self.nodes.len() as u64 + 1;
+        }
+        self.nodes.insert(path, Node { metadata, target, children: Vec::new(), data });
+    }
+
+    /// Finds the entry a path names inside the archive, following symlinks on the way
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path, starting with the archive's path
+    /// * `follow` - Whether a symlink at the end of the path is followed too
+    ///
+    /// # Returns
+    ///
+    /// The entry's path inside the archive, or an error if there is no such entry or a
+    /// symlink leads out of the archive
+    fn resolve(&self, path: &Path, follow: bool) -> io::Result<PathBuf> {
+        let inner = path.strip_prefix(&self.root).map_err(|_| not_found())?;
+        let mut pending = names(inner)?;
+        let mut resolved = PathBuf::new();
+        let mut links = 0;
+        while let Some(name) = pending.pop_front() {
+            if name == ".." {
+                resolved.pop();
+                continue;
+            }
+            let next = resolved.join(&name);
+            let node = self.nodes.get(&next).ok_or_else(not_found)?;
+            match &node.target {
+                Some(target) if follow || !pending.is_empty() => {
+                    links += 1;
+                    if links > MAX_LINKS {
+                        return Err(platform::error(OsError::TooManyLinks));
+                    }
+                    let mut names = names(target)?;
+                    names.extend(pending);
+                    pending = names;
+                }
+                _ => resolved = next,
+            }
+        }
+        Ok(resolved)
+    }
+
+    /// Looks up the entry a path names, following symlinks on the way
+    fn node(&self, path: &Path, follow: bool) -> io::Result<&Node> {
+        let path = self.resolve(path, follow)?;
+        self.nodes.get(&path).ok_or_else(not_found)
+    }
+}
+
+impl FileSystem for ArchiveFileSystem {
+    fn read_dir(&self, path: &Path) -> io::Result<Box<dyn Iterator<Item = io::Result<DirEntry>> + '_>> {
+        let node = self.node(path, true)?;
+        if !node.metadata.is_dir() {
+            return Err(platform::error(OsError::NotADirectory));
+        }
+        let path = path.to_path_buf();
+        Ok(Box::new(node.children.iter().map(move |name| Ok(DirEntry { name: name.clone(), path: path.join(name) }))))
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<Metadata> {
+        self.node(path, true).map(|node| node.metadata.clone())
+    }
+
+    fn symlink_metadata(&self, path: &Path) -> io::Result<Metadata> {
+        self.node(path, false).map(|node| node.metadata.clone())
+    }
+
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+        self.node(path, false)?.target.clone().ok_or_else(|| platform::error(OsError::InvalidArgument))
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        let inner = self.resolve(path, true)?;
+        Ok(if inner.as_os_str().is_empty() { self.root.clone() } else { self.root.join(inner) })
+    }
+
+    /// Reads a file's contents out of the archive, opening the archive file again
+    ///
+    /// A `.tar.gz` is decompressed once, on the first call, and its files are read out
+    /// of the decompressed bytes from then on.
+    fn open(&self, path: &Path) -> io::Result<Box<dyn FileReader>> {
+        let data = self.node(path, true)?.data.ok_or_else(|| io::Error::from(io::ErrorKind::Unsupported))?;
+        if self.format == Format::TarGz {
+            let archive = self.decompressed.get(&self.root)?;
+            let start = usize::try_from(data.offset).map_err(|_| io::Error::from(io::ErrorKind::UnexpectedEof))?;
+            let end = start.saturating_add(usize::try_from(data.len).unwrap_or(usize::MAX));
+            if end > archive.len() {
+                return Err(io::Error::from(io::ErrorKind::UnexpectedEof));
+            }
+            return Ok(Box::new(Cursor::new(Stored { archive, start, end })));
+        }
+        let mut file = BufReader::new(readonly::open_file(&self.root)?);
+        file.seek(SeekFrom::Start(data.offset))?;
+        let stored = file.take(data.len);
+        Ok(if data.deflated { Box::new(Rewindable::new(DeflateDecoder::new(stored))) } else { Box::new(Rewindable::new(stored)) })
+    }
+}
+
+/// Normalizes the name an archive stores an entry under, dropping leading `/` and `./`
+///
+/// # Returns
+///
+/// The entry's path inside the archive, or None if the name climbs out of it with `..`
+fn inner_path(name: &Path) -> Option<PathBuf> {
+    name.components()
+        .filter(|component| !matches!(component, Component::RootDir | Component::CurDir))
+        .map(|component| match component {
+            Component::Normal(name) => Some(name),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Splits a relative path into the names resolving it walks through, `..` included
+///
+/// # Returns
+///
+/// The names, or an error for an absolute path, which leads out of the archive
+fn names(path: &Path) -> io::Result<VecDeque<OsString>> {
+    path.components()
+        .filter(|component| *component != Component::CurDir)
+        .map(|component| match component {
+            Component::Normal(name) => Ok(name.to_os_string()),
+            Component::ParentDir => Ok(OsString::from("..")),
+            _ => Err(not_found()),
+        })
+        .collect()
+}
+
+/// The error a missing path gets, worded as the local file system words it
+fn not_found() -> io::Error {
+    platform::error(OsError::NotFound)
+}
+
+/// Reads a PAX time, seconds since the epoch with an optional fraction such as
+/// `1700000000.123456789`
+///
+/// # Returns
+///
+/// The time, or None if the value is malformed or before the epoch
+fn pax_time(value: &str) -> Option<SystemTime> {
+    let (seconds, fraction) = value.split_once('.').unwrap_or((value, ""));
+    if !fraction.bytes().all(|digit| digit.is_ascii_digit()) {
+        return None;
+    }
+    // Only the first nine digits fit in nanoseconds
+    let nanos = format!("{:0<9}", &fraction[..fraction.len().min(9)]).parse().ok()?;
+    Some(UNIX_EPOCH + Duration::new(seconds.parse().ok()?, nanos))
+}
+
+/// Converts an NTFS time, in 100 nanosecond ticks since 1601, to a system time
+///
+/// # Returns
+///
+/// The time, or None if it is unset or before the Unix epoch
+fn ntfs_time(ticks: u64) -> Option<SystemTime> {
+    const EPOCH_TICKS: u64 = 11_644_473_600 * 10_000_000;
+    let ticks = ticks.checked_sub(EPOCH_TICKS)?;
+    Some(UNIX_EPOCH + Duration::new(ticks / 10_000_000, (ticks % 10_000_000) as u32 * 100))
+}
+
+/// Finds the uid and gid in the Info-ZIP Unix extra field (`ux`, 0x7875) of a zip
+/// entry's extra data
+///
+/// # Returns
+///
+/// The uid and gid, or None if the field is missing or holds ids wider than 32 bits
+fn unix_owner(mut extra: &[u8]) -> Option<(u32, u32)> {
+    let id = |bytes: &[u8]| -> Option<u32> {
+        let mut padded = [0u8; 4];
+        padded.get_mut(..bytes.len())?.copy_from_slice(bytes);
+        Some(u32::from_le_bytes(padded))
+    };
+    while extra.len() >= 4 {
+        let (tag, len) = (u16::from_le_bytes([extra[0], extra[1]]), u16::from_le_bytes([extra[2], extra[3]]) as usize);
+        let data = extra.get(4..4 + len)?;
+        extra = &extra[4 + len..];
+        // Version 1, then each id preceded by its size in bytes
+        if tag != 0x7875 || data.first() != Some(&1) {
+            continue;
+        }
+        let uid_len = usize::from(*data.get(1)?);
+        let uid = id(data.get(2..2 + uid_len)?)?;
+        let gid_len = usize::from(*data.get(2 + uid_len)?);
+        let gid = id(data.get(3 + uid_len..3 + uid_len + gid_len)?)?;
+        return Some((uid, gid));
+    }
+    None
+}
+
+/// The `st_mode` file type bits of a kind of entry
+fn type_bits(file_type: FileType) -> u32 {
+    match file_type {
+        FileType::File => 0o100000,
+        FileType::Directory => 0o040000,
+        FileType::Symlink => 0o120000,
+        FileType::Fifo => 0o010000,
+        FileType::Socket => 0o140000,
+        FileType::BlockDevice => 0o060000,
+        FileType::CharDevice => 0o020000,
+    }
+}
+
+/// The kind of entry an `st_mode` describes, a regular file if its type bits are missing
+fn type_of(mode: u32) -> FileType {
+    match mode & 0o170000 {
+        0o040000 => FileType::Directory,
+        0o120000 => FileType::Symlink,
+        0o010000 => FileType::Fifo,
+        0o140000 => FileType::Socket,
+        0o060000 => FileType::BlockDevice,
+        0o020000 => FileType::CharDevice,
+        _ => FileType::File,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        checksum::{hash_file, HashAlgorithm},
+        content::{sniff, ContentKind},
+        dir_utils::{list_entries, list_files_detailed, DirContents, DirSize, FileKind, ListOptions},
+    };
+    use std::io::Write;
+    use tempfile::tempdir;
+    use zip::{
+        write::{FullFileOptions, SimpleFileOptions},
+        CompressionMethod, ZipWriter,
+    };
+
+    /// Builds a tar archive holding `docs/readme.txt`, a symlink to it and a hard link to it
+    fn write_tar(path: &Path, gzip: bool) {
+        let file = fs::File::create(path).unwrap();
+        let writer: Box<dyn Write> = if gzip { Box::new(flate2::write::GzEncoder::new(file, flate2::Compression::fast())) } else { Box::new(file) };
+        let mut builder = tar::Builder::new(writer);
+        let header = |entry_type: TarEntryType, mode: u32, size: u64| {
+            let mut header = tar::Header::new_gnu();
+            header.set_entry_type(entry_type);
+            header.set_mode(mode);
+            header.set_size(size);
+            header.set_mtime(1_000_000_000);
+            header.set_uid(1234);
+            header.set_gid(1234);
+            header
+        };
+        builder.append_data(&mut header(TarEntryType::Regular, 0o640, 5), "./docs/readme.txt", &b"hello"[..]).unwrap();
+        builder.append_link(&mut header(TarEntryType::Symlink, 0o777, 0), "latest", "docs/readme.txt").unwrap();
+        builder.append_link(&mut header(TarEntryType::Link, 0o640, 0), "copy.txt", "docs/readme.txt").unwrap();
+        builder.into_inner().unwrap().flush().unwrap();
+    }
+
+    #[test]
+    fn test_find() {
+        let temp_dir = tempdir().unwrap();
+        let archive = temp_dir.path().join("backup.tar.gz");
+        fs::write(&archive, "").unwrap();
+        fs::create_dir(temp_dir.path().join("folder.zip")).unwrap();
+
+        assert_eq!(find(&archive), Some((archive.as_path(), Format::TarGz)));
+        assert_eq!(find(&archive.join("docs/inner")), Some((archive.as_path(), Format::TarGz)));
+        assert_eq!(find(&temp_dir.path().join("folder.zip")), None);
+        assert_eq!(find(temp_dir.path()), None);
+        assert_eq!(Format::from_name(Path::new("a.TGZ")), Some(Format::TarGz));
+        assert_eq!(Format::from_name(Path::new("a.tar.xz")), None);
+        assert_eq!(inner_path(Path::new("/./docs/a.txt")), Some(PathBuf::from("docs/a.txt")));
+        assert_eq!(inner_path(Path::new("docs/../../escape")), None);
+    }
+
+    #[test]
+    fn test_tar() {
+        let temp_dir = tempdir().unwrap();
+        for (name, format) in [("backup.tar", Format::Tar), ("backup.tar.gz", Format::TarGz)] {
+            let path = temp_dir.path().join(name);
+            write_tar(&path, format == Format::TarGz);
+            let archive = ArchiveFileSystem::open(&path, format).unwrap();
+
+            let readme = archive.symlink_metadata(&path.join("docs/readme.txt")).unwrap();
+            assert_eq!((readme.mode, readme.len, readme.uid), (0o100640, 5, 1234));
+            assert_eq!(readme.modified, Some(UNIX_EPOCH + Duration::from_secs(1_000_000_000)));
+            assert_eq!(archive.metadata(&path.join("copy.txt")).unwrap().ino, readme.ino);
+            assert_eq!(readme.nlink, 2);
+            assert_eq!(archive.symlink_metadata(&path.join("latest")).unwrap().nlink, 1);
+            assert!(archive.symlink_metadata(&path.join("docs")).unwrap().is_dir());
+            assert!(archive.symlink_metadata(&temp_dir.path().join("docs")).is_err());
+
+            let mut contents = String::new();
+            archive.open(&path.join("latest")).unwrap().read_to_string(&mut contents).unwrap();
+            assert_eq!(contents, "hello");
+            assert_eq!(hash_file(&path.join("copy.txt"), HashAlgorithm::Md5, &archive).unwrap(), "5d41402abc4b2a76b9719d911017c592");
+            assert!(archive.open(&path.join("docs")).is_err());
+
+            let options = ListOptions::default().file_system(archive);
+            let entries = list_entries(&path, &options).unwrap();
+            let names: Vec<&str> = entries.iter().map(|entry| entry.name.as_str()).collect();
+            assert_eq!(names, ["copy.txt", "docs", "latest"]);
+            assert_eq!((entries[2].kind, entries[2].target_kind), (FileKind::Symlink, Some(FileKind::File)));
+
+            let files = list_files_detailed(path.join("docs"), &options).unwrap();
+            assert_eq!(files[0].path, path.join("docs/readme.txt"));
+            assert!(list_entries(path.join("latest"), &options).is_err());
+        }
+    }
+
+    #[test]
+    fn test_tar_extended_headers() {
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().join("audit.tar");
+        let mut builder = tar::Builder::new(fs::File::create(&path).unwrap());
+        // A PAX header's records override the ustar header that follows it
+        builder
+            .append_pax_extensions([
+                ("mtime", &b"1700000000.25"[..]),
+                ("atime", b"1700000100.5"),
+                ("ctime", b"1700000200"),
+                ("uid", b"4000000"),
+                ("uname", b"alice"),
+                ("gname", b"staff"),
+            ])
+            .unwrap();
+        let mut header = tar::Header::new_ustar();
+        header.set_mode(0o644);
+        header.set_size(3);
+        header.set_mtime(1);
+        header.set_uid(65534);
+        header.set_gid(65534);
+        header.set_username("nobody").unwrap();
+        builder.append_data(&mut header, "pax.txt", &b"pax"[..]).unwrap();
+        let mut header = tar::Header::new_gnu();
+        header.set_mode(0o644);
+        header.set_size(3);
+        header.set_mtime(1_000_000_000);
+        header.set_uid(1001);
+        header.set_gid(1001);
+        header.set_username("bob").unwrap();
+        header.set_groupname("wheel").unwrap();
+        let gnu = header.as_gnu_mut().unwrap();
+        gnu.set_atime(1_000_000_100);
+        gnu.set_ctime(1_000_000_200);
+        builder.append_data(&mut header, "gnu.txt", &b"gnu"[..]).unwrap();
+        builder.into_inner().unwrap().flush().unwrap();
+
+        let archive = ArchiveFileSystem::open(&path, Format::Tar).unwrap();
+        let time = |seconds: u64, nanos: u32| Some(UNIX_EPOCH + Duration::new(seconds, nanos));
+        let pax = archive.symlink_metadata(&path.join("pax.txt")).unwrap();
+        assert_eq!((pax.modified, pax.accessed, pax.changed), (time(1_700_000_000, 250_000_000), time(1_700_000_100, 500_000_000), time(1_700_000_200, 0)));
+        assert_eq!((pax.uid, pax.owner.as_deref(), pax.group.as_deref()), (4_000_000, Some("alice"), Some("staff")));
+        let gnu = archive.symlink_metadata(&path.join("gnu.txt")).unwrap();
+        assert_eq!((gnu.modified, gnu.accessed, gnu.changed), (time(1_000_000_000, 0), time(1_000_000_100, 0), time(1_000_000_200, 0)));
+
+        // The long format shows the names the archive stores, whether or not they exist here
+        let options = ListOptions::default().file_system(archive);
+        let files = list_files_detailed(path.clone(), &options).unwrap();
+        let owners: Vec<(&str, &str)> = files.iter().map(|file| (&*file.owner, &*file.group)).collect();
+        assert_eq!(owners, [("bob", "wheel"), ("alice", "staff")]);
+
+        assert_eq!(pax_time("12.5"), time(12, 500_000_000));
+        assert_eq!(pax_time("12.0000000019"), time(12, 1));
+        assert_eq!(pax_time("-1"), None);
+    }
+
+    #[test]
+    fn test_zip() {
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().join("backup.zip");
+        let mut writer = ZipWriter::new(fs::File::create(&path).unwrap());
+        let options = SimpleFileOptions::default().unix_permissions(0o755);
+        writer.start_file("bin/tool", options).unwrap();
+        writer.write_all(b"#!/bin/sh\n").unwrap();
+        writer.add_directory("empty/", SimpleFileOptions::default()).unwrap();
+        writer.add_symlink("tools", "bin", SimpleFileOptions::default()).unwrap();
+        writer.start_file("notes.txt", SimpleFileOptions::default().compression_method(CompressionMethod::Stored)).unwrap();
+        writer.write_all(b"plain\0").unwrap();
+        // Info-ZIP's Unix owner field and an extended timestamp with all three times
+        let mut options = FullFileOptions::default();
+        options.add_extra_data(0x7875, vec![1, 4, 0xe8, 0x03, 0, 0, 2, 0x64, 0].into_boxed_slice(), false).unwrap();
+        let mut times = vec![0b111];
+        [1_700_000_000u32, 1_700_000_100, 1_600_000_000].iter().for_each(|time| times.extend(time.to_le_bytes()));
+        options.add_extra_data(0x5455, times.into_boxed_slice(), false).unwrap();
+        writer.start_file("owned.txt", options).unwrap();
+        writer.finish().unwrap();
+
+        let archive = ArchiveFileSystem::open(&path, Format::Zip).unwrap();
+        let owned = archive.metadata(&path.join("owned.txt")).unwrap();
+        assert_eq!((owned.uid, owned.gid), (1000, 100));
+        let time = |seconds: u64| Some(UNIX_EPOCH + Duration::from_secs(seconds));
+        assert_eq!((owned.modified, owned.accessed, owned.created), (time(1_700_000_000), time(1_700_000_100), time(1_600_000_000)));
+        assert_eq!(ntfs_time(116_444_736_000_000_000 + 15), Some(UNIX_EPOCH + Duration::from_nanos(1500)));
+        let tool = archive.metadata(&path.join("tools/tool")).unwrap();
+        assert_eq!((tool.mode, tool.len), (0o100755, 10));
+        assert_eq!((tool.uid, tool.gid), (platform::current_uid(), platform::current_gid()));
+        assert!(tool.modified.is_some());
+        assert_eq!(archive.read_link(&path.join("tools")).unwrap(), Path::new("bin"));
+        assert_eq!(archive.canonicalize(&path.join("tools/../empty")).unwrap(), path.join("empty"));
+        assert_eq!(sniff(&path.join("tools/tool"), &archive), Some(ContentKind::Text));
+        assert_eq!(sniff(&path.join("notes.txt"), &archive), Some(ContentKind::Binary));
+
+        let options = ListOptions::default().file_system(archive);
+        let names = |dir: PathBuf| list_entries(dir, &options).unwrap().into_iter().map(|entry| entry.display_name).collect::<Vec<_>>();
+        assert_eq!(names(path.clone()), ["bin", "empty", "notes.txt", "owned.txt", "tools"]);
+        assert_eq!(names(path.join("tools")), ["tool"]);
+        assert!(names(path.join("empty")).is_empty());
+
+        let mut files = list_files_detailed(path.clone(), &options).unwrap();
+        DirSize::Entries.apply(&mut files, &*options.file_system);
+        assert_eq!(files[0].dir_contents, Some(DirContents::Entries(Some(1))));
+
+        fs::write(temp_dir.path().join("broken.zip"), "not a zip").unwrap();
+        assert!(ArchiveFileSystem::open(&temp_dir.path().join("broken.zip"), Format::Zip).is_err());
+    }
+}
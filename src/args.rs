@@ -1,15 +1,42 @@
-use clap::Parser;
+use std::{
+    ffi::{OsStr, OsString},
+    io::{self, IsTerminal},
+    path::PathBuf,
+    time::SystemTime,
+};
 
+use clap::{ArgAction, Parser, ValueEnum};
+use regex::Regex;
+
+use crate::{
+    checksum::{self, HashAlgorithm},
+    color::{ColorMode, ColorScale},
+    columns::OctalPermissions,
+    cycle::CyclePolicy,
+    dir_utils::{DirSize, DotfileOrder, ListOptions, OperandLinks, SortKey, SortSpec, TimeStyle},
+    filter::{self, EntryType, Filter, PermSpec, SizeBound, TimePreset},
+    hardlinks::HardlinkMode,
+    hyperlink::HyperlinkMode,
+    ids,
+    paths::PathStyle,
+    quoting::QuotingStyle,
+    terminal,
+};
+
+// Repeated options take their last value, letting the command line override
+// defaults from the configuration file
 #[derive(Parser)]
+#[command(args_override_self = true)]
 pub struct Args {
-    /// Paths to list (default to current directory if none provided)
+    /// Paths to list (default to current directory if none provided); zip, tar and tar.gz
+    /// archives, and directories inside them such as `backup.zip/docs`, are listed like directories
     #[arg(default_value = ".")]
-    pub paths: Vec<String>,
+    pub paths: Vec<PathBuf>,
 
     #[arg(short, long, help = "Show hidden files")]
     pub all: bool,
 
-    #[arg(short = 'A', long, help = "Do not list implied . and ..")]
+    #[arg(short = 'A', long, help = "Show hidden files, except . and ..")]
     pub almost_all: bool,
 
     #[arg(short, long, help = "Long listing format")]
@@ -18,24 +45,713 @@ pub struct Args {
     #[arg(short = 'R', long, help = "Recursive listing")]
     pub recursive: bool,
 
+    #[arg(long, help = "Recursive listing printing one full path per line, without section headers")]
+    pub flat: bool,
+
+    #[arg(long, conflicts_with = "relative_to", help = "List every entry by its absolute canonical path instead of its name")]
+    pub absolute: bool,
+
+    #[arg(long, value_name = "DIR", help = "List every entry by its path relative to DIR instead of its name")]
+    pub relative_to: Option<PathBuf>,
+
+    #[arg(long, requires = "flat", help = "In --flat mode, indent each path by its depth")]
+    pub indent_depth: bool,
+
+    #[arg(long, requires = "flat", help = "In --flat mode, prefix each path with its depth and a tab")]
+    pub depth_field: bool,
+
+    #[arg(long, value_enum, requires = "flat", default_value_t = FlatOrder::Walk, help = "In --flat mode, list paths in traversal order or sorted by depth then path")]
+    pub flat_order: FlatOrder,
+
+    #[arg(long, value_name = "N", help = "With -R or --flat, do not descend below depth N; entries directly inside a listed directory are at depth 0")]
+    pub max_depth: Option<usize>,
+
+    #[arg(long, value_name = "N", help = "With -R or --flat, only show entries at depth N or deeper")]
+    pub min_depth: Option<usize>,
+
+    #[arg(long, help = "With -R or --flat, do not descend into directories on other file systems")]
+    pub one_file_system: bool,
+
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u16).range(1..), help = "Threads used to fetch metadata, in every directory and throughout -R; without it only directories of 10,000+ entries use several threads, and 1 fetches sequentially")]
+    pub jobs: Option<u16>,
+
+    #[arg(long, value_name = "DIR", help = "Write each listed directory's section to DIR/<relative-path>.txt (.json under --json) instead of stdout")]
+    pub split_output: Option<PathBuf>,
+
+    #[arg(long, value_name = "FILE", conflicts_with = "split_output", help = "Write the listing to FILE instead of stdout, replacing it atomically")]
+    pub output_file: Option<PathBuf>,
+
+    #[arg(long, help = "Allow --output-file or --split-output to write inside a directory being listed")]
+    pub allow_self_write: bool,
+
+    #[arg(long, value_enum, default_value_t = CyclePolicy::Warn, help = "What to do when recursion reaches a directory it is already inside of")]
+    pub cycle: CyclePolicy,
+
+    #[arg(long = "type", value_enum, value_name = "TYPE", value_delimiter = ',', action = ArgAction::Append, help = "Only list entries of this type: f (file), d (directory), l (symlink) or x (executable); repeatable")]
+    pub types: Vec<EntryType>,
+
+    #[arg(long, value_name = "[+|-]SIZE", allow_hyphen_values = true, action = ArgAction::Append, help = "Only list entries larger (+SIZE), smaller (-SIZE) or exactly SIZE bytes, with optional K/M/G suffix; repeatable")]
+    pub size: Vec<SizeBound>,
+
+    #[arg(long, conflicts_with = "files_only", help = "Only list directories; shorthand for --type d")]
+    pub dirs_only: bool,
+
+    #[arg(long, help = "Only list regular files; shorthand for --type f")]
+    pub files_only: bool,
+
+    #[arg(long, value_name = "MODE", allow_hyphen_values = true, action = ArgAction::Append, help = "Only list entries whose permissions are exactly MODE (644, u=rw,go=r), include all of -MODE, or any of /MODE; repeatable")]
+    pub perm: Vec<PermSpec>,
+
+    #[arg(long, help = "Only list symlinks whose target does not exist; directories are still descended into")]
+    pub broken_links: bool,
+
+    #[arg(long = "filter", value_name = "GLOB", action = ArgAction::Append, help = "Only list entries whose name matches the shell glob GLOB; repeatable, directories are still descended into")]
+    pub name_filter: Vec<String>,
+
+    #[arg(long, value_name = "PATTERN", help = "Only list entries whose name matches the regular expression PATTERN; directories are still descended into")]
+    pub regex: Option<Regex>,
+
+    #[arg(short = 'I', long, value_name = "PATTERN", action = ArgAction::Append, help = "Do not list entries matching the shell glob PATTERN (repeatable)")]
+    pub ignore: Vec<String>,
+
+    #[arg(long, value_name = "PATTERN", action = ArgAction::Append, help = "Do not list entries matching the shell glob PATTERN, unless -a or -A is given (repeatable)")]
+    pub hide: Vec<String>,
+
+    #[arg(long, help = "Show each entry's git status (M, A, ??, I, or - when clean) in long and --flat listings")]
+    pub git: bool,
+
+    #[arg(long, help = "In the long format, show the date, hash and author of the last commit touching each entry")]
+    pub git_log: bool,
+
+    #[cfg(windows)]
+    #[arg(long, help = "In the long format, show each entry's read-only, hidden and system attributes, like -hs")]
+    pub attributes: bool,
+
+    #[cfg(target_os = "macos")]
+    #[arg(long, help = "In the long format, show each entry's BSD file flags, extended attribute count and what quarantined it")]
+    pub mac_metadata: bool,
+
+    #[arg(long, help = "Do not list entries ignored by git (.gitignore, .git/info/exclude and global excludes)")]
+    pub git_ignore: bool,
+
+    #[arg(short = 'B', long, help = "Do not list backup entries ending with ~, unless -a or -A is given")]
+    pub ignore_backups: bool,
+
     #[arg(long, help = "Human-readable sizes")]
     pub human_readable: bool,
 
+    #[arg(long, help = "With --human-readable, also show exact byte counts next to the size column")]
+    pub exact_size: bool,
+
+    #[arg(long, value_enum, value_name = "WHAT", default_value_t = DirSize::None, help = "What the size column shows for directories")]
+    pub dir_size: DirSize,
+
+    #[arg(long, value_enum, value_name = "WHERE", num_args = 0..=1, require_equals = true, default_missing_value = "alongside", help = "Show permissions as an octal mode such as 0755, alongside or instead of the rwx string")]
+    pub octal_permissions: Option<OctalPermissions>,
+
+    #[arg(long, value_enum, value_name = "STYLE", default_value_t = TimeStyle::Default, help = "How timestamps are shown in the long format")]
+    pub time_style: TimeStyle,
+
+    #[arg(long, help = "Long listing with full timestamps, like -l --time-style=full-iso")]
+    pub full_time: bool,
+
+    #[arg(long, value_name = "ZONE", help = "Show timestamps in this time zone: local, UTC, or a name like Europe/Berlin")]
+    pub time_zone: Option<String>,
+
+    #[arg(long, help = "Show when each entry was created, where the file system records it")]
+    pub created: bool,
+
+    #[arg(long, help = "Show the space each entry takes up on disk next to its size, marking sparse files")]
+    pub allocated: bool,
+
+    #[arg(long, help = "Show the mount point and type of the file system each entry lives on")]
+    pub filesystem: bool,
+
+    #[arg(long, value_enum, value_name = "ALGORITHM", help = "Show a content hash of each regular file")]
+    pub hash: Option<HashAlgorithm>,
+
+    #[arg(long, value_name = "SIZE", value_parser = checksum::parse_max_size, help = "With --hash, skip files larger than SIZE, such as 100M")]
+    pub hash_max_size: Option<u64>,
+
+    #[arg(long, help = "Show whether each regular file is text, binary or empty, judged by a NUL byte in its first 8KB")]
+    pub content_kind: bool,
+
+    #[arg(long, help = "Show image dimensions, and the running time of audio and video, read from file headers")]
+    pub media: bool,
+
+    #[arg(long, help = "Show the total size of everything inside each directory in the size column")]
+    pub total_size: bool,
+
+    #[arg(long, value_enum, value_name = "HOW", num_args = 0..=1, require_equals = true, default_missing_value = "annotate", help = "Point out entries that are hard links to the same file, optionally grouping them")]
+    pub hardlinks: Option<HardlinkMode>,
+
     #[arg(short = 'F', long, help = "Append indicator (one of */=>@|) to entries")]
     pub classify: bool,
 
-    #[arg(short = '1', help = "List one file per line")]
+    #[arg(long, help = "Print one JSON object per entry, including its indicator, color class and icon")]
+    pub json: bool,
+
+    #[arg(long, help = "Describe entry types in words instead of colors, symbols or icons, one entry per line")]
+    pub screen_reader: bool,
+
+    #[arg(short = 'p', help = "Append / to directories")]
+    pub slash_dirs: bool,
+
+    #[arg(short = 'Q', long, overrides_with_all = ["escape", "literal", "quoting_style"], help = "Enclose entry names in double quotes")]
+    pub quote_name: bool,
+
+    #[arg(short = 'b', long, overrides_with_all = ["quote_name", "literal", "quoting_style"], help = "Print C-style escapes for special characters in names")]
+    pub escape: bool,
+
+    #[arg(short = 'N', long, overrides_with_all = ["quote_name", "escape", "quoting_style"], help = "Print names as they are, without quoting")]
+    pub literal: bool,
+
+    #[arg(long, value_enum, value_name = "STYLE", overrides_with_all = ["quote_name", "escape", "literal"], help = "Quote names in this style")]
+    pub quoting_style: Option<QuotingStyle>,
+
+    #[arg(short = 'G', long, help = "In the long format, do not show the group column")]
+    pub no_group: bool,
+
+    #[arg(short = '1', overrides_with_all = ["across", "commas"], help = "List one file per line (the default when stdout is not a terminal)")]
     pub one_per_line: bool,
 
+    #[arg(short = 'C', overrides_with_all = ["one_per_line", "commas"], help = "List entries across the line (the default on a terminal)")]
+    pub across: bool,
+
+    #[arg(short = 'm', overrides_with_all = ["one_per_line", "across"], help = "List entries separated by commas")]
+    pub commas: bool,
+
+    #[arg(short = 'w', long, value_name = "COLS", help = "Wrap lines at this many columns instead of the terminal width (0 means no limit)")]
+    pub width: Option<usize>,
+
+    #[arg(short = 'H', long, overrides_with_all = ["dereference", "no_dereference"], help = "Follow symlinks given on the command line, even in the long format and with -F")]
+    pub dereference_command_line: bool,
+
+    #[arg(short = 'L', long, overrides_with_all = ["dereference_command_line", "no_dereference"], help = "Show the metadata of symlink targets instead of the links, and descend into symlinked directories")]
+    pub dereference: bool,
+
+    #[arg(short = 'P', long, overrides_with_all = ["dereference_command_line", "dereference"], help = "Never follow symlinks below the listed paths (the default)")]
+    pub no_dereference: bool,
+
+    #[arg(long, help = "In the long format, show the size, permissions and times of symlink targets while still listing the links and their arrows")]
+    pub dereference_target_info: bool,
+
     #[arg(short = 't', help = "Sort by modification time, newest first")]
     pub sort_time: bool,
 
     #[arg(short = 'S', help = "Sort by file size, largest first")]
     pub sort_size: bool,
 
+    #[arg(short = 'X', help = "Sort alphabetically by extension (like --sort=extension)")]
+    pub sort_extension: bool,
+
+    #[arg(short = 'v', help = "Natural sort of version numbers within names (like --sort=version)")]
+    pub sort_version: bool,
+
     #[arg(short = 'r', long, help = "Reverse order while sorting")]
     pub reverse: bool,
 
+    #[arg(short = 'f', help = "List all entries in directory order without classification (like -aU)")]
+    pub unsorted_all: bool,
+
+    #[arg(long, value_enum, value_delimiter = ',', action = ArgAction::Set, help = "Sort by the given keys in priority order (e.g. extension,size)")]
+    pub sort: Vec<SortKey>,
+
+    #[arg(long, help = "Seed for --sort=random and --sample, making them reproducible")]
+    pub seed: Option<u64>,
+
+    #[arg(long, value_enum, default_value_t = DotfileOrder::Mixed, help = "Place hidden entries first, last, or mixed in with the rest")]
+    pub dotfiles: DotfileOrder,
+
+    #[arg(long, value_name = "N", help = "List a uniform random sample of N entries from each directory, with the total count")]
+    pub sample: Option<usize>,
+
+    #[arg(long, value_name = "N", help = "List only the first N entries of each directory, after sorting, noting how many were left out")]
+    pub limit: Option<usize>,
+
     #[arg(short = 'U', help = "Do not sort; list entries in directory order")]
     pub unsorted: bool,
+
+    #[arg(long, group = "time_preset", help = "Only list entries modified since local midnight")]
+    pub today: bool,
+
+    #[arg(long, group = "time_preset", help = "Only list entries modified since Monday of this week")]
+    pub this_week: bool,
+
+    #[arg(long, group = "time_preset", help = "Only list entries modified since the first of this month")]
+    pub this_month: bool,
+
+    #[arg(long, value_name = "TIME", value_parser = filter::parse_time_bound, help = "Only list entries modified after TIME: a duration ago such as 2d or \"3 weeks\", or a date such as 2024-01-31")]
+    pub newer_than: Option<SystemTime>,
+
+    #[arg(long, value_name = "TIME", value_parser = filter::parse_time_bound, help = "Only list entries modified before TIME: a duration ago such as 2d or \"3 weeks\", or a date such as 2024-01-31")]
+    pub older_than: Option<SystemTime>,
+
+    #[arg(long, value_name = "MS", default_value_t = ids::DEFAULT_TIMEOUT.as_millis() as u64, help = "Show numeric owners and groups once a name lookup takes longer than this many milliseconds")]
+    pub nss_timeout: u64,
+
+    #[arg(long, help = "Print the optional features this build supports as JSON and exit")]
+    pub capabilities: bool,
+
+    #[arg(long, help = "Print entry counts and age/size histograms after each listing")]
+    pub stats: bool,
+
+    #[arg(long, help = "Report broken symlinks instead of listing, exiting non-zero if any are found")]
+    pub check_links: bool,
+
+    #[arg(long, help = "Like --check-links, also reporting links that resolve outside the listed path")]
+    pub check_links_escaping: bool,
+
+    #[arg(long, conflicts_with_all = ["output_file", "split_output"], help = "Guarantee no writes or access-time updates, failing on directories where atime cannot be preserved; rejects --output-file and --split-output")]
+    pub assert_readonly: bool,
+
+    #[arg(long, value_name = "NAME", help = "Show the long format columns of a preset from the config file")]
+    pub preset: Option<String>,
+
+    #[arg(long, value_enum, value_name = "WHEN", default_value_t = ColorMode::Auto, num_args = 0..=1, default_missing_value = "always", help = "Color the output")]
+    pub color: ColorMode,
+
+    #[arg(long, help = "Show an icon (requires a Nerd Font) before each file name")]
+    pub icons: bool,
+
+    #[arg(long, value_enum, value_name = "WHEN", default_value_t = HyperlinkMode::Never, num_args = 0..=1, default_missing_value = "always", help = "Link file names to their file:// URIs with OSC 8 hyperlinks")]
+    pub hyperlink: HyperlinkMode,
+
+    #[arg(long, value_enum, value_delimiter = ',', action = ArgAction::Set, help = "Color values along a gradient instead of a fixed color")]
+    pub color_scale: Vec<ColorScale>,
+
+    #[arg(long, help = "Also apply --color-scale gradients to file names")]
+    pub color_scale_names: bool,
+
+    #[arg(long, help = "Do not highlight owners and groups other than the current user's in color mode")]
+    pub no_highlight_foreign: bool,
+
+    #[arg(long, value_name = "FILE", help = "Load colors from this theme file instead of theme.toml in the config directory")]
+    pub theme: Option<PathBuf>,
+
+    #[arg(long, help = "Guarantee pure-ASCII output, escaping other characters as octal bytes and leaving out icons")]
+    pub ascii: bool,
+
+    #[arg(short = 'q', long, overrides_with = "show_control_chars", help = "Print ? instead of control characters in names (the default when stdout is a terminal)")]
+    pub hide_control_chars: bool,
+
+    #[arg(long, overrides_with = "hide_control_chars", help = "Print control characters in names as they are")]
+    pub show_control_chars: bool,
+
+    #[arg(long, help = "Align owner and group columns across all listed sections")]
+    pub uniform_columns: bool,
+
+    #[arg(long, help = "Show scan progress in the terminal title during recursive listings")]
+    pub osc_progress: bool,
+
+    #[arg(long, help = "Ignore .lsoxide.toml files in listed directories")]
+    pub no_dir_config: bool,
+
+    /// The program name plus config defaults, and the actual command line, kept
+    /// so per-directory overrides can be layered between them
+    #[arg(skip)]
+    layers: Option<(Vec<OsString>, Vec<OsString>)>,
+
+    /// The width short listings wrap at, resolved from `--width`, `COLUMNS` or the
+    /// terminal; None if lines are not wrapped
+    #[arg(skip)]
+    pub line_width: Option<usize>,
+}
+
+/// Order of the paths printed by `--flat`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum FlatOrder {
+    /// As the tree is walked, each directory's entries right before its subdirectories' entries
+    #[default]
+    Walk,
+    /// Shallow entries first, sorted by path within each depth
+    Depth,
+}
+
+impl Args {
+    /// Parses the command line on top of default flags, exiting on error like `Args::parse`
+    ///
+    /// # Arguments
+    ///
+    /// * `base` - The program name followed by default flags from the config file
+    /// * `command_line` - The arguments given on the command line
+    pub fn parse_layered(base: Vec<OsString>, command_line: Vec<OsString>) -> Args {
+        let mut args = Args::parse_from(base.iter().chain(&command_line));
+        args.layers = Some((base, command_line));
+        args
+    }
+
+    /// Re-parses the arguments with extra flags slotted in between the defaults and the
+    /// command line, so the command line still takes precedence
+    ///
+    /// # Arguments
+    ///
+    /// * `flags` - Long flags such as those from a directory's `.lsoxide.toml`
+    ///
+    /// # Returns
+    ///
+    /// The combined arguments, with implied flags resolved, or the parse error
+    pub fn with_overrides(&self, flags: &[String]) -> Result<Args, clap::Error> {
+        let (base, command_line) = self
+            .layers
+            .clone()
+            .unwrap_or_else(|| (vec![OsString::from(env!("CARGO_PKG_NAME"))], Vec::new()));
+        let argv = base.iter().cloned().chain(flags.iter().map(OsString::from)).chain(command_line.iter().cloned());
+        let mut args = Args::try_parse_from(argv)?;
+        args.layers = Some((base, command_line));
+        args.resolve_implied_flags();
+        Ok(args)
+    }
+
+    /// Expands flags that imply other flags, such as `-f` implying `-aU`
+    pub fn resolve_implied_flags(&mut self) {
+        // Detect the terminal once rather than for every entry
+        if self.hyperlink == HyperlinkMode::Auto {
+            self.hyperlink = if self.hyperlink.enabled() { HyperlinkMode::Always } else { HyperlinkMode::Never };
+        }
+        let terminal = io::stdout().is_terminal();
+        self.line_width = terminal::width(self.width);
+        if !self.show_control_chars && terminal {
+            self.hide_control_chars = true;
+        }
+        // Like GNU ls, pipes and files get one entry per line unless a layout was asked for
+        if !terminal && !self.one_per_line && !self.across && !self.commas {
+            self.one_per_line = true;
+        }
+        if self.flat {
+            self.recursive = true;
+        }
+        if self.full_time {
+            self.long = true;
+            self.time_style = TimeStyle::FullIso;
+        }
+        if self.screen_reader {
+            self.one_per_line = true;
+            self.across = false;
+            self.commas = false;
+            self.classify = false;
+            self.icons = false;
+            self.color = ColorMode::Never;
+        }
+        // Icons are Nerd Font glyphs, which pure-ASCII output cannot show
+        if self.ascii {
+            self.icons = false;
+        }
+        if self.unsorted_all {
+            self.all = true;
+            self.unsorted = true;
+            self.classify = false;
+            self.color = ColorMode::Never;
+        }
+    }
+
+    /// The quoting style picked by whichever of `-Q`, `-b`, `-N` and `--quoting-style` came last
+    pub fn quoting_style(&self) -> QuotingStyle {
+        if self.quote_name {
+            QuotingStyle::C
+        } else if self.escape {
+            QuotingStyle::Escape
+        } else {
+            self.quoting_style.unwrap_or_default()
+        }
+    }
+
+    /// How entries are listed by path under `--absolute` or `--relative-to`, or None to list names
+    pub fn path_style(&self) -> Option<PathStyle> {
+        if self.absolute {
+            Some(PathStyle::Absolute)
+        } else {
+            self.relative_to.clone().map(PathStyle::RelativeTo)
+        }
+    }
+
+    /// Checks whether the short listing prints names as read, with no classification or
+    /// decoration that would need each entry's metadata or path
+    pub fn plain_names(&self) -> bool {
+        !self.classify
+            && !self.slash_dirs
+            && !self.icons
+            && !self.screen_reader
+            && !self.color.enabled()
+            && !self.hyperlink.enabled()
+            && self.hardlinks.is_none()
+    }
+
+    /// Which symlinks given on the command line are followed
+    ///
+    /// Like GNU ls, links to directories are followed so their entries are listed,
+    /// except in the long format and with `-F`, which describe the links themselves
+    /// unless `-H` or `-L` is given.
+    pub fn operand_links(&self) -> OperandLinks {
+        if self.dereference_command_line || self.dereference {
+            OperandLinks::Always
+        } else if self.long || self.classify {
+            OperandLinks::Never
+        } else {
+            OperandLinks::Directories
+        }
+    }
+
+    /// Builds the sort spec from the sorting flags
+    ///
+    /// Sort keys come from `--sort`, falling back to `-t`/`-S`/`-X`/`-v`; no keys means sort by name.
+    pub fn sort_spec(&self) -> SortSpec {
+        let keys = if !self.sort.is_empty() {
+            self.sort.clone()
+        } else if self.sort_time {
+            vec![SortKey::Time]
+        } else if self.sort_size {
+            vec![SortKey::Size]
+        } else if self.sort_extension {
+            vec![SortKey::Extension]
+        } else if self.sort_version {
+            vec![SortKey::Version]
+        } else {
+            Vec::new()
+        };
+
+        SortSpec {
+            keys,
+            reverse: self.reverse,
+            dotfiles: self.dotfiles,
+            unsorted: self.unsorted,
+            seed: self.seed,
+        }
+    }
+
+    /// Builds the entry filter from the filtering flags
+    pub fn filter(&self) -> Filter {
+        let preset = if self.today {
+            Some(TimePreset::Today)
+        } else if self.this_week {
+            Some(TimePreset::ThisWeek)
+        } else if self.this_month {
+            Some(TimePreset::ThisMonth)
+        } else {
+            None
+        };
+
+        // Like GNU ls, --hide only applies while hidden entries are hidden
+        let mut ignore = self.ignore.clone();
+        if !self.all && !self.almost_all {
+            ignore.extend(self.hide.iter().cloned());
+            if self.ignore_backups {
+                ignore.push("*~".to_string());
+            }
+        }
+
+        let mut types = self.types.clone();
+        if self.dirs_only {
+            types.push(EntryType::Directory);
+        }
+        if self.files_only {
+            types.push(EntryType::File);
+        }
+
+        Filter {
+            modified_after: preset.map(|preset| preset.start()).max(self.newer_than),
+            modified_before: self.older_than,
+            ignore,
+            git_ignore: self.git_ignore,
+            types,
+            size: self.size.clone(),
+            perms: self.perm.clone(),
+            broken_links: self.broken_links,
+            names: self.name_filter.clone(),
+            regex: self.regex.clone(),
+        }
+    }
+
+    /// Gathers what a directory read needs from the hidden-entry, classify, sorting,
+    /// filtering and symlink flags
+    pub fn list_options(&self) -> ListOptions {
+        ListOptions::default()
+            .all(self.all)
+            .almost_all(self.almost_all)
+            .classify(self.classify)
+            .sort(self.sort_spec())
+            .filter(self.filter())
+            .dereference(self.dereference)
+            .target_info(self.dereference_target_info)
+    }
+}
+
+/// Long options whose separate value may itself start with `-`, such as a glob or `--size -1k`
+const HYPHEN_VALUE_LONG_OPTIONS: &[&str] = &["--ignore", "--hide", "--size", "--perm"];
+
+/// GNU `ls` short options this tool does not implement, with the GNU long flag
+/// they stand for and, where one exists, what to use instead
+const UNSUPPORTED_SHORT_OPTIONS: &[(char, &str, Option<&str>)] = &[
+    ('c', "--time=ctime", Some("--preset with the ctime column")),
+    ('d', "--directory", None),
+    ('D', "--dired", None),
+    ('g', "-l without owner", Some("--preset without the owner column")),
+    ('i', "--inode", Some("--preset with the inode column")),
+    ('k', "--kibibytes", None),
+    ('n', "--numeric-uid-gid", None),
+    ('o', "-l without group", Some("-lG")),
+    ('s', "--size", Some("--stats")),
+    ('T', "--tabsize", None),
+    ('u', "--time=atime", Some("--preset with the atime column")),
+    ('x', "--format=across", Some("-C, which fills each line in turn")),
+    ('Z', "--context", None),
+];
+
+/// Rejects GNU `ls` short options that are not implemented, naming the long flag they
+/// stand for, so habitual keystrokes fail with a useful message instead of a bare usage error
+///
+/// # Arguments
+///
+/// * `args` - The command line arguments, without the program name
+///
+/// # Returns
+///
+/// An error message for the first unsupported short option found before `--`
+pub fn check_short_options<I, S>(args: I) -> Result<(), String>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<OsStr>,
+{
+    // Set when the previous argument was an option whose value comes next, such as `-I`
+    let mut value_next = false;
+    for arg in args {
+        let Some(arg) = arg.as_ref().to_str() else {
+            continue;
+        };
+        if std::mem::take(&mut value_next) {
+            continue;
+        }
+        if arg == "--" {
+            break;
+        }
+        if HYPHEN_VALUE_LONG_OPTIONS.contains(&arg) {
+            value_next = true;
+            continue;
+        }
+        let Some(cluster) = arg.strip_prefix('-').filter(|rest| !rest.starts_with('-')) else {
+            continue;
+        };
+        for (at, option) in cluster.char_indices() {
+            if option == 'I' {
+                // The rest of the cluster, or else the next argument, is the pattern
+                value_next = at + 1 == cluster.len();
+                break;
+            }
+            if let Some(&(_, gnu, instead)) = UNSUPPORTED_SHORT_OPTIONS.iter().find(|&&(short, _, _)| short == option) {
+                return Err(match instead {
+                    Some(instead) => format!("option -{} (GNU {}) is not supported; try {}", option, gnu, instead),
+                    None => format!("option -{} (GNU {}) is not supported", option, gnu),
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_short_options() {
+        assert_eq!(check_short_options(["-la", "src"]), Ok(()));
+        assert_eq!(check_short_options(["--", "-d"]), Ok(()));
+        assert_eq!(check_short_options(["--dotfiles=first"]), Ok(()));
+        assert_eq!(check_short_options(["-Ibuild", "-I", "-d*", "--hide", "-b", "--size", "-1k"]), Ok(()));
+        assert_eq!(
+            check_short_options(["-ld"]),
+            Err("option -d (GNU --directory) is not supported".to_string())
+        );
+        assert_eq!(
+            check_short_options(["-i"]),
+            Err("option -i (GNU --inode) is not supported; try --preset with the inode column".to_string())
+        );
+        assert_eq!(
+            check_short_options(["-lx"]),
+            Err("option -x (GNU --format=across) is not supported; try -C, which fills each line in turn".to_string())
+        );
+    }
+
+    #[test]
+    fn test_ignore_backups() {
+        let args = Args::parse_from(["ls_oxide", "-B"]);
+        assert!(!args.filter().matches_name("notes.txt~"));
+        assert!(args.filter().matches_name("notes.txt"));
+
+        let args = Args::parse_from(["ls_oxide", "-Ba"]);
+        assert!(args.filter().matches_name("notes.txt~"));
+    }
+
+    #[test]
+    fn test_type_shorthands() {
+        assert_eq!(Args::parse_from(["ls_oxide", "--dirs-only"]).filter().types, [EntryType::Directory]);
+        assert_eq!(Args::parse_from(["ls_oxide", "--files-only", "--type", "l"]).filter().types, [EntryType::Symlink, EntryType::File]);
+        assert!(Args::try_parse_from(["ls_oxide", "--dirs-only", "--files-only"]).is_err());
+    }
+
+    #[test]
+    fn test_operand_links() {
+        assert_eq!(Args::parse_from(["ls_oxide"]).operand_links(), OperandLinks::Directories);
+        assert_eq!(Args::parse_from(["ls_oxide", "-l"]).operand_links(), OperandLinks::Never);
+        assert_eq!(Args::parse_from(["ls_oxide", "-F"]).operand_links(), OperandLinks::Never);
+        assert_eq!(Args::parse_from(["ls_oxide", "-lH"]).operand_links(), OperandLinks::Always);
+        assert_eq!(Args::parse_from(["ls_oxide", "-lL"]).operand_links(), OperandLinks::Always);
+        assert_eq!(Args::parse_from(["ls_oxide", "-lHP"]).operand_links(), OperandLinks::Never);
+    }
+
+    #[test]
+    fn test_screen_reader_disables_visual_cues() {
+        let mut args = Args::parse_from(["ls_oxide", "--screen-reader", "-CF", "--icons", "--color=always"]);
+        args.resolve_implied_flags();
+        assert!(args.one_per_line && !args.across);
+        assert!(!args.classify && !args.icons);
+        assert!(!args.color.enabled());
+    }
+
+    #[test]
+    fn test_ascii_disables_icons() {
+        let mut args = Args::parse_from(["ls_oxide", "--ascii", "--icons"]);
+        args.resolve_implied_flags();
+        assert!(!args.icons);
+    }
+
+    #[test]
+    fn test_assert_readonly_rejects_writing_output() {
+        for flag in ["--output-file=listing.txt", "--split-output=sections"] {
+            let err = Args::try_parse_from(["ls_oxide", "--assert-readonly", flag]).err().expect("writing output should be rejected");
+            assert_eq!(err.exit_code(), 2);
+        }
+        assert!(Args::try_parse_from(["ls_oxide", "--assert-readonly"]).is_ok());
+    }
+
+    #[test]
+    fn test_with_overrides() {
+        let args = Args::parse_layered(
+            vec![OsString::from("ls_oxide"), OsString::from("--sort=size")],
+            vec![OsString::from("-r")],
+        );
+        let overridden = args.with_overrides(&["--all".to_string(), "--sort=time".to_string()]).unwrap();
+        assert!(overridden.all && overridden.reverse);
+        assert_eq!(overridden.sort, vec![SortKey::Time]);
+
+        let args = Args::parse_layered(vec![OsString::from("ls_oxide")], vec![OsString::from("--sort=name")]);
+        let overridden = args.with_overrides(&["--sort=time".to_string()]).unwrap();
+        assert_eq!(overridden.sort, vec![SortKey::Name]);
+    }
+
+    #[test]
+    fn test_full_time() {
+        let mut args = Args::parse_from(["ls_oxide", "--full-time"]);
+        args.resolve_implied_flags();
+        assert!(args.long);
+        assert_eq!(args.time_style, TimeStyle::FullIso);
+    }
+
+    #[test]
+    fn test_layout_flags_override_each_other() {
+        let args = Args::parse_from(["ls_oxide", "-1", "-m"]);
+        assert!(args.commas && !args.one_per_line);
+        let args = Args::parse_from(["ls_oxide", "-m", "-C"]);
+        assert!(args.across && !args.commas);
+    }
 }
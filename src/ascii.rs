@@ -0,0 +1,140 @@
+use std::{borrow::Cow, ffi::OsStr};
+
+use crate::dir_utils::FileInfo;
+
+/// Escapes every non-ASCII character as backslash-octal UTF-8 bytes (e.g. `é` becomes `\303\251`)
+///
+/// # Arguments
+///
+/// * `text` - The text to make ASCII-only
+///
+/// # Returns
+///
+/// The text unchanged if it is already ASCII, otherwise an escaped copy
+pub fn escape(text: &str) -> Cow<'_, str> {
+    if text.is_ascii() {
+        return Cow::Borrowed(text);
+    }
+
+    let mut escaped = String::with_capacity(text.len() * 2);
+    for c in text.chars() {
+        if c.is_ascii() {
+            escaped.push(c);
+        } else {
+            let mut bytes = [0; 4];
+            for byte in c.encode_utf8(&mut bytes).bytes() {
+                escaped.push_str(&format!("\\{:03o}", byte));
+            }
+        }
+    }
+    Cow::Owned(escaped)
+}
+
+/// Converts a file name or path to text, escaping bytes that are not valid UTF-8 as
+/// backslash-octal (e.g. the Latin-1 `é` byte becomes `\351`) instead of replacing them
+///
+/// # Arguments
+///
+/// * `text` - The name or path as stored by the file system
+///
+/// # Returns
+///
+/// The text unchanged if it is valid UTF-8, otherwise an escaped copy
+pub fn os_text(text: &OsStr) -> Cow<'_, str> {
+    if let Some(text) = text.to_str() {
+        return Cow::Borrowed(text);
+    }
+
+    let mut escaped = String::with_capacity(text.len() * 2);
+    for chunk in text.as_encoded_bytes().utf8_chunks() {
+        escaped.push_str(chunk.valid());
+        for byte in chunk.invalid() {
+            escaped.push_str(&format!("\\{:03o}", byte));
+        }
+    }
+    Cow::Owned(escaped)
+}
+
+/// Replaces control characters such as newlines and escapes with `?`, so a name cannot
+/// break the layout or drive the terminal
+///
+/// # Arguments
+///
+/// * `text` - The name or path to print
+///
+/// # Returns
+///
+/// The text unchanged if it has no control characters, otherwise a copy with them replaced
+pub fn hide_control(text: &str) -> Cow<'_, str> {
+    if !text.chars().any(char::is_control) {
+        return Cow::Borrowed(text);
+    }
+    Cow::Owned(text.chars().map(|c| if c.is_control() { '?' } else { c }).collect())
+}
+
+/// Replaces control characters, and bytes that are not valid UTF-8, with `?` in a name
+/// as stored by the file system, as `-q` prints it
+///
+/// # Arguments
+///
+/// * `text` - The name or path to print
+///
+/// # Returns
+///
+/// The text unchanged if it is valid UTF-8 without control characters, otherwise a copy
+/// with them replaced
+pub fn hide_unprintable(text: &OsStr) -> Cow<'_, str> {
+    if let Some(text) = text.to_str() {
+        return hide_control(text);
+    }
+
+    let mut hidden = String::with_capacity(text.len());
+    for chunk in text.as_encoded_bytes().utf8_chunks() {
+        hidden.push_str(&hide_control(chunk.valid()));
+        hidden.extend(chunk.invalid().iter().map(|_| '?'));
+    }
+    Cow::Owned(hidden)
+}
+
+/// Escapes the owner and group names of a long format entry, before they are padded
+/// to a shared width; the other cells are escaped as they are rendered, and the name
+/// and link target as they are quoted
+pub fn escape_file_info(file: &mut FileInfo) {
+    for field in [&mut file.owner, &mut file.group] {
+        if let Cow::Owned(escaped) = escape(field) {
+            *field = escaped.into();
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::ffi::OsStrExt;
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape("plain.txt"), "plain.txt");
+        assert_eq!(escape("café"), "caf\\303\\251");
+        assert!(escape("日本…").is_ascii());
+    }
+
+    #[test]
+    fn test_hide_control() {
+        assert_eq!(hide_control("plain.txt"), "plain.txt");
+        assert_eq!(hide_control("two\nlines\t\u{1b}[31m"), "two?lines??[31m");
+        assert_eq!(hide_control("café\u{85}"), "café?");
+    }
+
+    #[test]
+    fn test_hide_unprintable() {
+        assert_eq!(hide_unprintable(OsStr::new("two\nlines")), "two?lines");
+        assert_eq!(hide_unprintable(OsStr::from_bytes(b"caf\xe9\t.txt")), "caf??.txt");
+    }
+
+    #[test]
+    fn test_os_text() {
+        assert_eq!(os_text(OsStr::new("café")), "café");
+        assert_eq!(os_text(OsStr::from_bytes(b"caf\xe9.txt")), "caf\\351.txt");
+    }
+}
@@ -0,0 +1,137 @@
+use std::{
+    fs, io,
+    path::Path,
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    dir_utils::{self, FileKind, ListOptions},
+    progress::format_count,
+};
+
+/// Shape of the synthetic tree and how often to list it
+#[derive(clap::Parser, Debug, Clone)]
+#[command(name = "ls_oxide_bench", about = "Generate synthetic directory trees and measure listing throughput")]
+pub struct BenchArgs {
+    #[arg(long, default_value_t = 1000, help = "Files in every directory of the tree")]
+    pub files: usize,
+
+    #[arg(long, default_value_t = 3, help = "Subdirectories in every directory above the deepest level")]
+    pub dirs: usize,
+
+    #[arg(long, default_value_t = 2, help = "Levels of subdirectories below the root")]
+    pub depth: usize,
+
+    #[arg(long, default_value_t = 3, help = "Times to list the tree per code path; the fastest run is reported")]
+    pub runs: usize,
+
+    #[arg(long, help = "Threads for the parallel runs, compared against a serial run (default: available CPUs)")]
+    pub jobs: Option<usize>,
+}
+
+/// A listing code path that can be benchmarked, returning how many entries it listed
+type Walk = fn(&Path) -> usize;
+
+/// The listing code paths measured by the benchmark
+const WALKS: &[(&str, Walk)] = &[("short", walk_short), ("long", walk_long)];
+
+/// Generates a synthetic tree in a temporary directory and reports listing throughput
+///
+/// # Arguments
+///
+/// * `params` - Shape of the tree and number of runs
+///
+/// # Returns
+///
+/// The report, one line per code path listed serially and with `params.jobs` threads,
+/// or an error if the tree cannot be created
+pub fn run(params: &BenchArgs) -> io::Result<String> {
+    let root = tempfile::tempdir()?;
+    let generated = generate(root.path(), params, 0)?;
+    let mut report = format!(
+        "generated {} entries ({} files per directory, {} subdirectories per level, depth {})\n",
+        generated, params.files, params.dirs, params.depth
+    );
+
+    let jobs = params.jobs.unwrap_or_else(|| thread::available_parallelism().map_or(1, usize::from)).max(2);
+    for &(name, walk) in WALKS {
+        // Serial first, since the thread pool cannot be shrunk once started
+        for threads in [1, jobs] {
+            dir_utils::set_jobs(threads);
+            let mut best = Duration::MAX;
+            let mut listed = 0;
+            for _ in 0..params.runs.max(1) {
+                let start = Instant::now();
+                listed = walk(root.path());
+                best = best.min(start.elapsed());
+            }
+            let rate = (listed as f64 / best.as_secs_f64().max(f64::EPSILON)) as u64;
+            let mode = if threads == 1 { "serial".to_string() } else { format!("{} jobs", threads) };
+            report.push_str(&format!(
+                "{:<6} {:<8} {} entries in {:.1} ms ({} entries/s)\n",
+                name,
+                mode,
+                listed,
+                best.as_secs_f64() * 1000.0,
+                format_count(rate)
+            ));
+        }
+    }
+    Ok(report)
+}
+
+/// Fills a directory with empty files and subdirectories, recursing until `params.depth`
+///
+/// # Returns
+///
+/// The number of entries created under `dir`
+fn generate(dir: &Path, params: &BenchArgs, level: usize) -> io::Result<usize> {
+    for i in 0..params.files {
+        fs::File::create(dir.join(format!("file{}.txt", i)))?;
+    }
+    let mut created = params.files;
+    if level < params.depth {
+        for i in 0..params.dirs {
+            let sub = dir.join(format!("dir{}", i));
+            fs::create_dir(&sub)?;
+            created += 1 + generate(&sub, params, level + 1)?;
+        }
+    }
+    Ok(created)
+}
+
+/// Lists a tree the way the short recursive listing does
+fn walk_short(dir: &Path) -> usize {
+    let entries = dir_utils::list_entries(dir, &ListOptions::default().all(true).almost_all(true)).unwrap_or_default();
+    entries.len()
+        + entries
+            .iter()
+            .filter(|entry| entry.kind == FileKind::Directory)
+            .map(|entry| walk_short(&entry.path))
+            .sum::<usize>()
+}
+
+/// Lists a tree gathering the full long format metadata
+fn walk_long(dir: &Path) -> usize {
+    let files = dir_utils::list_files_detailed(dir, &ListOptions::default().all(true).almost_all(true)).unwrap_or_default();
+    files.len() + files.iter().filter(|file| file.is_dir).map(|file| walk_long(&file.path)).sum::<usize>()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generated_tree_is_fully_listed() {
+        let params = BenchArgs { files: 4, dirs: 2, depth: 2, runs: 1, jobs: None };
+        let root = tempfile::tempdir().expect("Unable to create temporary directory");
+        let generated = generate(root.path(), &params, 0).expect("Unable to generate tree");
+
+        // 4 files per directory in 1 + 2 + 4 directories, plus the 6 subdirectories
+        assert_eq!(generated, 4 * 7 + 6);
+        for &(_, walk) in WALKS {
+            assert_eq!(walk(root.path()), generated);
+        }
+    }
+}
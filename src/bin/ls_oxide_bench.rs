@@ -0,0 +1,16 @@
+//! Generates synthetic directory trees and measures how fast `ls_oxide` lists them,
+//! serially and on several threads
+
+use clap::Parser;
+use ls_oxide::bench::{self, BenchArgs};
+
+fn main() {
+    let params = BenchArgs::parse();
+    match bench::run(&params) {
+        Ok(report) => print!("{}", report),
+        Err(err) => {
+            eprintln!("ls_oxide_bench: {}", err);
+            std::process::exit(2);
+        }
+    }
+}
@@ -0,0 +1,39 @@
+/// Optional features and whether this build supports them, as reported by `--capabilities`
+pub const CAPABILITIES: &[(&str, bool)] = &[
+    ("git", true),
+    ("remote", cfg!(feature = "s3")),
+    ("xattrs", cfg!(target_os = "macos")),
+    ("icons", true),
+    ("windows-acl", cfg!(windows)),
+    ("noatime", cfg!(target_os = "linux")),
+];
+
+/// Renders the capability report as a single-line JSON object
+///
+/// # Returns
+///
+/// JSON such as `{"version":"0.1.0","features":{"git":false,...}}`
+pub fn report() -> String {
+    let features: Vec<String> = CAPABILITIES
+        .iter()
+        .map(|(name, supported)| format!("\"{}\":{}", name, supported))
+        .collect();
+    format!(
+        "{{\"version\":\"{}\",\"features\":{{{}}}}}",
+        env!("CARGO_PKG_VERSION"),
+        features.join(",")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report() {
+        let report = report();
+        assert!(report.starts_with(&format!("{{\"version\":\"{}\",\"features\":{{", env!("CARGO_PKG_VERSION"))));
+        assert!(report.contains(&format!("\"remote\":{}", cfg!(feature = "s3"))));
+        assert!(report.ends_with("}}"));
+    }
+}
@@ -0,0 +1,107 @@
+use std::{
+    io::{self, Read},
+    path::Path,
+};
+
+use clap::ValueEnum;
+use md5::Md5;
+use rayon::iter::{IntoParallelRefMutIterator, ParallelIterator};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    dir_utils::{parse_size, FileInfo, FileKind},
+    file_system::FileSystem,
+};
+
+/// The hash `--hash` shows for each regular file
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum HashAlgorithm {
+    Sha256,
+    Md5,
+    Blake3,
+}
+
+/// Fills in the hash column for every regular file, hashing files in parallel
+///
+/// Files larger than `max_size` show `-`, and files that cannot be read show `?`.
+/// Other entries are left blank.
+///
+/// # Arguments
+///
+/// * `files` - The long format entries to update
+/// * `algorithm` - The hash to compute
+/// * `max_size` - The largest file to hash, in bytes, if there is a limit
+/// * `file_system` - Where the files are read from
+pub fn apply(files: &mut [FileInfo], algorithm: HashAlgorithm, max_size: Option<u64>, file_system: &dyn FileSystem) {
+    files
+        .par_iter_mut()
+        .filter(|file| matches!(file.kind, FileKind::File | FileKind::Executable))
+        .for_each(|file| {
+            file.hash = Some(if max_size.is_some_and(|max| file.file_size > max) {
+                "-".to_string()
+            } else {
+                hash_file(&file.path, algorithm, file_system).unwrap_or_else(|_| "?".to_string())
+            });
+        });
+}
+
+/// Hashes a file's contents
+///
+/// # Arguments
+///
+/// * `path` - The file to read
+/// * `algorithm` - The hash to compute
+/// * `file_system` - Where the file is read from
+///
+/// # Returns
+///
+/// The digest as lowercase hex
+pub fn hash_file(path: &Path, algorithm: HashAlgorithm, file_system: &dyn FileSystem) -> io::Result<String> {
+    let mut file = file_system.open(path)?;
+    match algorithm {
+        HashAlgorithm::Sha256 => digest::<Sha256>(&mut file),
+        HashAlgorithm::Md5 => digest::<Md5>(&mut file),
+        HashAlgorithm::Blake3 => {
+            let mut hasher = blake3::Hasher::new();
+            io::copy(&mut file, &mut hasher)?;
+            Ok(hasher.finalize().to_hex().to_string())
+        }
+    }
+}
+
+/// Streams a reader through a RustCrypto hash
+fn digest<D: Digest + io::Write>(reader: &mut impl Read) -> io::Result<String> {
+    let mut hasher = D::new();
+    io::copy(reader, &mut hasher)?;
+    Ok(hasher.finalize().iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+/// Parses the `--hash-max-size` limit, such as `100M`
+pub fn parse_max_size(text: &str) -> Result<u64, String> {
+    parse_size(text).ok_or_else(|| format!("invalid size '{}'", text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_system::StdFileSystem;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_hash_file() {
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().join("abc");
+        fs::write(&path, "abc").unwrap();
+        assert_eq!(hash_file(&path, HashAlgorithm::Sha256, &StdFileSystem).unwrap(), "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+        assert_eq!(hash_file(&path, HashAlgorithm::Md5, &StdFileSystem).unwrap(), "900150983cd24fb0d6963f7d28e17f72");
+        assert_eq!(hash_file(&path, HashAlgorithm::Blake3, &StdFileSystem).unwrap(), "6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85");
+        assert!(hash_file(&temp_dir.path().join("missing"), HashAlgorithm::Md5, &StdFileSystem).is_err());
+    }
+
+    #[test]
+    fn test_parse_max_size() {
+        assert_eq!(parse_max_size("1K"), Ok(1024));
+        assert!(parse_max_size("big").is_err());
+    }
+}
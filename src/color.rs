@@ -0,0 +1,744 @@
+use std::{
+    collections::HashMap,
+    env,
+    ffi::OsString,
+    fs,
+    io::{self, IsTerminal},
+    path::Path,
+    time::SystemTime,
+};
+
+use clap::ValueEnum;
+use serde::{Deserialize, Deserializer};
+
+use crate::{
+    columns::Column,
+    config::{self, ConfigError},
+    dir_utils::{parse_size, FileInfo, FileKind},
+    icons::Icons,
+    platform,
+};
+
+/// When to color the output
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum ColorMode {
+    /// Always emit colors
+    Always,
+    /// Emit colors when stdout is a terminal, honoring NO_COLOR, CLICOLOR and CLICOLOR_FORCE
+    #[default]
+    Auto,
+    /// Never emit colors
+    Never,
+}
+
+impl ColorMode {
+    /// Decides whether output should be colored in this mode
+    pub fn enabled(&self) -> bool {
+        match self {
+            ColorMode::Always => true,
+            ColorMode::Auto => auto_color(|name| env::var_os(name), io::stdout().is_terminal()),
+            ColorMode::Never => false,
+        }
+    }
+}
+
+/// Auto-detects color support from the environment and the terminal
+///
+/// `NO_COLOR` (when non-empty) disables color and takes precedence over everything
+/// else, then `CLICOLOR_FORCE` (when set and not `0`) forces it on, then
+/// `CLICOLOR=0` disables it; otherwise color is used only on a terminal.
+///
+/// # Arguments
+///
+/// * `var` - Looks up an environment variable
+/// * `is_terminal` - Whether stdout is a terminal
+fn auto_color(var: impl Fn(&str) -> Option<OsString>, is_terminal: bool) -> bool {
+    if var("NO_COLOR").is_some_and(|value| !value.is_empty()) {
+        return false;
+    }
+    if var("CLICOLOR_FORCE").is_some_and(|value| !value.is_empty() && value != "0") {
+        return true;
+    }
+    if var("CLICOLOR").is_some_and(|value| value == "0") {
+        return false;
+    }
+    is_terminal
+}
+
+/// A terminal text style, stored as the parameters of an SGR escape sequence
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Style(String);
+
+impl Style {
+    /// Parses a style description such as `"bold blue"`, `"on red"`, `"208"` or `"#ff8800"`
+    ///
+    /// # Arguments
+    ///
+    /// * `spec` - Space separated attributes and colors; a color after `on` is a background
+    ///
+    /// # Returns
+    ///
+    /// The style, or a message naming the first word that could not be understood
+    pub fn parse(spec: &str) -> Result<Style, String> {
+        let mut params = Vec::new();
+        let mut background = false;
+        for word in spec.split_whitespace() {
+            let word = word.to_ascii_lowercase();
+            let attribute = match word.as_str() {
+                "on" => {
+                    background = true;
+                    continue;
+                }
+                "bold" => Some("1"),
+                "dim" => Some("2"),
+                "italic" => Some("3"),
+                "underline" => Some("4"),
+                "blink" => Some("5"),
+                "reverse" => Some("7"),
+                _ => None,
+            };
+            if let Some(attribute) = attribute {
+                params.push(attribute.to_string());
+                continue;
+            }
+
+            let color = parse_color(&word).ok_or_else(|| format!("unknown style '{}'", word))?;
+            params.push(color.sgr(background));
+            background = false;
+        }
+        Ok(Style(params.join(";")))
+    }
+
+    /// Creates a style directly from SGR parameters (e.g. `"1;34"`)
+    pub fn from_sgr(params: &str) -> Style {
+        Style(params.to_string())
+    }
+
+    /// Whether this style leaves text unchanged
+    pub fn is_plain(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Wraps text in this style's escape sequences
+    pub fn paint(&self, text: &str) -> String {
+        if self.is_plain() {
+            text.to_string()
+        } else {
+            format!("\x1b[{}m{}\x1b[0m", self.0, text)
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Style {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let spec = String::deserialize(deserializer)?;
+        Style::parse(&spec).map_err(serde::de::Error::custom)
+    }
+}
+
+/// A single foreground or background color
+enum Color {
+    Basic(u8),
+    Bright(u8),
+    Fixed(u8),
+    Rgb(u8, u8, u8),
+}
+
+impl Color {
+    /// The SGR parameters selecting this color
+    fn sgr(&self, background: bool) -> String {
+        let base = if background { 40 } else { 30 };
+        match self {
+            Color::Basic(n) => (base + n).to_string(),
+            Color::Bright(n) => (base + 60 + n).to_string(),
+            Color::Fixed(n) => format!("{};5;{}", base + 8, n),
+            Color::Rgb(r, g, b) => format!("{};2;{};{};{}", base + 8, r, g, b),
+        }
+    }
+}
+
+/// Parses a color name, 256-color index or `#rrggbb` hex value
+fn parse_color(word: &str) -> Option<Color> {
+    const NAMES: [&str; 8] = ["black", "red", "green", "yellow", "blue", "magenta", "cyan", "white"];
+
+    if let Some(hex) = word.strip_prefix('#') {
+        if hex.len() != 6 {
+            return None;
+        }
+        let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+        return Some(Color::Rgb(channel(0)?, channel(2)?, channel(4)?));
+    }
+    if let Ok(index) = word.parse::<u8>() {
+        return Some(Color::Fixed(index));
+    }
+    let (bright, name) = match word.strip_prefix("bright-") {
+        Some(name) => (true, name),
+        None => (false, word),
+    };
+    let n = NAMES.iter().position(|&candidate| candidate == name)? as u8;
+    Some(if bright { Color::Bright(n) } else { Color::Basic(n) })
+}
+
+/// Colors for the characters of the permissions string
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PermissionColors {
+    pub read: Style,
+    pub write: Style,
+    pub exec: Style,
+    /// Style for setuid, setgid and sticky bits (`s`, `S`, `t`, `T`)
+    pub special: Style,
+    /// Style for unset bits (`-`)
+    pub none: Style,
+}
+
+impl Default for PermissionColors {
+    fn default() -> Self {
+        PermissionColors {
+            read: Style::from_sgr("33"),
+            write: Style::from_sgr("31"),
+            exec: Style::from_sgr("32"),
+            special: Style::from_sgr("1;30;43"),
+            none: Style::from_sgr("2"),
+        }
+    }
+}
+
+/// Colors for file names by kind of entry
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct KindColors {
+    pub file: Style,
+    pub directory: Style,
+    pub symlink: Style,
+    /// Symlinks whose target does not exist
+    pub broken: Style,
+    pub executable: Style,
+    pub pipe: Style,
+    pub socket: Style,
+    pub device: Style,
+}
+
+impl Default for KindColors {
+    fn default() -> Self {
+        KindColors {
+            file: Style::default(),
+            directory: Style::from_sgr("1;34"),
+            symlink: Style::from_sgr("36"),
+            broken: Style::from_sgr("41"),
+            executable: Style::from_sgr("1;32"),
+            pipe: Style::from_sgr("33"),
+            socket: Style::from_sgr("1;35"),
+            device: Style::from_sgr("1;33"),
+        }
+    }
+}
+
+/// Colors and thresholds for the size gradient
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SizeScale {
+    pub small: Style,
+    pub medium: Style,
+    pub large: Style,
+    /// Sizes from this many bytes on use the medium color (accepts suffixes like "1M")
+    #[serde(deserialize_with = "deserialize_size")]
+    pub medium_from: u64,
+    /// Sizes from this many bytes on use the large color
+    #[serde(deserialize_with = "deserialize_size")]
+    pub large_from: u64,
+}
+
+impl Default for SizeScale {
+    fn default() -> Self {
+        SizeScale {
+            small: Style::from_sgr("32"),
+            medium: Style::from_sgr("33"),
+            large: Style::from_sgr("31"),
+            medium_from: 1 << 20,
+            large_from: 100 << 20,
+        }
+    }
+}
+
+impl SizeScale {
+    /// Picks the gradient style for a file of `size` bytes
+    pub fn style(&self, size: u64) -> &Style {
+        if size >= self.large_from {
+            &self.large
+        } else if size >= self.medium_from {
+            &self.medium
+        } else {
+            &self.small
+        }
+    }
+}
+
+/// Reads a size given either as a plain byte count or as a string like "10M"
+fn deserialize_size<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Size {
+        Bytes(u64),
+        Text(String),
+    }
+
+    match Size::deserialize(deserializer)? {
+        Size::Bytes(bytes) => Ok(bytes),
+        Size::Text(text) => {
+            parse_size(&text).ok_or_else(|| serde::de::Error::custom(format!("invalid size '{}'", text)))
+        }
+    }
+}
+
+/// The full set of colors used for output, loaded from `theme.toml`
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Theme {
+    pub permissions: PermissionColors,
+    pub kinds: KindColors,
+    /// File name colors for regular files by extension (without the dot)
+    pub extensions: HashMap<String, Style>,
+    /// File name colors by exact name, such as `Makefile`, taking precedence over kind and extension
+    pub filenames: HashMap<String, Style>,
+    pub size: Style,
+    pub date: Style,
+    pub owner: Style,
+    pub group: Style,
+    /// Owner and group names that differ from the invoking user's
+    pub foreign: Style,
+    /// Colors used by `--color-scale=size`
+    pub size_scale: SizeScale,
+    /// Icons used by `--icons`, over the built-in ones
+    pub icons: Icons,
+    /// Color symlinks like the entry they point to (`ln=target` in `LS_COLORS`)
+    pub link_as_target: bool,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme {
+            permissions: PermissionColors::default(),
+            kinds: KindColors::default(),
+            extensions: HashMap::new(),
+            filenames: HashMap::new(),
+            size: Style::from_sgr("32"),
+            date: Style::from_sgr("34"),
+            owner: Style::from_sgr("33"),
+            group: Style::from_sgr("33"),
+            foreign: Style::from_sgr("1;35"),
+            size_scale: SizeScale::default(),
+            icons: Icons::default(),
+            link_as_target: false,
+        }
+    }
+}
+
+impl Theme {
+    /// Loads the theme, with any values missing from the file taken from the defaults
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - An explicit theme file; when unset, `theme.toml` in the config
+    ///   directory is used if it exists
+    ///
+    /// # Returns
+    ///
+    /// The theme, or an error if the file cannot be read or parsed
+    pub fn load(path: Option<&Path>) -> Result<Theme, ConfigError> {
+        let path = match path {
+            Some(path) => path.to_path_buf(),
+            None => match config::config_dir() {
+                Some(dir) if dir.join("theme.toml").exists() => dir.join("theme.toml"),
+                _ => return Ok(Theme::default()),
+            },
+        };
+
+        let contents = fs::read_to_string(&path).map_err(|err| ConfigError::Io(path.clone(), err))?;
+        toml::from_str(&contents).map_err(|err| ConfigError::Parse(path, err))
+    }
+
+    /// Overrides colors with a GNU `LS_COLORS` specification such as `di=01;34:ln=target:*.rs=33`
+    ///
+    /// Kind keys (`fi`, `di`, `ln`, `ex`, `pi`, `so`, `bd`, `cd`, `or`) and `*.ext` / `*name`
+    /// patterns are understood; other keys are ignored.
+    ///
+    /// # Arguments
+    ///
+    /// * `spec` - The value of `LS_COLORS`
+    pub fn apply_ls_colors(&mut self, spec: &str) {
+        for (key, value) in spec.split(':').filter_map(|item| item.split_once('=')) {
+            if key == "ln" && value == "target" {
+                self.link_as_target = true;
+                continue;
+            }
+            let style = Style::from_sgr(value);
+            let kinds = &mut self.kinds;
+            match key {
+                "fi" => kinds.file = style,
+                "di" => kinds.directory = style,
+                "ln" => {
+                    kinds.symlink = style;
+                    self.link_as_target = false;
+                }
+                "ex" => kinds.executable = style,
+                "pi" => kinds.pipe = style,
+                "so" => kinds.socket = style,
+                "bd" | "cd" => kinds.device = style,
+                "or" => kinds.broken = style,
+                _ => match key.strip_prefix("*.") {
+                    Some(ext) => {
+                        self.extensions.insert(ext.to_ascii_lowercase(), style);
+                    }
+                    None => {
+                        if let Some(name) = key.strip_prefix('*') {
+                            self.filenames.insert(name.to_string(), style);
+                        }
+                    }
+                },
+            }
+        }
+    }
+
+    /// Picks the kind an entry is colored as, following symlinks when `link_as_target` is set
+    ///
+    /// # Arguments
+    ///
+    /// * `kind` - The entry's own kind
+    /// * `target_kind` - The kind of the entry's symlink target, if any
+    pub fn color_kind(&self, kind: FileKind, target_kind: Option<FileKind>) -> FileKind {
+        match (kind, target_kind) {
+            (FileKind::Symlink, Some(target)) if self.link_as_target => target,
+            _ => kind,
+        }
+    }
+
+    /// Picks the style for a file name from its exact name, its kind and, for regular files, its extension
+    pub fn name_style(&self, name: &str, kind: FileKind) -> &Style {
+        if let Some(style) = self.filenames.get(name) {
+            return style;
+        }
+        match kind {
+            FileKind::Directory => &self.kinds.directory,
+            FileKind::Symlink => &self.kinds.symlink,
+            FileKind::BrokenSymlink => &self.kinds.broken,
+            FileKind::Executable => &self.kinds.executable,
+            FileKind::Pipe => &self.kinds.pipe,
+            FileKind::Socket => &self.kinds.socket,
+            FileKind::BlockDevice | FileKind::CharDevice => &self.kinds.device,
+            FileKind::File => name
+                .rsplit_once('.')
+                .filter(|(stem, _)| !stem.is_empty())
+                .and_then(|(_, ext)| self.extensions.get(&ext.to_ascii_lowercase()))
+                .unwrap_or(&self.kinds.file),
+        }
+    }
+
+    /// Names the `LS_COLORS` key whose style [`Theme::name_style`] picks for a name,
+    /// such as `di`, `*.rs` or `*Makefile`
+    pub fn name_class(&self, name: &str, kind: FileKind) -> String {
+        if self.filenames.contains_key(name) {
+            return format!("*{}", name);
+        }
+        let key = match kind {
+            FileKind::Directory => "di",
+            FileKind::Symlink => "ln",
+            FileKind::BrokenSymlink => "or",
+            FileKind::Executable => "ex",
+            FileKind::Pipe => "pi",
+            FileKind::Socket => "so",
+            FileKind::BlockDevice => "bd",
+            FileKind::CharDevice => "cd",
+            FileKind::File => {
+                let ext = name
+                    .rsplit_once('.')
+                    .filter(|(stem, _)| !stem.is_empty())
+                    .map(|(_, ext)| ext.to_ascii_lowercase())
+                    .filter(|ext| self.extensions.contains_key(ext));
+                return ext.map_or_else(|| "fi".to_string(), |ext| format!("*.{}", ext));
+            }
+        };
+        key.to_string()
+    }
+
+    /// Colors a name according to its kind and extension
+    pub fn paint_name(&self, name: &str, kind: FileKind) -> String {
+        self.name_style(name, kind).paint(name)
+    }
+
+    /// Colors each character of a permissions string such as `drwxr-x---`
+    pub fn paint_permissions(&self, permissions: &str) -> String {
+        permissions
+            .char_indices()
+            .map(|(i, c)| {
+                let style = match c {
+                    _ if i == 0 => return c.to_string(),
+                    'r' => &self.permissions.read,
+                    'w' => &self.permissions.write,
+                    'x' => &self.permissions.exec,
+                    's' | 'S' | 't' | 'T' => &self.permissions.special,
+                    _ => &self.permissions.none,
+                };
+                style.paint(&c.to_string())
+            })
+            .collect()
+    }
+}
+
+/// A value that can be shown as a color gradient instead of a fixed color
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ColorScale {
+    /// Fade modification times from bright (recent) to dim (old)
+    Age,
+    /// Tint sizes from green (small) through yellow to red (huge)
+    Size,
+}
+
+/// Upper age bounds in seconds, paired with the 256-color gray used below each bound
+const AGE_GRADIENT: [(u64, u8); 5] = [
+    (60 * 60, 231),
+    (24 * 60 * 60, 255),
+    (7 * 24 * 60 * 60, 252),
+    (30 * 24 * 60 * 60, 248),
+    (365 * 24 * 60 * 60, 244),
+];
+
+/// The gray used for entries older than every bound in `AGE_GRADIENT`
+const AGE_GRADIENT_OLDEST: u8 = 240;
+
+/// Picks the gradient style for an entry modified at `time`
+///
+/// # Arguments
+///
+/// * `time` - The entry's modification time
+/// * `now` - Reference time used to compute the entry's age
+pub fn age_style(time: SystemTime, now: SystemTime) -> Style {
+    let age = now.duration_since(time).unwrap_or_default().as_secs();
+    let gray = AGE_GRADIENT
+        .iter()
+        .find(|&&(bound, _)| age < bound)
+        .map_or(AGE_GRADIENT_OLDEST, |&(_, gray)| gray);
+    Style(format!("38;5;{}", gray))
+}
+
+/// Applies a theme, plus any runtime color options, to output
+pub struct Painter {
+    pub theme: Theme,
+    /// Values shown as gradients instead of fixed theme colors
+    pub scale: Vec<ColorScale>,
+    /// Whether gradients also apply to file names
+    pub scale_names: bool,
+    /// Whether owners and groups other than the invoking user's are colored differently
+    pub highlight_foreign: bool,
+    now: SystemTime,
+    uid: u32,
+    gid: u32,
+}
+
+impl Painter {
+    /// Creates a painter; gradient ages are measured from the moment it is created
+    pub fn new(theme: Theme, scale: Vec<ColorScale>, scale_names: bool) -> Self {
+        Painter {
+            theme,
+            scale,
+            scale_names,
+            highlight_foreign: true,
+            now: SystemTime::now(),
+            uid: platform::current_uid(),
+            gid: platform::current_gid(),
+        }
+    }
+
+    /// Picks the style for an entry's owner column
+    pub fn owner_style(&self, uid: u32) -> &Style {
+        if self.highlight_foreign && uid != self.uid {
+            &self.theme.foreign
+        } else {
+            &self.theme.owner
+        }
+    }
+
+    /// Picks the style for an entry's group column
+    pub fn group_style(&self, gid: u32) -> &Style {
+        if self.highlight_foreign && gid != self.gid {
+            &self.theme.foreign
+        } else {
+            &self.theme.group
+        }
+    }
+
+    /// Colors a file name by kind and extension, or by a gradient when names are scaled
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name to color
+    /// * `kind` - The entry's kind
+    /// * `modified` - The entry's modification time, for the age gradient
+    /// * `size` - The entry's size in bytes, for the size gradient
+    pub fn paint_name(&self, name: &str, kind: FileKind, modified: SystemTime, size: u64) -> String {
+        if self.scale_names && self.scale.contains(&ColorScale::Age) {
+            age_style(modified, self.now).paint(name)
+        } else if self.scale_names && self.scale.contains(&ColorScale::Size) && kind != FileKind::Directory {
+            self.theme.size_scale.style(size).paint(name)
+        } else {
+            self.theme.paint_name(name, kind)
+        }
+    }
+
+    /// Colors a long format entry's name in place
+    pub fn paint_file_name(&self, file: &mut FileInfo) {
+        let kind = self.theme.color_kind(file.kind, file.target_kind);
+        file.name = self.paint_name(&file.name, kind, file.modified_time, file.file_size);
+    }
+
+    /// Colors a rendered long format cell the way its column is colored; columns
+    /// without a color of their own are left plain
+    ///
+    /// # Arguments
+    ///
+    /// * `column` - The column the cell belongs to
+    /// * `file` - The entry, for the owner, size and age the colors depend on
+    /// * `text` - The rendered cell
+    pub fn paint_cell(&self, column: Column, file: &FileInfo, text: &str) -> String {
+        let theme = &self.theme;
+        match column {
+            Column::Permissions => theme.paint_permissions(text),
+            Column::Owner => self.owner_style(file.uid).paint(text),
+            Column::Group => self.group_style(file.gid).paint(text),
+            Column::Size if self.scale.contains(&ColorScale::Size) && !file.is_dir => theme.size_scale.style(file.file_size).paint(text),
+            Column::Size => theme.size.paint(text),
+            Column::Modified if self.scale.contains(&ColorScale::Age) => age_style(file.modified_time, self.now).paint(text),
+            Column::Modified => theme.date.paint(text),
+            _ => text.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_style() {
+        assert_eq!(Style::parse("bold blue"), Ok(Style::from_sgr("1;34")));
+        assert_eq!(Style::parse("bright-red on black"), Ok(Style::from_sgr("91;40")));
+        assert_eq!(Style::parse("208"), Ok(Style::from_sgr("38;5;208")));
+        assert_eq!(Style::parse("#ff8800"), Ok(Style::from_sgr("38;2;255;136;0")));
+        assert!(Style::parse("sparkly").is_err());
+        assert_eq!(Style::parse("").map(|style| style.paint("x")), Ok("x".to_string()));
+    }
+
+    #[test]
+    fn test_auto_color_env() {
+        let env = |vars: &'static [(&'static str, &'static str)]| {
+            move |name: &str| {
+                vars.iter()
+                    .find(|(key, _)| *key == name)
+                    .map(|(_, value)| OsString::from(value))
+            }
+        };
+
+        assert!(auto_color(env(&[]), true));
+        assert!(!auto_color(env(&[]), false));
+        assert!(!auto_color(env(&[("NO_COLOR", "1")]), true));
+        assert!(auto_color(env(&[("NO_COLOR", "")]), true));
+        assert!(!auto_color(env(&[("CLICOLOR", "0")]), true));
+        assert!(auto_color(env(&[("CLICOLOR_FORCE", "1")]), false));
+        assert!(!auto_color(env(&[("CLICOLOR_FORCE", "0")]), false));
+        assert!(!auto_color(env(&[("CLICOLOR_FORCE", "1"), ("NO_COLOR", "1")]), false));
+    }
+
+    #[test]
+    fn test_age_style() {
+        let now = SystemTime::now();
+        let hour = std::time::Duration::from_secs(60 * 60);
+        assert_eq!(age_style(now, now), Style::from_sgr("38;5;231"));
+        assert_eq!(age_style(now - 2 * hour, now), Style::from_sgr("38;5;255"));
+        assert_eq!(age_style(now - 24 * 400 * hour, now), Style::from_sgr("38;5;240"));
+        // Timestamps in the future count as brand new
+        assert_eq!(age_style(now + hour, now), Style::from_sgr("38;5;231"));
+    }
+
+    #[test]
+    fn test_theme_overrides_defaults() {
+        let theme: Theme = toml::from_str(
+            r#"
+            size = "red"
+            [kinds]
+            directory = "green"
+            [extensions]
+            rs = "yellow"
+            [filenames]
+            Makefile = "red"
+            [icons.filenames]
+            Dockerfile = "D"
+            "#,
+        )
+        .expect("Unable to parse theme");
+
+        assert_eq!(theme.size, Style::from_sgr("31"));
+        assert_eq!(theme.kinds.directory, Style::from_sgr("32"));
+        assert_eq!(theme.kinds.symlink, KindColors::default().symlink);
+        assert_eq!(theme.name_style("main.rs", FileKind::File), &Style::from_sgr("33"));
+        assert_eq!(theme.name_style(".rs", FileKind::File), &Style::default());
+        assert_eq!(theme.name_style("Makefile", FileKind::File), &Style::from_sgr("31"));
+        assert_eq!(theme.name_class("lib.rs", FileKind::File), "*.rs");
+        assert_eq!(theme.name_class("Makefile", FileKind::File), "*Makefile");
+        assert_eq!(theme.name_class("notes.xyz", FileKind::File), "fi");
+        assert_eq!(theme.name_class("src", FileKind::Directory), "di");
+        assert_eq!(theme.icons.icon("Dockerfile", FileKind::File), "D");
+    }
+
+    #[test]
+    fn test_paint_permissions_special_bits() {
+        let theme = Theme::default();
+        let colors = &theme.permissions;
+        let painted = theme.paint_permissions("-rwS");
+        let expected = ["-".to_string(), colors.read.paint("r"), colors.write.paint("w"), colors.special.paint("S")].concat();
+        assert_eq!(painted, expected);
+    }
+
+    #[test]
+    fn test_foreign_owner_style() {
+        let mut painter = Painter::new(Theme::default(), Vec::new(), false);
+        let (uid, gid) = (painter.uid, painter.gid);
+        assert_eq!(painter.owner_style(uid), &painter.theme.owner);
+        assert_eq!(painter.owner_style(uid.wrapping_add(1)), &painter.theme.foreign);
+        assert_eq!(painter.group_style(gid.wrapping_add(1)), &painter.theme.foreign);
+
+        painter.highlight_foreign = false;
+        assert_eq!(painter.group_style(gid.wrapping_add(1)), &painter.theme.group);
+    }
+
+    #[test]
+    fn test_ls_colors() {
+        let mut theme = Theme::default();
+        theme.apply_ls_colors("di=01;32:ln=target:*.RS=33:*Makefile=31:bogus");
+        assert_eq!(theme.kinds.directory, Style::from_sgr("01;32"));
+        assert_eq!(theme.name_style("lib.rs", FileKind::File), &Style::from_sgr("33"));
+        assert_eq!(theme.name_style("Makefile", FileKind::File), &Style::from_sgr("31"));
+        assert_eq!(theme.color_kind(FileKind::Symlink, Some(FileKind::Directory)), FileKind::Directory);
+        assert_eq!(theme.color_kind(FileKind::BrokenSymlink, None), FileKind::BrokenSymlink);
+
+        theme.apply_ls_colors("ln=01;36");
+        assert_eq!(theme.color_kind(FileKind::Symlink, Some(FileKind::Directory)), FileKind::Symlink);
+    }
+
+    #[test]
+    fn test_size_scale_thresholds() {
+        let theme: Theme = toml::from_str(
+            r#"
+            [size_scale]
+            medium_from = "10K"
+            large_from = 1048576
+            "#,
+        )
+        .expect("Unable to parse theme");
+
+        let scale = &theme.size_scale;
+        assert_eq!(scale.style(100), &scale.small);
+        assert_eq!(scale.style(10 * 1024), &scale.medium);
+        assert_eq!(scale.style(1 << 20), &scale.large);
+        assert!(toml::from_str::<Theme>("[size_scale]\nlarge_from = \"huge\"").is_err());
+    }
+}
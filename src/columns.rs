@@ -0,0 +1,241 @@
+use clap::ValueEnum;
+use serde::Deserialize;
+
+/// A column that can be shown in the long format
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Column {
+    #[serde(alias = "perms")]
+    Permissions,
+    /// Permission bits as a 4-digit octal mode, ready for `chmod`
+    #[serde(alias = "mode")]
+    Octal,
+    Links,
+    Owner,
+    Group,
+    Size,
+    /// Exact size in bytes, regardless of `-h`
+    Bytes,
+    /// Space taken up on disk, marking sparse files
+    Allocated,
+    #[serde(alias = "mtime")]
+    Modified,
+    #[serde(alias = "ctime")]
+    Changed,
+    #[serde(alias = "atime")]
+    Accessed,
+    /// Creation time, blank where the file system does not record it
+    #[serde(alias = "btime")]
+    Created,
+    Inode,
+    /// Mount point and type of the file system the entry lives on
+    #[serde(alias = "fs")]
+    Filesystem,
+    /// Content hash of regular files, as chosen with `--hash`
+    #[serde(alias = "checksum")]
+    Hash,
+    /// Whether a regular file holds text or binary data, or is empty
+    Kind,
+    /// Image dimensions, or the running time of audio and video
+    Media,
+    /// Git working-tree status
+    Git,
+    /// Abbreviated hash of the last commit that touched the entry
+    Commit,
+    /// Author of the last commit that touched the entry
+    Author,
+    /// Date of the last commit that touched the entry
+    Committed,
+    /// Read-only, hidden and system attributes
+    #[cfg(windows)]
+    #[serde(alias = "attrib")]
+    Attributes,
+    /// BSD file flags such as `uchg` and `hidden`
+    #[cfg(target_os = "macos")]
+    #[serde(alias = "fflags")]
+    Flags,
+    /// Number of extended attributes
+    #[cfg(target_os = "macos")]
+    #[serde(alias = "xattr")]
+    Xattrs,
+    /// The application that downloaded a quarantined entry
+    #[cfg(target_os = "macos")]
+    Quarantine,
+    Name,
+}
+
+impl Column {
+    /// The column's header, matching the default long format headers
+    pub fn header(&self) -> &'static str {
+        match self {
+            Column::Permissions => "permissions",
+            Column::Octal => "octal",
+            Column::Links => "links",
+            Column::Owner => "owner",
+            Column::Group => "group",
+            Column::Size => "size",
+            Column::Bytes => "bytes",
+            Column::Allocated => "allocated",
+            Column::Modified => "modified",
+            Column::Changed => "changed",
+            Column::Accessed => "accessed",
+            Column::Created => "created",
+            Column::Inode => "inode",
+            Column::Filesystem => "filesystem",
+            Column::Hash => "hash",
+            Column::Kind => "kind",
+            Column::Media => "media",
+            Column::Git => "git",
+            Column::Commit => "commit",
+            Column::Author => "author",
+            Column::Committed => "committed",
+            #[cfg(windows)]
+            Column::Attributes => "attributes",
+            #[cfg(target_os = "macos")]
+            Column::Flags => "flags",
+            #[cfg(target_os = "macos")]
+            Column::Xattrs => "xattrs",
+            #[cfg(target_os = "macos")]
+            Column::Quarantine => "quarantine",
+            Column::Name => "name",
+        }
+    }
+}
+
+/// The columns of the default long format
+pub const DEFAULT_COLUMNS: [Column; 7] = [
+    Column::Permissions,
+    Column::Links,
+    Column::Owner,
+    Column::Group,
+    Column::Size,
+    Column::Modified,
+    Column::Name,
+];
+
+/// Adds an exact byte count column right after the size column, if there is one
+pub fn with_exact_size(columns: &[Column]) -> Vec<Column> {
+    let mut columns = columns.to_vec();
+    if !columns.contains(&Column::Bytes) {
+        if let Some(i) = columns.iter().position(|&column| column == Column::Size) {
+            columns.insert(i + 1, Column::Bytes);
+        }
+    }
+    columns
+}
+
+/// Where `--octal-permissions` shows the octal mode
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum OctalPermissions {
+    /// Show it right before the permission string
+    Alongside,
+    /// Show it in place of the permission string
+    Instead,
+}
+
+/// Adds an octal mode column next to the permission string, or in its place
+///
+/// # Arguments
+///
+/// * `columns` - The columns to add to
+/// * `placement` - Whether the permission string is kept
+pub fn with_octal(columns: &[Column], placement: OctalPermissions) -> Vec<Column> {
+    let mut columns: Vec<Column> = columns.iter().copied().filter(|&column| column != Column::Octal).collect();
+    match columns.iter().position(|&column| column == Column::Permissions) {
+        Some(i) if placement == OctalPermissions::Instead => columns[i] = Column::Octal,
+        Some(i) => columns.insert(i, Column::Octal),
+        None => columns.insert(0, Column::Octal),
+    }
+    columns
+}
+
+/// Adds an allocated size column right after the size columns, if there are any
+pub fn with_allocated(columns: &[Column]) -> Vec<Column> {
+    let mut columns = columns.to_vec();
+    if !columns.contains(&Column::Allocated) {
+        if let Some(i) = columns.iter().rposition(|&column| matches!(column, Column::Size | Column::Bytes)) {
+            columns.insert(i + 1, Column::Allocated);
+        }
+    }
+    columns
+}
+
+/// Adds a creation time column right after the modification time column, or before
+/// the name column if there is none
+pub fn with_created(columns: &[Column]) -> Vec<Column> {
+    let mut columns = columns.to_vec();
+    if !columns.contains(&Column::Created) {
+        let i = match columns.iter().position(|&column| column == Column::Modified) {
+            Some(i) => i + 1,
+            None => columns.iter().position(|&column| column == Column::Name).unwrap_or(columns.len()),
+        };
+        columns.insert(i, Column::Created);
+    }
+    columns
+}
+
+/// Adds columns right before the name column, or last if there is none, leaving out
+/// any that are already shown
+///
+/// # Arguments
+///
+/// * `columns` - The columns to add to
+/// * `added` - The columns to add, in the order they are shown
+pub fn insert_before_name(columns: &[Column], added: &[Column]) -> Vec<Column> {
+    let mut columns = columns.to_vec();
+    let i = columns.iter().position(|&column| column == Column::Name).unwrap_or(columns.len());
+    let missing: Vec<Column> = added.iter().copied().filter(|column| !columns.contains(column)).collect();
+    columns.splice(i..i, missing);
+    columns
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_with_exact_size() {
+        let columns = with_exact_size(&DEFAULT_COLUMNS);
+        assert_eq!(columns[4..6], [Column::Size, Column::Bytes]);
+        assert_eq!(with_exact_size(&columns), columns);
+        assert_eq!(with_exact_size(&[Column::Name]), vec![Column::Name]);
+    }
+
+    #[test]
+    fn test_with_octal() {
+        let columns = with_octal(&DEFAULT_COLUMNS, OctalPermissions::Alongside);
+        assert_eq!(columns[..3], [Column::Octal, Column::Permissions, Column::Links]);
+        assert_eq!(with_octal(&columns, OctalPermissions::Alongside), columns);
+        let columns = with_octal(&DEFAULT_COLUMNS, OctalPermissions::Instead);
+        assert_eq!(columns[..2], [Column::Octal, Column::Links]);
+        assert_eq!(with_octal(&[Column::Name], OctalPermissions::Instead), vec![Column::Octal, Column::Name]);
+    }
+
+    #[test]
+    fn test_with_allocated() {
+        let columns = with_allocated(&DEFAULT_COLUMNS);
+        assert_eq!(columns[4..6], [Column::Size, Column::Allocated]);
+        assert_eq!(with_allocated(&columns), columns);
+        let columns = with_allocated(&with_exact_size(&DEFAULT_COLUMNS));
+        assert_eq!(columns[4..7], [Column::Size, Column::Bytes, Column::Allocated]);
+        assert_eq!(with_allocated(&[Column::Name]), vec![Column::Name]);
+    }
+
+    #[test]
+    fn test_with_created() {
+        let columns = with_created(&DEFAULT_COLUMNS);
+        assert_eq!(columns[5..], [Column::Modified, Column::Created, Column::Name]);
+        assert_eq!(with_created(&columns), columns);
+        assert_eq!(with_created(&[Column::Size, Column::Name]), vec![Column::Size, Column::Created, Column::Name]);
+    }
+
+    #[test]
+    fn test_insert_before_name() {
+        let columns = insert_before_name(&DEFAULT_COLUMNS, &[Column::Hash]);
+        assert_eq!(columns[5..], [Column::Modified, Column::Hash, Column::Name]);
+        assert_eq!(insert_before_name(&columns, &[Column::Hash]), columns);
+        let columns = insert_before_name(&columns, &[Column::Committed, Column::Hash, Column::Commit]);
+        assert_eq!(columns[5..], [Column::Modified, Column::Hash, Column::Committed, Column::Commit, Column::Name]);
+        assert_eq!(insert_before_name(&[Column::Size], &[Column::Git]), vec![Column::Size, Column::Git]);
+    }
+}
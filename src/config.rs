@@ -0,0 +1,232 @@
+use std::{
+    collections::HashMap,
+    env, fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+
+use crate::columns::Column;
+
+/// Settings read from the user's configuration file
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Named long format column sets, selectable with `--preset`
+    #[serde(default)]
+    pub preset: HashMap<String, Vec<Column>>,
+    /// Default command line options, keyed by long flag name (e.g. `icons = true`, `color = "always"`)
+    #[serde(default)]
+    pub defaults: toml::Table,
+}
+
+impl Config {
+    /// Turns the `[defaults]` table into long command line flags
+    ///
+    /// `true` becomes `--name`, `false` is left out, arrays are joined with commas and
+    /// any other value becomes `--name=value`.
+    ///
+    /// # Returns
+    ///
+    /// The flags, or the name of the first default whose value cannot be expressed as a flag
+    pub fn default_flags(&self) -> Result<Vec<String>, String> {
+        flags_from_table(&self.defaults)
+    }
+}
+
+/// Turns a table of options into long command line flags, as described on [`Config::default_flags`]
+fn flags_from_table(table: &toml::Table) -> Result<Vec<String>, String> {
+    let mut flags = Vec::new();
+    for (name, value) in table {
+        let flag = name.replace('_', "-");
+        match value {
+            toml::Value::Boolean(true) => flags.push(format!("--{}", flag)),
+            toml::Value::Boolean(false) => {}
+            toml::Value::Array(values) => {
+                let values: Option<Vec<String>> = values.iter().map(flag_value).collect();
+                let values = values.ok_or_else(|| name.clone())?;
+                flags.push(format!("--{}={}", flag, values.join(",")));
+            }
+            value => flags.push(format!("--{}={}", flag, flag_value(value).ok_or_else(|| name.clone())?)),
+        }
+    }
+    Ok(flags)
+}
+
+/// Formats a scalar config value as a flag argument
+fn flag_value(value: &toml::Value) -> Option<String> {
+    match value {
+        toml::Value::String(text) => Some(text.clone()),
+        toml::Value::Integer(number) => Some(number.to_string()),
+        toml::Value::Float(number) => Some(number.to_string()),
+        toml::Value::Boolean(flag) => Some(flag.to_string()),
+        _ => None,
+    }
+}
+
+/// An error encountered while loading the configuration file
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(PathBuf, io::Error),
+    Parse(PathBuf, toml::de::Error),
+    /// A `[defaults]` entry whose value cannot be turned into a flag
+    Default(PathBuf, String),
+    /// An option a per-directory file is not allowed to set
+    DirOption(PathBuf, String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(path, err) => write!(f, "cannot read config '{}': {}", path.display(), err),
+            ConfigError::Parse(path, err) => write!(f, "invalid config '{}': {}", path.display(), err),
+            ConfigError::Default(path, name) => {
+                write!(f, "invalid config '{}': default '{}' must be a string, number, boolean or array", path.display(), name)
+            }
+            ConfigError::DirOption(path, name) => {
+                write!(f, "invalid config '{}': option '{}' cannot be set per directory", path.display(), name)
+            }
+        }
+    }
+}
+
+/// Locates the directory holding ls_oxide's configuration files
+///
+/// # Returns
+///
+/// `ls_oxide` under `$XDG_CONFIG_HOME` or `~/.config`
+pub fn config_dir() -> Option<PathBuf> {
+    let base = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(base.join("ls_oxide"))
+}
+
+/// Locates the configuration file
+///
+/// # Returns
+///
+/// `$LS_OXIDE_CONFIG` if set, otherwise `config.toml` in the config directory
+pub fn config_path() -> Option<PathBuf> {
+    if let Some(path) = env::var_os("LS_OXIDE_CONFIG") {
+        return Some(PathBuf::from(path));
+    }
+    Some(config_dir()?.join("config.toml"))
+}
+
+/// Loads the configuration file, treating a missing default file as empty
+///
+/// # Returns
+///
+/// The parsed configuration, or an error if the file exists but cannot be read or parsed
+pub fn load() -> Result<Config, ConfigError> {
+    let Some(path) = config_path() else {
+        return Ok(Config::default());
+    };
+    match fs::read_to_string(&path) {
+        Ok(contents) => parse(&contents).map_err(|err| ConfigError::Parse(path, err)),
+        Err(err) if err.kind() == io::ErrorKind::NotFound && env::var_os("LS_OXIDE_CONFIG").is_none() => {
+            Ok(Config::default())
+        }
+        Err(err) => Err(ConfigError::Io(path, err)),
+    }
+}
+
+/// Name of the per-directory file overriding display options for that directory
+pub const DIR_CONFIG_FILE: &str = ".lsoxide.toml";
+
+/// Options a per-directory file may set; anything that writes files or changes
+/// where output goes is deliberately left out
+const DIR_OPTIONS: &[&str] = &["all", "almost-all", "classify", "dotfiles", "full-time", "human-readable", "quote-name", "reverse", "sort", "time-style"];
+
+/// Reads the display overrides of a directory's `.lsoxide.toml`, if it has one
+///
+/// # Arguments
+///
+/// * `dir` - The directory about to be listed
+///
+/// # Returns
+///
+/// The overrides as long flags, None if the directory has no such file, or an error if
+/// the file cannot be read, cannot be parsed or sets an option not allowed per directory
+pub fn load_dir_flags(dir: &Path) -> Result<Option<Vec<String>>, ConfigError> {
+    let path = dir.join(DIR_CONFIG_FILE);
+    let contents = match fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        // Directories inside archives are not on disk, so their path leads through a file
+        Err(err) if matches!(err.kind(), io::ErrorKind::NotFound | io::ErrorKind::NotADirectory) => return Ok(None),
+        Err(err) => return Err(ConfigError::Io(path, err)),
+    };
+    let table: toml::Table = toml::from_str(&contents).map_err(|err| ConfigError::Parse(path.clone(), err))?;
+    if let Some(name) = table.keys().find(|name| !DIR_OPTIONS.contains(&name.replace('_', "-").as_str())) {
+        return Err(ConfigError::DirOption(path, name.clone()));
+    }
+    flags_from_table(&table).map(Some).map_err(|name| ConfigError::Default(path, name))
+}
+
+/// Parses configuration file contents
+pub fn parse(contents: &str) -> Result<Config, toml::de::Error> {
+    toml::from_str(contents)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_presets() {
+        let config = parse(
+            r#"
+            [preset]
+            audit = ["perms", "owner", "group", "ctime", "name"]
+            "#,
+        )
+        .expect("Unable to parse config");
+
+        assert_eq!(
+            config.preset["audit"],
+            vec![Column::Permissions, Column::Owner, Column::Group, Column::Changed, Column::Name]
+        );
+        assert!(parse("[preset]\nbad = [\"colour\"]").is_err());
+    }
+
+    #[test]
+    fn test_default_flags() {
+        let config = parse(
+            r#"
+            [defaults]
+            icons = true
+            stats = false
+            color = "always"
+            color_scale = ["age", "size"]
+            nss-timeout = 100
+            "#,
+        )
+        .expect("Unable to parse config");
+
+        let mut flags = config.default_flags().expect("Unable to convert defaults");
+        flags.sort();
+        assert_eq!(flags, vec!["--color-scale=age,size", "--color=always", "--icons", "--nss-timeout=100"]);
+
+        let config = parse("[defaults.color]\nwhen = \"always\"").expect("Unable to parse config");
+        assert_eq!(config.default_flags(), Err("color".to_string()));
+    }
+
+    #[test]
+    fn test_load_dir_flags() {
+        let temp_dir = tempfile::tempdir().expect("Unable to create temporary directory");
+        let dir_path = temp_dir.path();
+        assert!(load_dir_flags(dir_path).expect("Unable to check directory").is_none());
+
+        fs::write(dir_path.join(DIR_CONFIG_FILE), "all = true\nsort = [\"time\"]\n").expect("Unable to write config");
+        let mut flags = load_dir_flags(dir_path).expect("Unable to load config").expect("Config should be found");
+        flags.sort();
+        assert_eq!(flags, vec!["--all", "--sort=time"]);
+
+        fs::write(dir_path.join(DIR_CONFIG_FILE), "time_style = \"full-iso\"\n").expect("Unable to write config");
+        assert_eq!(load_dir_flags(dir_path).expect("Unable to load config"), Some(vec!["--time-style=full-iso".to_string()]));
+
+        fs::write(dir_path.join(DIR_CONFIG_FILE), "split_output = \"/tmp\"\n").expect("Unable to write config");
+        assert!(matches!(load_dir_flags(dir_path), Err(ConfigError::DirOption(_, name)) if name == "split_output"));
+    }
+}
@@ -0,0 +1,107 @@
+use std::{fmt, io::Read, path::Path};
+
+use rayon::iter::{IntoParallelRefMutIterator, ParallelIterator};
+
+use crate::{
+    dir_utils::{FileInfo, FileKind},
+    file_system::FileSystem,
+};
+
+/// How much of a file is read to tell text from binary, like git and grep do
+const SNIFF_LEN: u64 = 8 * 1024;
+
+/// Whether a regular file holds text or binary data, as the `kind` column shows
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ContentKind {
+    Empty,
+    Text,
+    Binary,
+}
+
+impl fmt::Display for ContentKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ContentKind::Empty => "empty",
+            ContentKind::Text => "text",
+            ContentKind::Binary => "binary",
+        })
+    }
+}
+
+/// Guesses whether a file is text or binary from a NUL byte in its first 8KB
+///
+/// # Arguments
+///
+/// * `path` - The file to look at
+/// * `file_system` - Where the file is read from
+///
+/// # Returns
+///
+/// The kind of content, or None if the file cannot be read
+pub fn sniff(path: &Path, file_system: &dyn FileSystem) -> Option<ContentKind> {
+    let mut head = Vec::with_capacity(SNIFF_LEN as usize);
+    file_system.open(path).ok()?.take(SNIFF_LEN).read_to_end(&mut head).ok()?;
+    Some(if head.is_empty() {
+        ContentKind::Empty
+    } else if head.contains(&0) {
+        ContentKind::Binary
+    } else {
+        ContentKind::Text
+    })
+}
+
+/// Fills in the `kind` column for every regular file, reading files in parallel
+///
+/// Files that cannot be read show `?`. Other entries are left blank.
+///
+/// # Arguments
+///
+/// * `files` - The long format entries to update
+/// * `file_system` - Where the files are read from
+pub fn apply(files: &mut [FileInfo], file_system: &dyn FileSystem) {
+    files
+        .par_iter_mut()
+        .filter(|file| matches!(file.kind, FileKind::File | FileKind::Executable))
+        .for_each(|file| file.content_kind = Some(sniff(&file.path, file_system).map_or_else(|| "?".to_string(), |kind| kind.to_string())));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        dir_utils::{list_files_detailed, ListOptions},
+        file_system::StdFileSystem,
+    };
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_sniff() {
+        let temp_dir = tempdir().unwrap();
+        let file = |name: &str, contents: &[u8]| {
+            let path = temp_dir.path().join(name);
+            fs::write(&path, contents).unwrap();
+            path
+        };
+        assert_eq!(sniff(&file("empty", b""), &StdFileSystem), Some(ContentKind::Empty));
+        assert_eq!(sniff(&file("notes.txt", "héllo\n".as_bytes()), &StdFileSystem), Some(ContentKind::Text));
+        assert_eq!(sniff(&file("a.out", b"\x7fELF\x02\x01\x01\0\0"), &StdFileSystem), Some(ContentKind::Binary));
+        // A NUL past the first 8KB goes unnoticed
+        let mut late = vec![b'a'; SNIFF_LEN as usize];
+        late.push(0);
+        assert_eq!(sniff(&file("late", &late), &StdFileSystem), Some(ContentKind::Text));
+        assert_eq!(sniff(&temp_dir.path().join("missing"), &StdFileSystem), None);
+    }
+
+    #[test]
+    fn test_apply() {
+        let temp_dir = tempdir().unwrap();
+        fs::create_dir(temp_dir.path().join("dir")).unwrap();
+        fs::write(temp_dir.path().join("notes.txt"), "hello\n").unwrap();
+
+        let mut files = list_files_detailed(temp_dir.path(), &ListOptions::default()).unwrap();
+        apply(&mut files, &StdFileSystem);
+        assert_eq!(files[0].content_kind, None);
+        assert_eq!(files[1].content_kind.as_deref(), Some("text"));
+    }
+}
@@ -0,0 +1,103 @@
+use std::collections::HashSet;
+
+use clap::ValueEnum;
+
+use crate::file_system::Metadata;
+
+/// What to do when a recursive listing runs into a directory it is already inside of
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum CyclePolicy {
+    /// Silently skip the directory
+    Skip,
+    /// Skip the directory and print a warning
+    #[default]
+    Warn,
+    /// Print an error and stop with a non-zero exit status
+    Error,
+}
+
+/// Tracks the directories on the current traversal path by device and inode
+#[derive(Debug, Default)]
+pub struct CycleGuard {
+    ancestors: Vec<(u64, u64)>,
+}
+
+impl CycleGuard {
+    /// Records that the traversal is descending into a directory
+    ///
+    /// # Arguments
+    ///
+    /// * `metadata` - Metadata of the directory, with symlinks followed
+    ///
+    /// # Returns
+    ///
+    /// false if the directory is already on the traversal path, in which case nothing is recorded
+    pub fn enter(&mut self, metadata: &Metadata) -> bool {
+        let id = (metadata.dev, metadata.ino);
+        if self.ancestors.contains(&id) {
+            return false;
+        }
+        self.ancestors.push(id);
+        true
+    }
+
+    /// Records that the traversal has finished with the most recently entered directory
+    pub fn leave(&mut self) {
+        self.ancestors.pop();
+    }
+}
+
+/// Tracks every directory a traversal has listed by device and inode, for walks that
+/// are not depth-first and so have no single path of ancestors
+#[derive(Debug, Default)]
+pub struct Visited {
+    seen: HashSet<(u64, u64)>,
+}
+
+impl Visited {
+    /// Records a directory about to be listed
+    ///
+    /// # Arguments
+    ///
+    /// * `metadata` - Metadata of the directory, with symlinks followed
+    ///
+    /// # Returns
+    ///
+    /// false if the directory was listed before
+    pub fn first_visit(&mut self, metadata: &Metadata) -> bool {
+        self.seen.insert((metadata.dev, metadata.ino))
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::{fs, os::unix::fs::symlink};
+    use tempfile::tempdir;
+
+    fn metadata<P: AsRef<std::path::Path>>(path: P) -> std::io::Result<Metadata> {
+        fs::metadata(path).map(Metadata::from)
+    }
+
+    #[test]
+    fn test_cycle_guard() {
+        let temp_dir = tempdir().expect("Unable to create temporary directory");
+        let dir_path = temp_dir.path();
+        fs::create_dir(dir_path.join("sub")).expect("Unable to create sub");
+        symlink("..", dir_path.join("sub/up")).expect("Unable to create up link");
+
+        let mut guard = CycleGuard::default();
+        assert!(guard.enter(&metadata(dir_path).unwrap()));
+        assert!(guard.enter(&metadata(dir_path.join("sub")).unwrap()));
+        assert!(!guard.enter(&metadata(dir_path.join("sub/up")).unwrap()));
+
+        guard.leave();
+        guard.leave();
+        assert!(guard.enter(&metadata(dir_path.join("sub/up")).unwrap()));
+
+        let mut visited = Visited::default();
+        assert!(visited.first_visit(&metadata(dir_path).unwrap()));
+        assert!(visited.first_visit(&metadata(dir_path.join("sub")).unwrap()));
+        assert!(!visited.first_visit(&metadata(dir_path.join("sub/up")).unwrap()));
+    }
+}
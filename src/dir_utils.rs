@@ -1,300 +1,1534 @@
 use std::{
-    fs,
-    os::unix::fs::{MetadataExt, PermissionsExt},
+    cmp::Ordering,
+    collections::HashSet,
+    error::Error,
+    ffi::OsStr,
+    fmt, io,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering as AtomicOrdering},
+        Arc, Mutex,
+    },
     time::{SystemTime, UNIX_EPOCH},
 };
 
 use chrono::{DateTime, Local};
-use humansize::{format_size, BINARY};
-use tabled::Tabled;
-use users::{get_user_by_uid, get_group_by_gid};
+use clap::ValueEnum;
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+use rayon::iter::{IntoParallelIterator, IntoParallelRefIterator, IntoParallelRefMutIterator, ParallelIterator};
 
-#[derive(Tabled)]
+use crate::{
+    ascii,
+    file_system::{FileSystem, FileType, Metadata, StdFileSystem},
+    filter::Filter,
+    ids::{group_name, user_name},
+    media, readonly, terminal,
+};
+
+/// What the long format knows about an entry, as raw values; `render` turns them into cells
 pub struct FileInfo {
-    pub permissions: String,
-    pub links: String,
-    pub owner: String,
-    pub group: String,
-    pub size: String,
-    pub modified: String,
+    pub owner: Arc<str>,
+    pub group: Arc<str>,
     pub name: String,
-    #[tabled(skip)]
     pub is_dir: bool,
-    #[tabled(skip)]
+    /// The full `st_mode`: the file type bits, and the permission bits including
+    /// setuid, setgid and sticky
+    pub mode: u32,
+    /// Windows file attributes such as hidden, read-only and system; always 0 elsewhere
+    pub attributes: u32,
     pub file_size: u64,
-    #[tabled(skip)]
+    /// Space the file takes up on disk, as `st_blocks` × 512 bytes
+    pub allocated_size: u64,
+    /// What the size column shows for a directory instead of `-`, if anything
+    pub dir_contents: Option<DirContents>,
+    pub modified_time: SystemTime,
+    pub link_count: u64,
+    pub inode: u64,
+    pub device: u64,
+    pub changed_time: SystemTime,
+    /// When the file was created, None if the file system does not record it
+    pub created_time: Option<SystemTime>,
+    pub accessed_time: SystemTime,
+    pub path: PathBuf,
+    pub uid: u32,
+    pub gid: u32,
+    pub kind: FileKind,
+    /// The kind of entry a symlink resolves to, None if not a symlink or broken
+    pub target_kind: Option<FileKind>,
+    /// Where the entry points to, if it is a symlink
+    pub link_target: Option<PathBuf>,
+    /// The fully resolved path of a symlink that points to another symlink, shown
+    /// when listing target information
+    pub resolved_target: Option<PathBuf>,
+    /// The content hash, filled in only for `--hash`
+    pub hash: Option<String>,
+    /// Whether a regular file holds text or binary data, filled in only for the kind column
+    pub content_kind: Option<String>,
+    /// Image dimensions or running time, filled in only for the media column
+    pub media: Option<String>,
+    /// When a photo was taken, from its EXIF data; only read when sorting by it
+    pub captured_time: Option<SystemTime>,
+}
+
+impl FileInfo {
+    /// Appends the symlink target to the displayed name, like `name -> target`,
+    /// marking targets that do not exist with `(broken)`, and then where a chain of
+    /// links finally leads, like `name -> target ⇒ /resolved/path`
+    ///
+    /// # Arguments
+    ///
+    /// * `ascii` - Whether to avoid the non-ASCII arrow
+    /// * `text` - Turns a target into the text it is shown as, quoted like the name
+    pub fn append_link_target(&mut self, ascii: bool, text: impl Fn(&OsStr) -> String) {
+        if let Some(target) = self.link_target.take() {
+            let broken = if self.kind == FileKind::BrokenSymlink { " (broken)" } else { "" };
+            self.name = format!("{} -> {}{}", self.name, text(target.as_os_str()), broken);
+        }
+        if let Some(resolved) = self.resolved_target.take() {
+            self.name = format!("{} {} {}", self.name, if ascii { "=>" } else { "\u{21d2}" }, text(resolved.as_os_str()));
+        }
+    }
+
+    /// The entry's device and inode if it may share them with other hard links, for `--hardlinks`
+    pub fn file_id(&self) -> Option<(u64, u64)> {
+        (self.link_count > 1 && !self.is_dir).then_some((self.device, self.inode))
+    }
+
+    /// Whether the file takes up much less space on disk than its length, as sparse
+    /// files such as VM images do; files within a block of their length never count
+    pub fn is_sparse(&self) -> bool {
+        !self.is_dir && self.file_size > self.allocated_size + 4096 && self.allocated_size < self.file_size / 2
+    }
+}
+
+/// Maximum owner and group name widths seen across listing sections
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ColumnWidths {
+    pub owner: usize,
+    pub group: usize,
+}
+
+impl ColumnWidths {
+    /// Widens the cached widths to fit every entry in `files`
+    pub fn update(&mut self, files: &[FileInfo]) {
+        for file in files {
+            self.owner = self.owner.max(terminal::visible_width(&file.owner));
+            self.group = self.group.max(terminal::visible_width(&file.group));
+        }
+    }
+
+    /// Pads the owner and group columns of `files` to the cached widths
+    pub fn apply(&self, files: &mut [FileInfo]) {
+        for file in files {
+            file.owner = terminal::pad(&file.owner, self.owner).into();
+            file.group = terminal::pad(&file.group, self.group).into();
+        }
+    }
+}
+
+/// A directory, or an entry in one, that could not be read for listing
+#[derive(Debug)]
+pub struct LsError {
+    pub path: PathBuf,
+    pub source: io::Error,
+    /// Whether the directory itself could not be opened, rather than one of its entries
+    directory: bool,
+}
+
+impl LsError {
+    /// Wraps the error the directory at `path` could not be read with
+    pub fn new(path: &Path, source: io::Error) -> Self {
+        LsError { path: path.to_path_buf(), source, directory: true }
+    }
+
+    /// Wraps the error the metadata of the entry at `path` could not be read with
+    pub fn entry(path: &Path, source: io::Error) -> Self {
+        LsError { path: path.to_path_buf(), source, directory: false }
+    }
+}
+
+impl fmt::Display for LsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Like GNU ls, a directory that exists but cannot be read is reported differently
+        // from a path that cannot be found
+        let action = if self.directory && self.source.kind() != io::ErrorKind::NotFound { "open directory" } else { "access" };
+        write!(f, "cannot {} '{}': {}", action, ascii::os_text(self.path.as_os_str()), self.source)
+    }
+}
+
+impl Error for LsError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+impl From<LsError> for io::Error {
+    fn from(err: LsError) -> Self {
+        io::Error::new(err.source.kind(), err.to_string())
+    }
+}
+
+/// Opens a directory for listing
+///
+/// # Arguments
+///
+/// * `path` - The directory to read
+///
+/// # Returns
+///
+/// An iterator over the directory's entries, or the error naming the directory
+pub fn open_dir(path: &Path) -> Result<readonly::Entries, LsError> {
+    readonly::read_dir(path).map_err(|err| LsError::new(path, err))
+}
+
+/// Finds where a symlink that points to another symlink finally leads
+///
+/// # Arguments
+///
+/// * `path` - The entry's path
+/// * `file_system` - Where the entry lives
+///
+/// # Returns
+///
+/// The canonical path at the end of the chain, or None if the entry is not a link to
+/// a link or the chain is broken
+fn resolve_chain(path: &Path, file_system: &dyn FileSystem) -> Option<PathBuf> {
+    let target = file_system.read_link(path).ok()?;
+    let target = path.parent().map_or_else(|| target.clone(), |parent| parent.join(&target));
+    if !file_system.symlink_metadata(&target).ok()?.is_symlink() {
+        return None;
+    }
+    file_system.canonicalize(path).ok()
+}
+
+/// Fetches a listed entry's metadata from its file system, following a symlink under `-L`
+///
+/// A symlink whose target does not exist is described by the link itself.
+///
+/// # Arguments
+///
+/// * `path` - The entry's path
+/// * `options` - Whether symlinks are followed and where the entry lives
+pub fn entry_metadata(path: &Path, options: &ListOptions) -> io::Result<Metadata> {
+    let file_system = &*options.file_system;
+    if options.dereference {
+        file_system.metadata(path).or_else(|_| file_system.symlink_metadata(path))
+    } else {
+        file_system.symlink_metadata(path)
+    }
+}
+
+/// Entries left out of listings because they could not be read, held until the caller
+/// reports them
+///
+/// Clones share what is recorded, so every listing made with copies of one
+/// [`ListOptions`] records into the same place.
+#[derive(Clone, Debug, Default)]
+pub struct EntryErrors(Arc<Mutex<Vec<LsError>>>);
+
+impl EntryErrors {
+    /// Notes an entry that is left out of a listing because it could not be read
+    fn record(&self, err: LsError) {
+        self.lock().push(err);
+    }
+
+    /// Keeps an entry, or notes the error it could not be read with and leaves it out
+    fn keep<T>(&self, entry: Result<T, LsError>) -> Option<T> {
+        entry.map_err(|err| self.record(err)).ok()
+    }
+
+    /// Takes the entries left out since the last call, so they can be reported
+    ///
+    /// # Returns
+    ///
+    /// The errors, in the order they were met
+    pub fn take(&self) -> Vec<LsError> {
+        std::mem::take(&mut *self.lock())
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<LsError>> {
+        self.0.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// How many entries a directory needs before their metadata is fetched on several threads
+static PARALLEL_THRESHOLD: AtomicUsize = AtomicUsize::new(10_000);
+
+/// Sets how many threads fetch metadata, as `--jobs` asks
+///
+/// Without it only directories of 10,000 entries or more are fetched in parallel;
+/// asking for several threads uses them in every directory.
+///
+/// # Arguments
+///
+/// * `jobs` - The number of threads; 1 fetches metadata sequentially
+pub fn set_jobs(jobs: usize) {
+    if jobs <= 1 {
+        PARALLEL_THRESHOLD.store(usize::MAX, AtomicOrdering::Relaxed);
+    } else {
+        PARALLEL_THRESHOLD.store(0, AtomicOrdering::Relaxed);
+        // Only fails if the pool was already started, in which case it is kept
+        let _ = rayon::ThreadPoolBuilder::new().num_threads(jobs).build_global();
+    }
+}
+
+/// Checks whether a directory has enough entries to fetch their metadata in parallel
+fn parallel(entries: usize) -> bool {
+    entries >= PARALLEL_THRESHOLD.load(AtomicOrdering::Relaxed)
+}
+
+/// Fetches the metadata of a directory's entries, keeping those the filter matches
+///
+/// # Arguments
+///
+/// * `candidates` - The name and full path of each entry
+/// * `options` - The criteria entries must match to be kept and where they live
+/// * `parallel` - Whether to fetch metadata on several threads
+///
+/// # Returns
+///
+/// The kept entries with their metadata, in the order they were given
+fn fetch_metadata(candidates: Vec<(String, PathBuf)>, options: &ListOptions, parallel: bool) -> Vec<(String, PathBuf, Metadata)> {
+    let stat = |(name, path): (String, PathBuf)| {
+        let metadata = entry_metadata(&path, options).map_err(|err| options.errors.record(LsError::entry(&path, err))).ok()?;
+        keeps_metadata(&path, &metadata, options).then_some((name, path, metadata))
+    };
+    if parallel {
+        candidates.into_par_iter().filter_map(stat).collect()
+    } else {
+        candidates.into_iter().filter_map(stat).collect()
+    }
+}
+
+/// Deduplicates strings that most entries of a listing share, such as owner and
+/// group, so each distinct value is allocated once
+#[derive(Default, Debug)]
+pub struct Interner {
+    strings: HashSet<Arc<str>>,
+}
+
+impl Interner {
+    /// Returns the shared copy of `text`, storing it first if it is new
+    pub fn intern(&mut self, text: String) -> Arc<str> {
+        if let Some(shared) = self.strings.get(text.as_str()) {
+            return Arc::clone(shared);
+        }
+        let shared: Arc<str> = text.into();
+        self.strings.insert(Arc::clone(&shared));
+        shared
+    }
+}
+
+/// Builds the long format information for an entry from already fetched metadata
+///
+/// # Arguments
+///
+/// * `file_name` - The entry's file name
+/// * `path` - The entry's full path, used to resolve symlink targets
+/// * `metadata` - The entry's metadata
+/// * `options` - Which symlinks are described by their targets and where the entry lives
+/// * `interner` - Shares owner and group strings between entries
+///
+/// # Returns
+///
+/// FileInfo containing the entry's metadata
+fn file_info_from_metadata(file_name: &str, path: &Path, metadata: &Metadata, options: &ListOptions, interner: &mut Interner) -> FileInfo {
+    let file_system = &*options.file_system;
+    // The link itself still decides the name's kind, color and arrow
+    let link_metadata = metadata;
+    let target_metadata = (options.target_info && metadata.is_symlink()).then(|| file_system.metadata(path).ok()).flatten();
+    let metadata = target_metadata.as_ref().unwrap_or(metadata);
+    let resolved_target = if options.dereference || options.target_info { resolve_chain(path, file_system) } else { None };
+
+    let owner = interner.intern(metadata.owner.clone().unwrap_or_else(|| user_name(metadata.uid)));
+    let group = interner.intern(metadata.group.clone().unwrap_or_else(|| group_name(metadata.gid)));
+
+    // Get modification time
+    let modified_time = metadata.modified.unwrap_or(SystemTime::now());
+
+    FileInfo {
+        owner,
+        group,
+        name: file_name.to_string(),
+        is_dir: metadata.is_dir(),
+        mode: metadata.mode,
+        attributes: metadata.attributes,
+        file_size: metadata.len,
+        allocated_size: metadata.blocks * 512,
+        dir_contents: None,
+        modified_time,
+        link_count: metadata.nlink,
+        inode: metadata.ino,
+        device: metadata.dev,
+        changed_time: metadata.changed.unwrap_or(modified_time),
+        created_time: metadata.created,
+        accessed_time: metadata.accessed.unwrap_or(modified_time),
+        path: path.to_path_buf(),
+        uid: metadata.uid,
+        gid: metadata.gid,
+        kind: file_kind(path, link_metadata, file_system),
+        target_kind: target_kind(path, link_metadata, file_system),
+        link_target: link_metadata.is_symlink().then(|| file_system.read_link(path).unwrap_or_default()),
+        resolved_target,
+        hash: None,
+        content_kind: None,
+        media: None,
+        captured_time: None,
+    }
+}
+
+/// How the long format shows timestamps
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum TimeStyle {
+    /// The date and time, like `Jan  5 14:03`
+    #[default]
+    Default,
+    /// How long ago, like `3m`, `2h`, `5d` or `1y`
+    Relative,
+    /// The full time with nanoseconds and the UTC offset, like `2025-01-05 14:03:22.123456789 +0100`
+    FullIso,
+}
+
+/// Where the system keeps its time zone database, unless `TZDIR` says otherwise
+const ZONEINFO: &str = "/usr/share/zoneinfo";
+
+/// Sets the time zone timestamps are shown in, as `--time-zone` asks
+///
+/// Named zones come from the system time zone database. The zone is passed on
+/// through `TZ`, so this must run before any timestamp is formatted, and before any
+/// other thread is started (including the pool [`set_jobs`] builds), since changing
+/// the environment while other threads may read it is unsound.
+///
+/// # Arguments
+///
+/// * `zone` - `local`, `UTC`, or an IANA name such as `Europe/Berlin`
+///
+/// # Returns
+///
+/// An error naming the zone if the database does not know it
+pub fn set_time_zone(zone: &str) -> Result<(), String> {
+    if zone.eq_ignore_ascii_case("local") {
+        return Ok(());
+    }
+    let tz = if zone.eq_ignore_ascii_case("utc") {
+        "UTC0".to_string()
+    } else {
+        let zoneinfo = std::env::var_os("TZDIR").map(PathBuf::from).unwrap_or_else(|| PathBuf::from(ZONEINFO));
+        let known = !zone.is_empty()
+            && Path::new(zone).components().all(|part| matches!(part, std::path::Component::Normal(_)))
+            && zoneinfo.join(zone).is_file();
+        if !known {
+            return Err(format!("unknown time zone '{}'", zone));
+        }
+        zone.to_string()
+    };
+    std::env::set_var("TZ", tz);
+    Ok(())
+}
+
+/// Formats a timestamp the way the long format displays it, in the chosen time style
+///
+/// # Arguments
+///
+/// * `time` - The timestamp to format
+/// * `style` - How the timestamp is shown, as `--time-style` asks
+///
+/// # Returns
+///
+/// The local time as a string like "Jan  5 14:03", or the age like "  5d"
+pub fn format_time(time: SystemTime, style: TimeStyle) -> String {
+    let local = || -> DateTime<Local> {
+        time.duration_since(UNIX_EPOCH)
+            .ok()
+            .map(|d| DateTime::from(UNIX_EPOCH + d))
+            .unwrap_or_else(Local::now)
+    };
+    match style {
+        TimeStyle::Default => local().format("%b %e %H:%M").to_string(),
+        TimeStyle::Relative => format_age(time, SystemTime::now()),
+        TimeStyle::FullIso => local().format("%Y-%m-%d %H:%M:%S%.9f %z").to_string(),
+    }
+}
+
+/// Formats how long before `now` a timestamp was, in its largest whole unit and
+/// right-aligned so ages line up in a column
+///
+/// # Arguments
+///
+/// * `time` - The timestamp to describe
+/// * `now` - The time to measure from
+///
+/// # Returns
+///
+/// The age like "  3m", or like "in 2h" for timestamps in the future
+pub fn format_age(time: SystemTime, now: SystemTime) -> String {
+    const UNITS: [(u64, &str); 5] = [(365 * 86_400, "y"), (86_400, "d"), (3_600, "h"), (60, "m"), (1, "s")];
+    let (seconds, future) = match now.duration_since(time) {
+        Ok(age) => (age.as_secs(), false),
+        Err(err) => (err.duration().as_secs(), true),
+    };
+    let (length, unit) = UNITS.iter().find(|(length, _)| seconds >= *length).unwrap_or(&(1, "s"));
+    let age = format!("{}{}", seconds / length, unit);
+    if future {
+        format!("in {}", age)
+    } else {
+        format!("{:>4}", age)
+    }
+}
+
+/// Parses a size such as `512`, `10K`, `1.5M` or `2G` into bytes, using binary multiples
+///
+/// # Arguments
+///
+/// * `text` - A number with an optional K/M/G/T suffix (case-insensitive, optional trailing `B` or `iB`)
+///
+/// # Returns
+///
+/// The size in bytes, or None if the text is not a valid size
+pub fn parse_size(text: &str) -> Option<u64> {
+    let text = text.trim();
+    let upper = text.to_ascii_uppercase();
+    let upper = upper.strip_suffix("IB").or_else(|| upper.strip_suffix('B')).unwrap_or(&upper);
+    let (number, multiplier) = match upper.chars().last()? {
+        'K' => (&upper[..upper.len() - 1], 1u64 << 10),
+        'M' => (&upper[..upper.len() - 1], 1 << 20),
+        'G' => (&upper[..upper.len() - 1], 1 << 30),
+        'T' => (&upper[..upper.len() - 1], 1 << 40),
+        _ => (upper, 1),
+    };
+    if let Ok(whole) = number.parse::<u64>() {
+        return whole.checked_mul(multiplier);
+    }
+    let fractional = number.parse::<f64>().ok().filter(|n| n.is_finite() && *n >= 0.0)?;
+    Some((fractional * multiplier as f64) as u64)
+}
+
+/// The type of a directory entry, as used for classification and coloring
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileKind {
+    File,
+    Directory,
+    Symlink,
+    /// A symlink whose target does not exist
+    BrokenSymlink,
+    Executable,
+    Pipe,
+    Socket,
+    BlockDevice,
+    CharDevice,
+}
+
+impl FileKind {
+    /// Names the kind in words, as shown after entry names in `--screen-reader` mode
+    ///
+    /// # Returns
+    ///
+    /// The description, or None for regular files
+    pub fn description(self) -> Option<&'static str> {
+        match self {
+            FileKind::File => None,
+            FileKind::Directory => Some("directory"),
+            FileKind::Symlink => Some("link"),
+            FileKind::BrokenSymlink => Some("broken link"),
+            FileKind::Executable => Some("executable"),
+            FileKind::Pipe => Some("pipe"),
+            FileKind::Socket => Some("socket"),
+            FileKind::BlockDevice => Some("block device"),
+            FileKind::CharDevice => Some("character device"),
+        }
+    }
+
+    /// The indicator `-F` style classification appends for this kind, if any
+    pub fn indicator(self) -> &'static str {
+        match self {
+            FileKind::Directory => "/",
+            FileKind::Executable => "*",
+            FileKind::Symlink | FileKind::BrokenSymlink => "@",
+            FileKind::Pipe => "|",
+            FileKind::Socket => "=",
+            FileKind::File | FileKind::BlockDevice | FileKind::CharDevice => "",
+        }
+    }
+}
+
+/// Determines the kind of an entry from its (non-followed) metadata
+///
+/// # Arguments
+///
+/// * `path` - The entry's full path, used to check whether a symlink's target exists
+/// * `metadata` - The entry's metadata, not following symlinks
+/// * `file_system` - Where the entry lives
+pub fn file_kind(path: &Path, metadata: &Metadata, file_system: &dyn FileSystem) -> FileKind {
+    match metadata.file_type {
+        FileType::Directory => FileKind::Directory,
+        FileType::Symlink if file_system.metadata(path).is_ok() => FileKind::Symlink,
+        FileType::Symlink => FileKind::BrokenSymlink,
+        FileType::Fifo => FileKind::Pipe,
+        FileType::Socket => FileKind::Socket,
+        FileType::BlockDevice => FileKind::BlockDevice,
+        FileType::CharDevice => FileKind::CharDevice,
+        FileType::File if metadata.is_executable() => FileKind::Executable,
+        FileType::File => FileKind::File,
+    }
+}
+
+/// Determines the kind of entry a symlink points to
+///
+/// # Arguments
+///
+/// * `path` - The entry's full path
+/// * `metadata` - The entry's metadata, not following symlinks
+/// * `file_system` - Where the entry lives
+///
+/// # Returns
+///
+/// The target's kind, or None if the entry is not a symlink or its target does not exist
+pub fn target_kind(path: &Path, metadata: &Metadata, file_system: &dyn FileSystem) -> Option<FileKind> {
+    if !metadata.is_symlink() {
+        return None;
+    }
+    file_system.metadata(path).ok().map(|target| file_kind(path, &target, file_system))
+}
+
+/// Adds file type indicator to filename based on file type
+///
+/// # Arguments
+///
+/// * `name` - The filename
+/// * `metadata` - File metadata to determine type
+///
+/// # Returns
+///
+/// Filename with appropriate indicator appended
+fn add_file_type_indicator(name: &str, metadata: &Metadata) -> String {
+    let indicator = if metadata.is_dir() {
+        "/"
+    } else if metadata.is_executable() {
+        "*" // executable
+    } else {
+        ""
+    };
+    format!("{}{}", name, indicator)
+}
+
+/// A key that listings can be sorted by
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum SortKey {
+    /// Alphabetically by file name
+    Name,
+    /// By file size, largest first
+    Size,
+    /// By modification time, newest first
+    Time,
+    /// Alphabetically by extension; entries without one come first
+    Extension,
+    /// Alphabetically by owner name
+    Owner,
+    /// Alphabetically by group name
+    Group,
+    /// By hard link count, most links first
+    Links,
+    /// By inode number, lowest first
+    Inode,
+    /// Shuffled into a random order, reproducible with a seed
+    Random,
+    /// By name, comparing runs of digits as numbers (file2 before file10)
+    Version,
+    /// By creation time, newest first; entries without one come last
+    Created,
+    /// By when a photo was taken, from its EXIF data, newest first; entries without one come last
+    Captured,
+}
+
+/// How a listing is ordered
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SortSpec {
+    /// Sort keys in priority order; ties are broken by name
+    pub keys: Vec<SortKey>,
+    /// Whether to reverse the sort order
+    pub reverse: bool,
+    /// Where hidden entries are placed in the sorted listing
+    pub dotfiles: DotfileOrder,
+    /// Whether to skip sorting entirely
+    pub unsorted: bool,
+    /// Seed for `SortKey::Random`; a fresh random order is used when unset
+    pub seed: Option<u64>,
+}
+
+impl SortSpec {
+    /// Sorts by the given keys in priority order
+    pub fn by(keys: Vec<SortKey>) -> Self {
+        SortSpec { keys, ..Default::default() }
+    }
+
+    /// Sets whether the order is reversed
+    pub fn reverse(mut self, reverse: bool) -> Self {
+        self.reverse = reverse;
+        self
+    }
+
+    /// Sets where hidden entries are placed
+    pub fn dotfiles(mut self, dotfiles: DotfileOrder) -> Self {
+        self.dotfiles = dotfiles;
+        self
+    }
+
+    /// Sets whether entries are left in directory order
+    pub fn unsorted(mut self, unsorted: bool) -> Self {
+        self.unsorted = unsorted;
+        self
+    }
+
+    /// Sets the seed for `SortKey::Random`
+    pub fn seed(mut self, seed: Option<u64>) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Checks whether the listing is ordered by the given key
+    fn sorts_by(&self, key: SortKey) -> bool {
+        !self.unsorted && self.keys.contains(&key)
+    }
+
+    /// Checks whether ordering entries looks at more than their names
+    pub fn needs_metadata(&self) -> bool {
+        !self.unsorted
+            && self.keys.iter().any(|key| {
+                matches!(key, SortKey::Size | SortKey::Time | SortKey::Created | SortKey::Captured | SortKey::Owner | SortKey::Group | SortKey::Links | SortKey::Inode)
+            })
+    }
+}
+
+/// What a listing includes and how it is ordered
+///
+/// Built from the command line with `Args::list_options`, or step by step:
+/// `ListOptions::default().almost_all(true).sort(SortSpec::by(vec![SortKey::Size]))`.
+#[derive(Clone, Debug)]
+pub struct ListOptions {
+    /// Include hidden entries, along with `.` and `..`, like `-a`
+    pub all: bool,
+    /// Include hidden entries but not `.` and `..`, like `-A`; wins over `all`
+    pub almost_all: bool,
+    /// Give each entry's display name its file type indicator, like `-F`
+    pub classify: bool,
+    /// How entries are ordered
+    pub sort: SortSpec,
+    /// Criteria entries must match to be listed
+    pub filter: Filter,
+    /// Where directories and metadata are read from
+    pub file_system: Arc<dyn FileSystem>,
+    /// Where entries left out because they could not be read are recorded
+    pub errors: EntryErrors,
+    /// Describe symlink targets instead of the links themselves, like `-L`
+    pub dereference: bool,
+    /// Show the size, permissions and times of symlink targets in the long format while
+    /// keeping each link's name and arrow, like `--dereference-target-info`
+    pub target_info: bool,
+}
+
+impl Default for ListOptions {
+    fn default() -> Self {
+        ListOptions {
+            all: false,
+            almost_all: false,
+            classify: false,
+            sort: SortSpec::default(),
+            filter: Filter::default(),
+            file_system: Arc::new(StdFileSystem),
+            errors: EntryErrors::default(),
+            dereference: false,
+            target_info: false,
+        }
+    }
+}
+
+impl ListOptions {
+    /// Sets whether hidden entries and `.` and `..` are included
+    pub fn all(mut self, all: bool) -> Self {
+        self.all = all;
+        self
+    }
+
+    /// Sets whether hidden entries other than `.` and `..` are included
+    pub fn almost_all(mut self, almost_all: bool) -> Self {
+        self.almost_all = almost_all;
+        self
+    }
+
+    /// Sets whether display names get file type indicators
+    pub fn classify(mut self, classify: bool) -> Self {
+        self.classify = classify;
+        self
+    }
+
+    /// Sets how entries are ordered
+    pub fn sort(mut self, sort: SortSpec) -> Self {
+        self.sort = sort;
+        self
+    }
+
+    /// Sets the criteria entries must match
+    pub fn filter(mut self, filter: Filter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Sets where directories and metadata are read from
+    pub fn file_system(mut self, file_system: impl FileSystem + 'static) -> Self {
+        self.file_system = Arc::new(file_system);
+        self
+    }
+
+    /// Sets whether entries are described by their symlink targets
+    pub fn dereference(mut self, dereference: bool) -> Self {
+        self.dereference = dereference;
+        self
+    }
+
+    /// Sets whether the long format shows what symlinks point to
+    pub fn target_info(mut self, target_info: bool) -> Self {
+        self.target_info = target_info;
+        self
+    }
+}
+
+/// Where hidden entries are placed relative to the rest of a sorted listing
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum DotfileOrder {
+    /// Group hidden entries before all others
+    First,
+    /// Group hidden entries after all others
+    Last,
+    /// Interleave hidden entries with the rest
+    #[default]
+    Mixed,
+}
+
+/// What the long format's size column shows for directories
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum DirSize {
+    /// Show `-`
+    #[default]
+    None,
+    /// Show how many entries the directory holds, without descending into it
+    Entries,
+    /// Show the size of the directory itself, as the file system reports it
+    Bytes,
+}
+
+impl DirSize {
+    /// Fills in what the size column shows for the directories among `files`
+    ///
+    /// # Arguments
+    ///
+    /// * `files` - The long format entries to update
+    /// * `file_system` - Where the directories are read from
+    pub fn apply(&self, files: &mut [FileInfo], file_system: &dyn FileSystem) {
+        for file in files.iter_mut().filter(|file| file.is_dir) {
+            file.dir_contents = match self {
+                DirSize::None => continue,
+                // Directories that cannot be read have no count to show
+                DirSize::Entries => Some(DirContents::Entries(file_system.read_dir(&file.path).ok().map(|entries| entries.count() as u64))),
+                DirSize::Bytes => Some(DirContents::Bytes(file.file_size)),
+            };
+        }
+    }
+}
+
+/// What the size column shows for a directory in place of `-`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DirContents {
+    /// How many entries it holds, None if it cannot be read
+    Entries(Option<u64>),
+    /// A size in bytes, such as the directory's own or its whole tree's
+    Bytes(u64),
+}
+
+/// Values an entry exposes so it can be ordered by any `SortKey`
+pub trait SortFields {
+    fn sort_name(&self) -> &str;
+    fn sort_size(&self) -> u64;
+    fn sort_time(&self) -> SystemTime;
+    fn sort_created(&self) -> SystemTime;
+    fn sort_captured(&self) -> SystemTime;
+    fn sort_owner(&self) -> &str;
+    fn sort_group(&self) -> &str;
+    fn sort_links(&self) -> u64;
+    fn sort_inode(&self) -> u64;
+}
+
+impl SortFields for FileInfo {
+    fn sort_name(&self) -> &str {
+        &self.name
+    }
+
+    fn sort_size(&self) -> u64 {
+        self.file_size
+    }
+
+    fn sort_time(&self) -> SystemTime {
+        self.modified_time
+    }
+
+    fn sort_created(&self) -> SystemTime {
+        self.created_time.unwrap_or(UNIX_EPOCH)
+    }
+
+    fn sort_captured(&self) -> SystemTime {
+        self.captured_time.unwrap_or(UNIX_EPOCH)
+    }
+
+    fn sort_owner(&self) -> &str {
+        &self.owner
+    }
+
+    fn sort_group(&self) -> &str {
+        &self.group
+    }
+
+    fn sort_links(&self) -> u64 {
+        self.link_count
+    }
+
+    fn sort_inode(&self) -> u64 {
+        self.inode
+    }
+}
+
+/// Bare names, kept with their paths, can only be ordered by the keys derived from
+/// the name; the other keys compare every name as equal
+impl SortFields for (String, PathBuf) {
+    fn sort_name(&self) -> &str {
+        &self.0
+    }
+
+    fn sort_size(&self) -> u64 {
+        0
+    }
+
+    fn sort_time(&self) -> SystemTime {
+        UNIX_EPOCH
+    }
+
+    fn sort_created(&self) -> SystemTime {
+        UNIX_EPOCH
+    }
+
+    fn sort_captured(&self) -> SystemTime {
+        UNIX_EPOCH
+    }
+
+    fn sort_owner(&self) -> &str {
+        ""
+    }
+
+    fn sort_group(&self) -> &str {
+        ""
+    }
+
+    fn sort_links(&self) -> u64 {
+        0
+    }
+
+    fn sort_inode(&self) -> u64 {
+        0
+    }
+}
+
+/// An entry collected for the short listing
+pub struct Entry {
+    pub name: String,
+    /// The name as displayed, including any file type indicator
+    pub display_name: String,
+    /// The entry's full path
+    pub path: PathBuf,
+    pub metadata: Metadata,
+    pub kind: FileKind,
+    /// The kind of entry a symlink resolves to, None if not a symlink or broken
+    pub target_kind: Option<FileKind>,
     pub modified_time: SystemTime,
+    /// Owner and group names, only resolved when a sort key needs them
+    owner: String,
+    group: String,
+    /// When a photo was taken, only read when sorting by it
+    captured: Option<SystemTime>,
+}
+
+impl Entry {
+    /// The file type indicator appended to the name when classifying, or an empty string
+    pub fn indicator(&self) -> &str {
+        &self.display_name[self.name.len()..]
+    }
+
+    /// The entry's device and inode if it may share them with other hard links, for `--hardlinks`
+    pub fn file_id(&self) -> Option<(u64, u64)> {
+        (self.metadata.nlink > 1 && !self.metadata.is_dir()).then_some((self.metadata.dev, self.metadata.ino))
+    }
+
+    /// Whether this is the `.` or `..` entry `-a` lists, which recursion never descends into
+    pub fn is_dot(&self) -> bool {
+        self.name == "." || self.name == ".."
+    }
+
+    /// The entry's full path as displayed, including any file type indicator
+    pub fn display_path(&self) -> String {
+        format!("{}{}", ascii::os_text(self.path.as_os_str()), self.indicator())
+    }
+}
+
+impl SortFields for Entry {
+    fn sort_name(&self) -> &str {
+        &self.name
+    }
+
+    fn sort_size(&self) -> u64 {
+        self.metadata.len
+    }
+
+    fn sort_time(&self) -> SystemTime {
+        self.modified_time
+    }
+
+    fn sort_created(&self) -> SystemTime {
+        self.metadata.created.unwrap_or(UNIX_EPOCH)
+    }
+
+    fn sort_captured(&self) -> SystemTime {
+        self.captured.unwrap_or(UNIX_EPOCH)
+    }
+
+    fn sort_owner(&self) -> &str {
+        &self.owner
+    }
+
+    fn sort_group(&self) -> &str {
+        &self.group
+    }
+
+    fn sort_links(&self) -> u64 {
+        self.metadata.nlink
+    }
+
+    fn sort_inode(&self) -> u64 {
+        self.metadata.ino
+    }
+}
+
+/// Returns the extension of a file name, ignoring the leading dot of hidden files
+fn extension(name: &str) -> &str {
+    match name.rfind('.') {
+        Some(i) if i > 0 => &name[i + 1..],
+        _ => "",
+    }
+}
+
+/// Compares names naturally, treating each run of ASCII digits as a number
+///
+/// # Arguments
+///
+/// * `a` - The first name
+/// * `b` - The second name
+///
+/// # Returns
+///
+/// The ordering of `a` relative to `b`, e.g. `v1.9` before `v1.10`
+fn version_cmp(a: &str, b: &str) -> Ordering {
+    let (mut a, mut b) = (a, b);
+    loop {
+        let (Some(a_first), Some(b_first)) = (a.chars().next(), b.chars().next()) else {
+            return a.len().cmp(&b.len());
+        };
+        let ordering = if a_first.is_ascii_digit() && b_first.is_ascii_digit() {
+            let a_end = a.find(|c: char| !c.is_ascii_digit()).unwrap_or(a.len());
+            let b_end = b.find(|c: char| !c.is_ascii_digit()).unwrap_or(b.len());
+            let (a_digits, b_digits) = (a[..a_end].trim_start_matches('0'), b[..b_end].trim_start_matches('0'));
+            let ordering = a_digits.len().cmp(&b_digits.len()).then_with(|| a_digits.cmp(b_digits));
+            (a, b) = (&a[a_end..], &b[b_end..]);
+            ordering
+        } else {
+            (a, b) = (&a[a_first.len_utf8()..], &b[b_first.len_utf8()..]);
+            a_first.cmp(&b_first)
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+}
+
+/// Orders entries according to a sort spec
+///
+/// Entries are sorted by each key in turn, falling back to the name so ties are
+/// deterministic. A random key shuffles the entries instead.
+///
+/// # Arguments
+///
+/// * `entries` - The entries to sort in place
+/// * `sort` - The keys, direction and dotfile placement to apply
+pub fn sort_entries<T: SortFields>(entries: &mut [T], sort: &SortSpec) {
+    if sort.unsorted {
+        return;
+    }
+
+    // Dotfile grouping is not affected by reverse
+    let group = |a: &T, b: &T| {
+        let a_hidden = a.sort_name().starts_with('.');
+        let b_hidden = b.sort_name().starts_with('.');
+        match sort.dotfiles {
+            DotfileOrder::First => b_hidden.cmp(&a_hidden),
+            DotfileOrder::Last => a_hidden.cmp(&b_hidden),
+            DotfileOrder::Mixed => Ordering::Equal,
+        }
+    };
+
+    if sort.keys.contains(&SortKey::Random) {
+        entries.shuffle(&mut rng(sort.seed));
+        entries.sort_by(group);
+        return;
+    }
+
+    entries.sort_by(|a, b| {
+        let ordering = sort
+            .keys
+            .iter()
+            .chain(std::iter::once(&SortKey::Name))
+            .fold(Ordering::Equal, |ordering, key| {
+                ordering.then_with(|| match key {
+                    SortKey::Name | SortKey::Random => a.sort_name().cmp(b.sort_name()),
+                    SortKey::Size => b.sort_size().cmp(&a.sort_size()),
+                    SortKey::Time => b.sort_time().cmp(&a.sort_time()),
+                    SortKey::Created => b.sort_created().cmp(&a.sort_created()),
+                    SortKey::Captured => b.sort_captured().cmp(&a.sort_captured()),
+                    SortKey::Extension => extension(a.sort_name()).cmp(extension(b.sort_name())),
+                    SortKey::Owner => a.sort_owner().cmp(b.sort_owner()),
+                    SortKey::Group => a.sort_group().cmp(b.sort_group()),
+                    SortKey::Links => b.sort_links().cmp(&a.sort_links()),
+                    SortKey::Inode => a.sort_inode().cmp(&b.sort_inode()),
+                    SortKey::Version => version_cmp(a.sort_name(), b.sort_name()),
+                })
+            });
+        group(a, b).then(if sort.reverse { ordering.reverse() } else { ordering })
+    });
+}
+
+/// Creates the random number generator for shuffling and sampling
+fn rng(seed: Option<u64>) -> StdRng {
+    match seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_os_rng(),
+    }
+}
+
+/// Keeps only the first entries, in their current order
+///
+/// # Arguments
+///
+/// * `entries` - The entries to shorten in place
+/// * `limit` - How many entries to keep
+///
+/// # Returns
+///
+/// The number of entries dropped
+pub fn limit_entries<T>(entries: &mut Vec<T>, limit: usize) -> usize {
+    let omitted = entries.len().saturating_sub(limit);
+    entries.truncate(limit);
+    omitted
+}
+
+/// Keeps a uniform random sample of the entries, preserving their current order
+///
+/// # Arguments
+///
+/// * `entries` - The entries to sample in place
+/// * `count` - How many entries to keep
+/// * `seed` - Seed making the sample reproducible
+///
+/// # Returns
+///
+/// The number of entries before sampling
+pub fn sample_entries<T>(entries: &mut Vec<T>, count: usize, seed: Option<u64>) -> usize {
+    let total = entries.len();
+    if total <= count {
+        return total;
+    }
+
+    let mut keep = vec![false; total];
+    for index in rand::seq::index::sample(&mut rng(seed), total, count) {
+        keep[index] = true;
+    }
+    let mut keep = keep.into_iter();
+    entries.retain(|_| keep.next().unwrap_or(false));
+    total
+}
+
+/// Checks whether an entry is listed judging by its name alone
+///
+/// # Arguments
+///
+/// * `file_name` - The entry's file name
+/// * `options` - Which entries to include and the criteria the name must match
+fn keeps_name(file_name: &str, options: &ListOptions) -> bool {
+    // -A shows dotfiles on its own; only . and .. stay hidden
+    if !options.all && !options.almost_all && file_name.starts_with('.') {
+        return false;
+    }
+    if options.almost_all && (file_name == "." || file_name == "..") {
+        return false;
+    }
+    options.filter.matches_name(file_name)
+}
+
+/// Checks whether an entry whose name was kept is listed judging by its metadata
+///
+/// Entries Windows marks hidden are left out like dotfiles unless `-a` or `-A` is given.
+///
+/// # Arguments
+///
+/// * `path` - The entry's full path
+/// * `metadata` - The entry's metadata
+/// * `options` - Which entries to include, the criteria they must match and where they live
+fn keeps_metadata(path: &Path, metadata: &Metadata, options: &ListOptions) -> bool {
+    if metadata.is_hidden() && !options.all && !options.almost_all {
+        return false;
+    }
+    options.filter.matches(path, metadata, &*options.file_system)
 }
 
-/// Gets detailed information about a file or directory entry
+/// Lists files in the specified directory with full metadata for the long format
 ///
 /// # Arguments
 ///
-/// * `entry` - A reference to a directory entry to get information about
-/// * `human_readable` - Whether to format file sizes in human-readable format
+/// * `path` - Path to the directory to list files from
+/// * `options` - Which entries to include and how to order them
 ///
 /// # Returns
 ///
-/// Some(FileInfo) containing the file's metadata if successful, None if there was an error
-pub fn get_file_info(entry: &fs::DirEntry, human_readable: bool) -> Option<FileInfo> {
-    let metadata = entry.metadata().ok()?;
-    let file_name = entry.file_name();
-    let file_name = file_name.to_string_lossy();
-
-    // Get permissions
-    let mode = metadata.permissions().mode();
-    let permissions = format!(
-        "{}{}",
-        if metadata.is_dir() { "d" } else { "-" },
-        format_mode(mode)
-    );
-
-    // Get number of hard links
-    let links = metadata.nlink().to_string();
-
-    // Get file size
-    let file_size = metadata.len();
-    let size = if metadata.is_dir() {
-        "-".to_string()
-    } else if human_readable {
-        format_size(file_size, BINARY)
-    } else {
-        file_size.to_string()
-    };
+/// A vector of FileInfo entries, or an error if the directory cannot be read
+pub fn list_files_detailed<P: AsRef<Path>>(path: P, options: &ListOptions) -> Result<Vec<FileInfo>, LsError> {
+    // Names are checked first so only the remaining entries need a stat
+    let candidates: Vec<(String, PathBuf)> = named_entries(path.as_ref(), options)?.filter_map(|entry| options.errors.keep(entry)).collect();
 
-    let owner = {
-        let uid = metadata.uid();
-        get_user_by_uid(uid)
-            .map(|u| u.name().to_string_lossy().into_owned())
-            .unwrap_or_else(|| uid.to_string())
-    };
+    let parallel = parallel(candidates.len());
+    let mut interner = Interner::default();
+    let mut files: Vec<FileInfo> = fetch_metadata(candidates, options, parallel)
+        .into_iter()
+        .map(|(file_name, path, metadata)| file_info_from_metadata(&file_name, &path, &metadata, options, &mut interner))
+        .collect();
 
-    let group = {
-        let gid = metadata.gid();
-        get_group_by_gid(gid)
-            .map(|g| g.name().to_string_lossy().into_owned())
-            .unwrap_or_else(|| gid.to_string())
-    };
+    if options.sort.sorts_by(SortKey::Captured) {
+        // Read once per entry here rather than on every comparison
+        let capture = |file: &mut FileInfo| file.captured_time = media::capture_time(&file.path, &*options.file_system);
+        if parallel {
+            files.par_iter_mut().for_each(capture);
+        } else {
+            files.iter_mut().for_each(capture);
+        }
+    }
+    sort_entries(&mut files, &options.sort);
 
-    // Get modification time
-    let modified_time = metadata.modified().unwrap_or(SystemTime::now());
-    let modified: DateTime<Local> = modified_time
-        .duration_since(UNIX_EPOCH)
-        .ok()
-        .map(|d| DateTime::from(UNIX_EPOCH + d))
-        .unwrap_or_else(|| Local::now());
-
-    let modified_str = modified.format("%b %e %H:%M").to_string();
-
-    Some(FileInfo {
-        permissions,
-        links,
-        owner,
-        group,
-        size,
-        modified: modified_str,
-        name: file_name.to_string(),
-        is_dir: metadata.is_dir(),
-        file_size,
-        modified_time,
-    })
+    Ok(files)
 }
 
-/// Formats Unix file permissions mode into rwx string representation
+/// Builds the long format information for entries already collected for the short listing
 ///
 /// # Arguments
 ///
-/// * `mode` - The Unix permissions mode as a u32 bitmask
+/// * `entries` - The entries, in the order they should be listed
+/// * `options` - Which symlinks are described by their targets and where the entries were read from
 ///
 /// # Returns
 ///
-/// A string containing the rwx permissions for user, group and other (e.g. "rwxr-xr--")
-fn format_mode(mode: u32) -> String {
-    let user = (mode >> 6) & 0o7;
-    let group = (mode >> 3) & 0o7;
-    let other = mode & 0o7;
-
-    format!(
-        "{}{}{}",
-        format_rwx(user),
-        format_rwx(group),
-        format_rwx(other)
-    )
+/// A vector of FileInfo entries in the same order
+pub fn detailed_entries(entries: &[Entry], options: &ListOptions) -> Vec<FileInfo> {
+    let detail = |interner: &mut Interner, entry: &Entry| FileInfo {
+        captured_time: entry.captured,
+        ..file_info_from_metadata(&entry.name, &entry.path, &entry.metadata, options, interner)
+    };
+    if parallel(entries.len()) {
+        entries.par_iter().map_init(Interner::default, detail).collect()
+    } else {
+        let mut interner = Interner::default();
+        entries.iter().map(|entry| detail(&mut interner, entry)).collect()
+    }
 }
 
-/// Formats a 3-bit Unix permission set into rwx string notation
+/// Lists the entries of the specified directory for the short listing
 ///
 /// # Arguments
 ///
-/// * `bits` - 3 bits representing read, write, execute permissions
+/// * `path` - Path to the directory to list files from
+/// * `options` - Which entries to include, how to order them and whether to classify them
 ///
 /// # Returns
 ///
-/// A 3-character string containing 'r', 'w', 'x' for set bits or '-' for unset bits
-fn format_rwx(bits: u32) -> String {
-    let r = if bits & 0b100 != 0 { 'r' } else { '-' };
-    let w = if bits & 0b010 != 0 { 'w' } else { '-' };
-    let x = if bits & 0b001 != 0 { 'x' } else { '-' };
-    format!("{}{}{}", r, w, x)
+/// A vector of entries with their metadata, or an error if the directory cannot be read
+pub fn list_entries<P: AsRef<Path>>(path: P, options: &ListOptions) -> Result<Vec<Entry>, LsError> {
+    let sort = &options.sort;
+    let needs = SortNeeds::of(sort);
+    let candidates: Vec<(String, PathBuf)> = named_entries(path.as_ref(), options)?.filter_map(|entry| options.errors.keep(entry)).collect();
+    let parallel = parallel(candidates.len());
+    let fetched = fetch_metadata(candidates, options, parallel);
+    let build = |(file_name, path, metadata)| build_entry(file_name, path, metadata, options, needs);
+    let mut files: Vec<Entry> = if parallel { fetched.into_par_iter().map(build).collect() } else { fetched.into_iter().map(build).collect() };
+    sort_entries(&mut files, sort);
+    Ok(files)
 }
 
-/// Adds file type indicator to filename based on file type
+/// Reads the entries of the specified directory for the short listing one at a time,
+/// in directory order, without holding the whole directory in memory
 ///
 /// # Arguments
 ///
-/// * `name` - The filename
-/// * `metadata` - File metadata to determine type
+/// * `path` - Path to the directory to list files from
+/// * `options` - Which entries to include and whether to classify them; the sort is ignored
 ///
 /// # Returns
 ///
-/// Filename with appropriate indicator appended
-fn add_file_type_indicator(name: &str, metadata: &fs::Metadata) -> String {
-    let indicator = if metadata.is_dir() {
-        "/"
-    } else if metadata.permissions().mode() & 0o111 != 0 {
-        "*" // executable
-    } else {
-        ""
-    };
-    format!("{}{}", name, indicator)
+/// An iterator over the entries with their metadata, or an error if the directory cannot be read
+pub fn stream_entries<'a>(path: &Path, options: &'a ListOptions) -> Result<impl Iterator<Item = Entry> + 'a, LsError> {
+    Ok(try_stream_entries(path, options)?.filter_map(|entry| options.errors.keep(entry)))
 }
 
-pub fn list_files_detailed(path: &str, show_hidden: bool, almost_all: bool, human_readable: bool, sort_time: bool, sort_size: bool, reverse: bool, unsorted: bool) -> Vec<FileInfo> {
-    let entries = fs::read_dir(path).expect("Unable to read directory");
-    let mut files: Vec<FileInfo> = entries
-        .filter_map(|entry| {
-            let entry = entry.ok()?;
-            let file_name = entry.file_name();
-            let file_name = file_name.to_string_lossy();
+/// Reads the entries of the specified directory one at a time, in directory order,
+/// like `stream_entries`, but hands back the entries that cannot be read as errors
+/// instead of recording them in the options' `errors`
+///
+/// # Arguments
+///
+/// * `path` - Path to the directory to list files from
+/// * `options` - Which entries to include and whether to classify them; the sort is ignored
+///
+/// # Returns
+///
+/// An iterator over each entry or the error it could not be read with, or an error if
+/// the directory cannot be read
+pub fn try_stream_entries<'a>(path: &Path, options: &'a ListOptions) -> Result<impl Iterator<Item = Result<Entry, LsError>> + 'a, LsError> {
+    read_entries(path, options, SortNeeds::default())
+}
 
-            // Handle hidden files and . .. filtering
-            if !show_hidden && file_name.starts_with('.') {
-                return None;
-            }
-            if almost_all && (file_name == "." || file_name == "..") {
-                return None;
-            }
+/// Reads the names of the specified directory's entries one at a time, in directory
+/// order, without fetching any metadata
+///
+/// # Arguments
+///
+/// * `path` - Path to the directory to list files from
+/// * `options` - Which entries to include; filter criteria on metadata and the sort are not used
+///
+/// # Returns
+///
+/// An iterator over the file names, each with the entry's full path, or an error if
+/// the directory cannot be read
+pub fn stream_names<'a>(path: &Path, options: &'a ListOptions) -> Result<impl Iterator<Item = (String, PathBuf)> + 'a, LsError> {
+    Ok(named_entries(path, options)?.filter_map(|entry| options.errors.keep(entry)))
+}
 
-            get_file_info(&entry, human_readable)
-        })
+/// Reads a directory lazily, yielding the name and full path of each entry whose name
+/// is listed, or the error an entry could not be read with
+///
+/// # Arguments
+///
+/// * `path` - Path to the directory to read
+/// * `options` - Which entries to include, the criteria their names must match and where they live
+fn named_entries<'a>(path: &Path, options: &'a ListOptions) -> Result<impl Iterator<Item = Result<(String, PathBuf), LsError>> + 'a, LsError> {
+    let entries = options.file_system.read_dir(path).map_err(|err| LsError::new(path, err))?;
+    let git_visible = options.filter.git_visible(path, &*options.file_system);
+    // read_dir never yields . and .., so -a adds them itself
+    let dots: Vec<Result<(String, PathBuf), LsError>> = [".", ".."]
+        .into_iter()
+        .filter(|name| options.all && !options.almost_all && options.filter.matches_name(name))
+        .map(|name| Ok((name.to_string(), path.join(name))))
         .collect();
+    let dir = path.to_path_buf();
+    Ok(dots.into_iter().chain(entries.filter_map(move |entry| {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(err) => return Some(Err(LsError::new(&dir, err))),
+        };
+        let file_name = ascii::os_text(&entry.name).into_owned();
+        if !keeps_name(&file_name, options) {
+            return None;
+        }
+        if git_visible.as_ref().is_some_and(|visible| !visible.contains(&entry.name)) {
+            return None;
+        }
+        Some(Ok((file_name, entry.path)))
+    })))
+}
 
-    // Apply sorting unless unsorted is specified
-    if !unsorted {
-        if sort_time {
-            files.sort_by(|a, b| {
-                if reverse {
-                    a.modified_time.cmp(&b.modified_time)
-                } else {
-                    b.modified_time.cmp(&a.modified_time)
-                }
-            });
-        } else if sort_size {
-            files.sort_by(|a, b| {
-                if reverse {
-                    a.file_size.cmp(&b.file_size)
-                } else {
-                    b.file_size.cmp(&a.file_size)
-                }
-            });
-        } else {
-            // Default alphabetical sort
-            files.sort_by(|a, b| {
-                if reverse {
-                    b.name.cmp(&a.name)
-                } else {
-                    a.name.cmp(&b.name)
-                }
-            });
+/// Reads a directory lazily, fetching the metadata of each listed entry, or yielding
+/// the error an entry could not be read with
+///
+/// # Arguments
+///
+/// * `path` - Path to the directory to read
+/// * `options` - Which entries to include, the criteria they must match and whether to classify them
+/// * `needs` - The values to resolve for sorting
+fn read_entries<'a>(path: &Path, options: &'a ListOptions, needs: SortNeeds) -> Result<impl Iterator<Item = Result<Entry, LsError>> + 'a, LsError> {
+    Ok(named_entries(path, options)?.filter_map(move |named| {
+        let (file_name, path) = match named {
+            Ok(named) => named,
+            Err(err) => return Some(Err(err)),
+        };
+        let metadata = match entry_metadata(&path, options) {
+            Ok(metadata) => metadata,
+            Err(err) => return Some(Err(LsError::entry(&path, err))),
+        };
+        if !keeps_metadata(&path, &metadata, options) {
+            return None;
         }
+        Some(Ok(build_entry(file_name, path, metadata, options, needs)))
+    }))
+}
+
+/// Builds a short listing entry from already fetched metadata
+///
+/// # Arguments
+///
+/// * `file_name` - The entry's file name
+/// * `path` - The entry's full path
+/// * `metadata` - The entry's metadata
+/// * `options` - Whether to classify it and where it lives
+/// * `needs` - The values to resolve for sorting
+fn build_entry(file_name: String, path: PathBuf, metadata: Metadata, options: &ListOptions, needs: SortNeeds) -> Entry {
+    let file_system = &*options.file_system;
+    let modified_time = metadata.modified.unwrap_or(SystemTime::now());
+
+    let display_name = if options.classify {
+        add_file_type_indicator(&file_name, &metadata)
+    } else {
+        file_name.clone()
+    };
+
+    let owner = if needs.owner { metadata.owner.clone().unwrap_or_else(|| user_name(metadata.uid)) } else { String::new() };
+    let group = if needs.group { metadata.group.clone().unwrap_or_else(|| group_name(metadata.gid)) } else { String::new() };
+    let captured = if needs.captured { media::capture_time(&path, file_system) } else { None };
+
+    Entry {
+        name: file_name,
+        display_name,
+        kind: file_kind(&path, &metadata, file_system),
+        target_kind: target_kind(&path, &metadata, file_system),
+        path,
+        metadata,
+        modified_time,
+        owner,
+        group,
+        captured,
     }
+}
+
+/// The values a sort compares that are costly to look up, so they are resolved once
+/// per entry before sorting and only when a sort key needs them
+#[derive(Clone, Copy, Default)]
+struct SortNeeds {
+    owner: bool,
+    group: bool,
+    captured: bool,
+}
 
-    files
+impl SortNeeds {
+    /// Works out which values the given sort compares
+    fn of(sort: &SortSpec) -> Self {
+        SortNeeds {
+            owner: sort.sorts_by(SortKey::Owner),
+            group: sort.sorts_by(SortKey::Group),
+            captured: sort.sorts_by(SortKey::Captured),
+        }
+    }
 }
 
 /// Lists files in the specified directory
 ///
+/// Entries are only stat'ed when classifying, sorting or filtering needs their metadata.
+///
 /// # Arguments
 ///
 /// * `path` - Path to the directory to list files from
-/// * `show_hidden` - Whether to include hidden files (those starting with .) in the listing
-/// * `almost_all` - Whether to exclude . and .. from listing
-/// * `classify` - Whether to add file type indicators
-/// * `sort_time` - Whether to sort by modification time
-/// * `sort_size` - Whether to sort by file size
-/// * `reverse` - Whether to reverse the sort order
-/// * `unsorted` - Whether to skip sorting entirely
+/// * `options` - Which entries to include, how to order them and whether to classify them
 ///
 /// # Returns
 ///
-/// A vector of filenames as strings
-pub fn list_files(path: &str, show_hidden: bool, almost_all: bool, classify: bool, sort_time: bool, sort_size: bool, reverse: bool, unsorted: bool) -> Vec<String> {
-    let entries = fs::read_dir(path).expect("Unable to read directory");
-    let mut files: Vec<(String, fs::Metadata, SystemTime)> = entries
-        .filter_map(|entry| {
-            let entry = entry.ok()?;
-            let file_name = entry.file_name();
-            let file_name = file_name.to_string_lossy();
-
-            // Handle hidden files and . .. filtering
-            if !show_hidden && file_name.starts_with('.') {
-                return None;
-            }
-            if almost_all && (file_name == "." || file_name == "..") {
-                return None;
-            }
+/// A vector of filenames as strings, or an error if the directory cannot be read
+pub fn list_files<P: AsRef<Path>>(path: P, options: &ListOptions) -> Result<Vec<String>, LsError> {
+    Ok(list_names(path, options)?.into_iter().map(|(name, _)| name).collect())
+}
 
-            let metadata = entry.metadata().ok()?;
-            let modified_time = metadata.modified().unwrap_or(SystemTime::now());
-            
-            let display_name = if classify {
-                add_file_type_indicator(&file_name, &metadata)
-            } else {
-                file_name.to_string()
-            };
+/// Lists files in the specified directory like `list_files`, keeping each entry's path
+/// so the name can be printed from the bytes the file system stores
+///
+/// # Arguments
+///
+/// * `path` - Path to the directory to list files from
+/// * `options` - Which entries to include, how to order them and whether to classify them
+///
+/// # Returns
+///
+/// The file names, each with the entry's full path, or an error if the directory cannot be read
+pub fn list_names<P: AsRef<Path>>(path: P, options: &ListOptions) -> Result<Vec<(String, PathBuf)>, LsError> {
+    if options.classify || options.sort.needs_metadata() || options.filter.needs_metadata() {
+        let entries = list_entries(path, options)?;
+        return Ok(entries.into_iter().map(|entry| (entry.display_name, entry.path)).collect());
+    }
 
-            Some((display_name, metadata, modified_time))
-        })
-        .collect();
+    let mut names: Vec<(String, PathBuf)> = stream_names(path.as_ref(), options)?.collect();
+    sort_entries(&mut names, &options.sort);
+    Ok(names)
+}
 
-    // Apply sorting unless unsorted is specified
-    if !unsorted {
-        if sort_time {
-            files.sort_by(|a, b| {
-                if reverse {
-                    a.2.cmp(&b.2)
-                } else {
-                    b.2.cmp(&a.2)
-                }
-            });
-        } else if sort_size {
-            files.sort_by(|a, b| {
-                if reverse {
-                    a.1.len().cmp(&b.1.len())
-                } else {
-                    b.1.len().cmp(&a.1.len())
-                }
-            });
-        } else {
-            // Default alphabetical sort
-            files.sort_by(|a, b| {
-                if reverse {
-                    b.0.cmp(&a.0)
-                } else {
-                    a.0.cmp(&b.0)
-                }
-            });
+/// How symlinks named on the command line are treated
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OperandLinks {
+    /// Describe every link itself, as the long format and `-F` do
+    Never,
+    /// Follow links to directories so their entries are listed, describing other links themselves
+    Directories,
+    /// Follow every link, as `-H` and `-L` do
+    Always,
+}
+
+/// What a path given on the command line turned out to be
+pub enum Operand {
+    /// A directory, whose entries are listed
+    Directory,
+    /// Anything else, listed as an entry of its own under the path as given
+    File(Box<Entry>),
+}
+
+/// Looks up a path given on the command line to decide how it is listed
+///
+/// A symlink whose target does not exist is described by the link itself.
+///
+/// # Arguments
+///
+/// * `path` - The command line path
+/// * `options` - Whether to classify it and where it lives
+/// * `links` - Which symlinks are followed
+///
+/// # Returns
+///
+/// Whether the path is listed as a directory or as an entry, or an error if it cannot be accessed
+pub fn operand(path: &Path, options: &ListOptions, links: OperandLinks) -> Result<Operand, LsError> {
+    let file_system = &*options.file_system;
+    let mut metadata = file_system.symlink_metadata(path).map_err(|err| LsError::entry(path, err))?;
+    if metadata.is_symlink() {
+        if let Ok(target) = file_system.metadata(path) {
+            if links == OperandLinks::Always || (links == OperandLinks::Directories && target.is_dir()) {
+                metadata = target;
+            }
         }
     }
-
-    files.into_iter().map(|(name, _, _)| name).collect()
+    if metadata.is_dir() {
+        return Ok(Operand::Directory);
+    }
+    let name = ascii::os_text(path.as_os_str()).into_owned();
+    Ok(Operand::File(Box::new(build_entry(name, path.to_path_buf(), metadata, options, SortNeeds::of(&options.sort)))))
 }
 
-#[cfg(test)]
+#[cfg(all(test, unix))]
 mod tests {
 
     use super::*;
-    use fs::File;
+    use std::fs::{self, File};
+    use std::{os::unix::fs::MetadataExt, time::Duration};
     use tempfile::tempdir;
 
     #[test]
@@ -310,7 +1544,7 @@ mod tests {
         fs::create_dir(dir_path.join("subdir")).expect("Unable to create subdir");
 
         // Test 1: show_hidden = false, classify = false
-        let files = list_files(dir_path.to_str().unwrap(), false, false, false, false, false, false, false);
+        let files = list_files(dir_path.to_str().unwrap(), &ListOptions::default()).unwrap();
         let mut expected_files = vec![
             "file1.txt".to_string(),
             "file2.txt".to_string(),
@@ -321,9 +1555,11 @@ mod tests {
         files_sorted.sort();
         assert_eq!(files_sorted, expected_files);
 
-        // Test 2: show_hidden = true, classify = false
-        let files = list_files(dir_path.to_str().unwrap(), true, false, false, false, false, false, false);
+        // Test 2: show_hidden = true, classify = false (. and .. are listed too)
+        let files = list_files(dir_path.to_str().unwrap(), &ListOptions::default().all(true)).unwrap();
         let mut expected_files = vec![
+            ".".to_string(),
+            "..".to_string(),
             "file1.txt".to_string(),
             "file2.txt".to_string(),
             ".hidden_file".to_string(),
@@ -335,7 +1571,7 @@ mod tests {
         assert_eq!(files_sorted, expected_files);
 
         // Test 3: classify = true (should add / to directories)
-        let files = list_files(dir_path.to_str().unwrap(), false, false, true, false, false, false, false);
+        let files = list_files(dir_path.to_str().unwrap(), &ListOptions::default().classify(true)).unwrap();
         let mut expected_files = vec![
             "file1.txt".to_string(),
             "file2.txt".to_string(),
@@ -348,20 +1584,123 @@ mod tests {
     }
 
     #[test]
-    fn test_file_info_permissions() {
-        // Test the permission format fix (should use '-' not '.')
+    fn test_broken_symlink() {
+        let temp_dir = tempdir().expect("Unable to create temporary directory");
+        let dir_path = temp_dir.path();
+        File::create(dir_path.join("real.txt")).expect("Unable to create real.txt");
+        std::os::unix::fs::symlink("real.txt", dir_path.join("good")).expect("Unable to create good link");
+        std::os::unix::fs::symlink("missing.txt", dir_path.join("bad")).expect("Unable to create bad link");
+
+        let mut files = list_files_detailed(dir_path.to_str().unwrap(), &ListOptions::default()).unwrap();
+        assert_eq!(files[0].kind, FileKind::BrokenSymlink);
+        assert_eq!(files[0].target_kind, None);
+        assert_eq!(files[1].kind, FileKind::Symlink);
+        assert_eq!(files[1].target_kind, Some(FileKind::File));
+        assert_eq!(files[2].link_target, None);
+
+        files.iter_mut().for_each(|file| file.append_link_target(false, |target| ascii::os_text(target).into_owned()));
+        assert_eq!(files[0].name, "bad -> missing.txt (broken)");
+        assert_eq!(files[1].name, "good -> real.txt");
+        assert_eq!(files[2].name, "real.txt");
+    }
+
+    #[test]
+    fn test_resolve_chain() {
+        let temp_dir = tempdir().expect("Unable to create temporary directory");
+        let dir_path = temp_dir.path().canonicalize().unwrap();
+        File::create(dir_path.join("real.txt")).expect("Unable to create real.txt");
+        std::os::unix::fs::symlink("real.txt", dir_path.join("one")).expect("Unable to create one");
+        std::os::unix::fs::symlink("one", dir_path.join("two")).expect("Unable to create two");
+        std::os::unix::fs::symlink("gone", dir_path.join("dangling")).expect("Unable to create dangling");
+        std::os::unix::fs::symlink("dangling", dir_path.join("broken_chain")).expect("Unable to create broken_chain");
+
+        let resolved = dir_path.join("real.txt");
+        assert_eq!(resolve_chain(&dir_path.join("two"), &StdFileSystem), Some(resolved.clone()));
+        assert_eq!(resolve_chain(&dir_path.join("one"), &StdFileSystem), None);
+        assert_eq!(resolve_chain(&dir_path.join("real.txt"), &StdFileSystem), None);
+        assert_eq!(resolve_chain(&dir_path.join("broken_chain"), &StdFileSystem), None);
+
+        let mut files = list_files_detailed(dir_path.to_str().unwrap(), &ListOptions::default().target_info(true)).unwrap();
+        let two = files.iter_mut().find(|file| file.name == "two").unwrap();
+        assert_eq!(two.resolved_target, Some(resolved.clone()));
+        two.append_link_target(true, |target| ascii::os_text(target).into_owned());
+        assert_eq!(two.name, format!("two -> one => {}", resolved.display()));
+    }
+
+    #[test]
+    fn test_dereference() {
+        let temp_dir = tempdir().expect("Unable to create temporary directory");
+        let dir_path = temp_dir.path();
+        fs::write(dir_path.join("real.txt"), "hello").expect("Unable to create real.txt");
+        std::os::unix::fs::symlink("real.txt", dir_path.join("link")).expect("Unable to create link");
+
+        let link = |options: &ListOptions| list_files_detailed(dir_path, options).unwrap().remove(0);
+        let plain = link(&ListOptions::default());
+        assert_eq!((plain.kind, plain.file_size), (FileKind::Symlink, 8));
+        let dereferenced = link(&ListOptions::default().dereference(true));
+        assert_eq!((dereferenced.kind, dereferenced.file_size), (FileKind::File, 5));
+        let target_info = link(&ListOptions::default().target_info(true));
+        assert_eq!((target_info.kind, target_info.file_size), (FileKind::Symlink, 5));
+    }
+
+    #[test]
+    fn test_operand() {
+        let temp_dir = tempdir().expect("Unable to create temporary directory");
+        let dir_path = temp_dir.path();
+        fs::create_dir(dir_path.join("docs")).expect("Unable to create docs");
+        fs::write(dir_path.join("notes.txt"), "hello").expect("Unable to create notes.txt");
+        std::os::unix::fs::symlink("docs", dir_path.join("docs_link")).expect("Unable to create docs_link");
+        std::os::unix::fs::symlink("notes.txt", dir_path.join("notes_link")).expect("Unable to create notes_link");
+
+        let options = ListOptions::default();
+        let kind = |name: &str, links: OperandLinks| match operand(&dir_path.join(name), &options, links).unwrap() {
+            Operand::Directory => None,
+            Operand::File(entry) => Some(entry.kind),
+        };
+        assert_eq!(kind("docs", OperandLinks::Never), None);
+        // The long format shows a symlinked directory as the link, unless -H follows it
+        assert_eq!(kind("docs_link", OperandLinks::Never), Some(FileKind::Symlink));
+        assert_eq!(kind("docs_link", OperandLinks::Directories), None);
+        assert_eq!(kind("docs_link", OperandLinks::Always), None);
+        assert_eq!(kind("notes_link", OperandLinks::Directories), Some(FileKind::Symlink));
+        assert_eq!(kind("notes_link", OperandLinks::Always), Some(FileKind::File));
+
+        let Operand::File(entry) = operand(&dir_path.join("notes.txt"), &options.clone().classify(true), OperandLinks::Never).unwrap() else {
+            panic!("notes.txt should be listed as a file");
+        };
+        assert_eq!(entry.name, dir_path.join("notes.txt").to_string_lossy());
+        assert_eq!(entry.metadata.len, 5);
+        assert!(operand(&dir_path.join("missing"), &options, OperandLinks::Never).is_err());
+    }
+
+    #[test]
+    fn test_shared_strings_are_interned() {
+        let temp_dir = tempdir().expect("Unable to create temporary directory");
+        File::create(temp_dir.path().join("a.txt")).expect("Unable to create a.txt");
+        File::create(temp_dir.path().join("b.txt")).expect("Unable to create b.txt");
+
+        let files = list_files_detailed(temp_dir.path().to_str().unwrap(), &ListOptions::default()).unwrap();
+        assert!(Arc::ptr_eq(&files[0].owner, &files[1].owner));
+        assert!(Arc::ptr_eq(&files[0].group, &files[1].group));
+    }
+
+    #[test]
+    fn test_column_widths() {
         let temp_dir = tempdir().expect("Unable to create temporary directory");
-        let file_path = temp_dir.path().join("test_file.txt");
-        File::create(&file_path).expect("Unable to create test file");
-        
-        let entry = fs::read_dir(temp_dir.path())
-            .expect("Unable to read directory")
-            .find(|e| e.as_ref().unwrap().file_name() == "test_file.txt")
-            .expect("File not found")
-            .expect("Unable to get directory entry");
-            
-        let file_info = get_file_info(&entry, false).expect("Unable to get file info");
-        assert!(file_info.permissions.starts_with('-'), "Regular file should start with '-' not '.'");
+        File::create(temp_dir.path().join("a.txt")).expect("Unable to create a.txt");
+
+        let mut files = list_files_detailed(temp_dir.path().to_str().unwrap(), &ListOptions::default()).unwrap();
+        let mut widths = ColumnWidths::default();
+        widths.update(&files);
+        assert_eq!(widths.owner, files[0].owner.chars().count());
+
+        // Widths only ever grow, and apply pads shorter names to them
+        widths.owner += 4;
+        widths.group += 4;
+        widths.update(&files);
+        widths.apply(&mut files);
+        assert_eq!(files[0].owner.chars().count(), widths.owner);
+        assert_eq!(files[0].group.chars().count(), widths.group);
     }
 
     #[test]
@@ -375,18 +1714,305 @@ mod tests {
         File::create(dir_path.join("b.txt")).expect("Unable to create b.txt");
 
         // Test default alphabetical sorting
-        let files = list_files(dir_path.to_str().unwrap(), false, false, false, false, false, false, false);
+        let files = list_files(dir_path.to_str().unwrap(), &ListOptions::default()).unwrap();
         assert_eq!(files, vec!["a.txt", "b.txt", "c.txt"]);
 
         // Test reverse sorting
-        let files = list_files(dir_path.to_str().unwrap(), false, false, false, false, false, true, false);
+        let files = list_files(dir_path.to_str().unwrap(), &ListOptions::default().sort(SortSpec::default().reverse(true))).unwrap();
         assert_eq!(files, vec!["c.txt", "b.txt", "a.txt"]);
 
         // Test unsorted (should maintain original order from filesystem)
-        let files = list_files(dir_path.to_str().unwrap(), false, false, false, false, false, false, true);
+        let files = list_files(dir_path.to_str().unwrap(), &ListOptions::default().sort(SortSpec::default().unsorted(true))).unwrap();
         // Just ensure we get all files (order might vary)
         let mut sorted_files = files.clone();
         sorted_files.sort();
         assert_eq!(sorted_files, vec!["a.txt", "b.txt", "c.txt"]);
     }
+
+    #[test]
+    fn test_sort_keys() {
+        let temp_dir = tempdir().expect("Unable to create temporary directory");
+        let dir_path = temp_dir.path();
+
+        fs::write(dir_path.join("b.txt"), "12").expect("Unable to create b.txt");
+        fs::write(dir_path.join("a.txt"), "12").expect("Unable to create a.txt");
+        fs::write(dir_path.join("c.rs"), "1").expect("Unable to create c.rs");
+        fs::write(dir_path.join("d.rs"), "123").expect("Unable to create d.rs");
+
+        // Size ties are broken by name
+        let files = list_files(dir_path.to_str().unwrap(), &ListOptions::default().sort(SortSpec::by(vec![SortKey::Size]))).unwrap();
+        assert_eq!(files, vec!["d.rs", "a.txt", "b.txt", "c.rs"]);
+
+        // Extension first, then size within each extension
+        let files = list_files(dir_path.to_str().unwrap(), &ListOptions::default().sort(SortSpec::by(vec![SortKey::Extension, SortKey::Size]))).unwrap();
+        assert_eq!(files, vec!["d.rs", "c.rs", "a.txt", "b.txt"]);
+
+        // Reverse flips the whole ordering, tie-breaks included
+        let files = list_files(dir_path.to_str().unwrap(), &ListOptions::default().sort(SortSpec::by(vec![SortKey::Size]).reverse(true))).unwrap();
+        assert_eq!(files, vec!["c.rs", "b.txt", "a.txt", "d.rs"]);
+    }
+
+    #[test]
+    fn test_sort_by_links_and_inode() {
+        let temp_dir = tempdir().expect("Unable to create temporary directory");
+        let dir_path = temp_dir.path();
+
+        File::create(dir_path.join("a.txt")).expect("Unable to create a.txt");
+        File::create(dir_path.join("b.txt")).expect("Unable to create b.txt");
+        fs::hard_link(dir_path.join("b.txt"), dir_path.join("c.txt")).expect("Unable to link c.txt");
+
+        // Hard-linked entries have the most links and sort first, tied by name
+        let files = list_files(dir_path.to_str().unwrap(), &ListOptions::default().sort(SortSpec::by(vec![SortKey::Links]))).unwrap();
+        assert_eq!(files, vec!["b.txt", "c.txt", "a.txt"]);
+
+        let detailed = list_files_detailed(dir_path.to_str().unwrap(), &ListOptions::default().sort(SortSpec::by(vec![SortKey::Inode]))).unwrap();
+        assert!(detailed.windows(2).all(|pair| pair[0].inode <= pair[1].inode));
+    }
+
+    #[test]
+    fn test_modified_after_filter() {
+        let temp_dir = tempdir().expect("Unable to create temporary directory");
+        let dir_path = temp_dir.path();
+
+        File::create(dir_path.join("new.txt")).expect("Unable to create new.txt");
+        let old = File::create(dir_path.join("old.txt")).expect("Unable to create old.txt");
+        old.set_modified(UNIX_EPOCH).expect("Unable to set mtime");
+
+        let filter = Filter {
+            modified_after: Some(crate::filter::TimePreset::ThisMonth.start()),
+            ..Default::default()
+        };
+        let files = list_files(dir_path.to_str().unwrap(), &ListOptions::default().filter(filter)).unwrap();
+        assert_eq!(files, vec!["new.txt"]);
+    }
+
+    #[test]
+    fn test_dotfile_order() {
+        let temp_dir = tempdir().expect("Unable to create temporary directory");
+        let dir_path = temp_dir.path();
+
+        File::create(dir_path.join("b.txt")).expect("Unable to create b.txt");
+        File::create(dir_path.join(".c")).expect("Unable to create .c");
+        File::create(dir_path.join("a.txt")).expect("Unable to create a.txt");
+
+        let list = |dotfiles, reverse| {
+            list_files(dir_path.to_str().unwrap(), &ListOptions::default().all(true).almost_all(true).sort(SortSpec::default().reverse(reverse).dotfiles(dotfiles))).unwrap()
+        };
+        assert_eq!(list(DotfileOrder::Mixed, false), vec![".c", "a.txt", "b.txt"]);
+        assert_eq!(list(DotfileOrder::Last, false), vec!["a.txt", "b.txt", ".c"]);
+        // Grouping holds even when the order within groups is reversed
+        assert_eq!(list(DotfileOrder::First, true), vec![".c", "b.txt", "a.txt"]);
+    }
+
+    #[test]
+    fn test_version_cmp() {
+        let mut names = vec!["file10", "file2", "file1", "v1.10.0", "v1.9.3", "file02a"];
+        names.sort_by(|a, b| version_cmp(a, b));
+        assert_eq!(names, vec!["file1", "file2", "file02a", "file10", "v1.9.3", "v1.10.0"]);
+    }
+
+    #[test]
+    fn test_limit_entries() {
+        let mut entries: Vec<u32> = (0..10).collect();
+        assert_eq!(limit_entries(&mut entries, 3), 7);
+        assert_eq!(entries, [0, 1, 2]);
+        assert_eq!(limit_entries(&mut entries, 5), 0);
+        assert_eq!(entries, [0, 1, 2]);
+    }
+
+    #[test]
+    fn test_entry_paths_keep_names_intact() {
+        let temp_dir = tempdir().unwrap();
+        File::create(temp_dir.path().join("star*")).unwrap();
+        fs::create_dir(temp_dir.path().join("dir")).unwrap();
+
+        let entries = list_entries(temp_dir.path(), &ListOptions::default().classify(true)).unwrap();
+        assert_eq!(entries[0].path, temp_dir.path().join("dir"));
+        assert_eq!(entries[0].indicator(), "/");
+        assert_eq!(entries[0].display_path(), format!("{}/", temp_dir.path().join("dir").display()));
+        assert_eq!(entries[1].path, temp_dir.path().join("star*"));
+        assert_eq!(entries[1].indicator(), "");
+
+        let files = detailed_entries(&entries, &ListOptions::default());
+        assert_eq!(files.iter().map(|file| file.name.as_str()).collect::<Vec<_>>(), ["dir", "star*"]);
+        assert!(files[0].is_dir);
+        assert_eq!(files[1].path, entries[1].path);
+    }
+
+    #[test]
+    fn test_parallel_metadata_matches_sequential() {
+        let temp_dir = tempdir().unwrap();
+        let candidates: Vec<(String, PathBuf)> = (0..50)
+            .map(|i| {
+                let name = format!("file{}", i);
+                File::create(temp_dir.path().join(&name)).unwrap();
+                (name.clone(), temp_dir.path().join(name))
+            })
+            .chain([("missing".to_string(), temp_dir.path().join("missing"))])
+            .collect();
+
+        let names = |parallel| -> Vec<String> { fetch_metadata(candidates.clone(), &ListOptions::default(), parallel).into_iter().map(|(name, _, _)| name).collect() };
+        assert_eq!(names(true).len(), 50);
+        assert_eq!(names(true), names(false));
+    }
+
+    #[test]
+    fn test_sort_needs_metadata() {
+        assert!(!SortSpec::default().needs_metadata());
+        assert!(!SortSpec { keys: vec![SortKey::Extension, SortKey::Version], ..Default::default() }.needs_metadata());
+        assert!(SortSpec { keys: vec![SortKey::Extension, SortKey::Size], ..Default::default() }.needs_metadata());
+        assert!(SortSpec { keys: vec![SortKey::Created], ..Default::default() }.needs_metadata());
+        assert!(!SortSpec { keys: vec![SortKey::Time], unsorted: true, ..Default::default() }.needs_metadata());
+    }
+
+    #[test]
+    fn test_streamed_entries_match_listing() {
+        let temp_dir = tempdir().unwrap();
+        for name in ["b.txt", "a.txt", ".hidden"] {
+            File::create(temp_dir.path().join(name)).unwrap();
+        }
+
+        let mut names: Vec<String> = stream_names(temp_dir.path(), &ListOptions::default()).unwrap().map(|(name, _)| name).collect();
+        names.sort();
+        assert_eq!(names, ["a.txt", "b.txt"]);
+
+        let mut streamed: Vec<String> = stream_entries(temp_dir.path(), &ListOptions::default().all(true).almost_all(true)).unwrap().map(|entry| entry.name).collect();
+        streamed.sort();
+        assert_eq!(streamed, list_files(temp_dir.path().to_str().unwrap(), &ListOptions::default().all(true).almost_all(true)).unwrap());
+    }
+
+    #[test]
+    fn test_unreadable_directory_is_an_error() {
+        let temp_dir = tempdir().unwrap();
+        let missing = temp_dir.path().join("missing");
+
+        let err = list_entries(&missing, &ListOptions::default()).err().expect("Listing should fail");
+        assert_eq!(err.path, missing);
+        assert!(err.to_string().starts_with(&format!("cannot access '{}': ", missing.display())));
+
+        let err = LsError::new(temp_dir.path(), io::Error::from(io::ErrorKind::PermissionDenied));
+        assert!(err.to_string().starts_with("cannot open directory"));
+        let err = LsError::entry(temp_dir.path(), io::Error::from(io::ErrorKind::PermissionDenied));
+        assert!(err.to_string().starts_with("cannot access"));
+    }
+
+    #[test]
+    fn test_format_age() {
+        let now = UNIX_EPOCH + Duration::from_secs(10 * 365 * 86_400);
+        let ago = |seconds: u64| format_age(now - Duration::from_secs(seconds), now);
+        assert_eq!(ago(0), "  0s");
+        assert_eq!(ago(59), " 59s");
+        assert_eq!(ago(3 * 60 + 5), "  3m");
+        assert_eq!(ago(2 * 3_600), "  2h");
+        assert_eq!(ago(364 * 86_400), "364d");
+        assert_eq!(ago(3 * 365 * 86_400), "  3y");
+        assert_eq!(format_age(now + Duration::from_secs(7_200), now), "in 2h");
+    }
+
+    #[test]
+    fn test_unknown_time_zone() {
+        assert_eq!(set_time_zone("Nowhere/Atlantis"), Err("unknown time zone 'Nowhere/Atlantis'".to_string()));
+        assert!(set_time_zone("../zoneinfo/UTC").is_err());
+        assert!(set_time_zone("").is_err());
+        assert_eq!(set_time_zone("local"), Ok(()));
+    }
+
+    #[test]
+    fn test_sparse_files() {
+        let temp_dir = tempdir().unwrap();
+        File::create(temp_dir.path().join("image")).unwrap().set_len(1 << 20).unwrap();
+        fs::write(temp_dir.path().join("written"), vec![1; 1 << 16]).unwrap();
+
+        let files = list_files_detailed(temp_dir.path(), &ListOptions::default()).unwrap();
+        assert!(files[0].is_sparse());
+        assert!(!files[1].is_sparse());
+        assert!(files[1].allocated_size >= 1 << 16);
+    }
+
+    #[test]
+    fn test_dir_size() {
+        let temp_dir = tempdir().unwrap();
+        let dir_path = temp_dir.path().join("dir");
+        fs::create_dir(&dir_path).unwrap();
+        File::create(dir_path.join("a")).unwrap();
+        File::create(dir_path.join(".b")).unwrap();
+        File::create(temp_dir.path().join("file")).unwrap();
+
+        let listing = || list_files_detailed(temp_dir.path(), &ListOptions::default()).unwrap();
+        let contents = |files: &[FileInfo]| files.iter().map(|file| file.dir_contents).collect::<Vec<_>>();
+        let mut files = listing();
+        DirSize::None.apply(&mut files, &StdFileSystem);
+        assert_eq!(contents(&files), [None, None]);
+        DirSize::Entries.apply(&mut files, &StdFileSystem);
+        assert_eq!(contents(&files), [Some(DirContents::Entries(Some(2))), None]);
+        let mut files = listing();
+        DirSize::Bytes.apply(&mut files, &StdFileSystem);
+        assert_eq!(contents(&files), [Some(DirContents::Bytes(fs::metadata(&dir_path).unwrap().len())), None]);
+    }
+
+    #[test]
+    fn test_dot_entries() {
+        let temp_dir = tempdir().unwrap();
+        let dir_path = temp_dir.path().join("dir");
+        fs::create_dir(&dir_path).unwrap();
+        File::create(dir_path.join(".hidden")).unwrap();
+
+        let all = list_entries(&dir_path, &ListOptions::default().all(true)).unwrap();
+        assert_eq!(all.iter().map(|entry| entry.name.as_str()).collect::<Vec<_>>(), [".", "..", ".hidden"]);
+        assert!(all[0].is_dot() && all[1].is_dot() && !all[2].is_dot());
+        assert_eq!(all[0].metadata.ino, fs::metadata(&dir_path).unwrap().ino());
+        assert_eq!(all[1].metadata.ino, fs::metadata(temp_dir.path()).unwrap().ino());
+
+        let almost_all = list_files(&dir_path, &ListOptions::default().almost_all(true)).unwrap();
+        assert_eq!(almost_all, [".hidden"]);
+        let both = list_files(&dir_path, &ListOptions::default().all(true).almost_all(true)).unwrap();
+        assert_eq!(both, [".hidden"]);
+    }
+
+    #[test]
+    fn test_sample_entries() {
+        let mut entries: Vec<u32> = (0..100).collect();
+        let total = sample_entries(&mut entries, 10, Some(7));
+        assert_eq!(total, 100);
+        assert_eq!(entries.len(), 10);
+        assert!(entries.windows(2).all(|pair| pair[0] < pair[1]), "sample should keep the original order");
+
+        let mut again: Vec<u32> = (0..100).collect();
+        sample_entries(&mut again, 10, Some(7));
+        assert_eq!(entries, again);
+
+        let mut few = vec![1, 2, 3];
+        assert_eq!(sample_entries(&mut few, 10, None), 3);
+        assert_eq!(few, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_random_sort_is_seedable() {
+        let temp_dir = tempdir().expect("Unable to create temporary directory");
+        let dir_path = temp_dir.path();
+        for i in 0..20 {
+            File::create(dir_path.join(format!("{:02}.txt", i))).expect("Unable to create file");
+        }
+
+        let options = ListOptions::default().sort(SortSpec::by(vec![SortKey::Random]).seed(Some(42)));
+        let first = list_files(dir_path.to_str().unwrap(), &options).unwrap();
+        let second = list_files(dir_path.to_str().unwrap(), &options).unwrap();
+        assert_eq!(first, second);
+
+        let mut sorted = first.clone();
+        sorted.sort();
+        assert_eq!(sorted.len(), 20);
+        assert_ne!(first, sorted);
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("512"), Some(512));
+        assert_eq!(parse_size("10k"), Some(10 * 1024));
+        assert_eq!(parse_size("1.5M"), Some(3 * 512 * 1024));
+        assert_eq!(parse_size("2GiB"), Some(2 << 30));
+        assert_eq!(parse_size("1T"), Some(1 << 40));
+        assert_eq!(parse_size("lots"), None);
+        assert_eq!(parse_size(""), None);
+    }
 }
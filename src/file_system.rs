@@ -0,0 +1,455 @@
+use std::{
+    collections::VecDeque,
+    ffi::OsString,
+    fmt, fs,
+    io::{self, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use crate::{platform, readonly};
+
+/// The Windows attribute of entries that cannot be written to
+pub const FILE_ATTRIBUTE_READONLY: u32 = 0x1;
+/// The Windows attribute of entries Explorer hides
+pub const FILE_ATTRIBUTE_HIDDEN: u32 = 0x2;
+/// The Windows attribute of entries that belong to the operating system
+pub const FILE_ATTRIBUTE_SYSTEM: u32 = 0x4;
+
+/// Shows Windows attributes as the attributes column does: `r`, `h` and `s` for
+/// read-only, hidden and system, each in its own place, with `-` for those not set
+///
+/// # Arguments
+///
+/// * `attributes` - The attribute bits, as in `Metadata::attributes`
+///
+/// # Returns
+///
+/// The three letters, like `-hs`
+pub fn attribute_letters(attributes: u32) -> String {
+    [(FILE_ATTRIBUTE_READONLY, 'r'), (FILE_ATTRIBUTE_HIDDEN, 'h'), (FILE_ATTRIBUTE_SYSTEM, 's')]
+        .into_iter()
+        .map(|(attribute, letter)| if attributes & attribute != 0 { letter } else { '-' })
+        .collect()
+}
+
+/// Where listings read directories and metadata from
+///
+/// [`StdFileSystem`] reads the local file system. Other implementations let archives,
+/// remote hosts or in-memory fixtures go through the same listing, sorting, filtering
+/// and long format rendering, and columns that read file contents, such as hashes
+/// and media details, read them through [`FileSystem::open`]. Git status still
+/// looks at the local file system, and `--git-ignore` only applies to it.
+pub trait FileSystem: fmt::Debug + Send + Sync {
+    /// Reads a directory's entries, without `.` and `..`
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The directory to read
+    ///
+    /// # Returns
+    ///
+    /// An iterator over the entries, or an error if the directory cannot be opened
+    fn read_dir(&self, path: &Path) -> io::Result<Box<dyn Iterator<Item = io::Result<DirEntry>> + '_>>;
+
+    /// Describes an entry, following it if it is a symlink
+    fn metadata(&self, path: &Path) -> io::Result<Metadata>;
+
+    /// Describes an entry without following symlinks
+    fn symlink_metadata(&self, path: &Path) -> io::Result<Metadata>;
+
+    /// Reads where a symlink points to
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf>;
+
+    /// Resolves a path to its absolute form, following every symlink in it
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf>;
+
+    /// Opens a regular file to read its contents, following symlinks
+    ///
+    /// File systems that cannot read contents leave this out, and the columns that
+    /// need them show the entry as unreadable.
+    ///
+    /// # Returns
+    ///
+    /// A reader positioned at the start of the file
+    fn open(&self, _path: &Path) -> io::Result<Box<dyn FileReader>> {
+        Err(io::Error::from(io::ErrorKind::Unsupported))
+    }
+
+    /// Whether paths name entries on the local disk, where tools such as git can
+    /// look at them directly
+    fn is_local(&self) -> bool {
+        false
+    }
+}
+
+/// A directory entry as a [`FileSystem`] reports it
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DirEntry {
+    pub name: OsString,
+    /// The directory's path joined with the name
+    pub path: PathBuf,
+}
+
+/// What kind of file system object an entry is
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FileType {
+    #[default]
+    File,
+    Directory,
+    Symlink,
+    Fifo,
+    Socket,
+    BlockDevice,
+    CharDevice,
+}
+
+/// An entry's metadata, as `stat` or `lstat` describe it
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Metadata {
+    pub file_type: FileType,
+    /// The full `st_mode`, file type bits included
+    pub mode: u32,
+    pub len: u64,
+    /// The number of 512-byte blocks allocated on disk
+    pub blocks: u64,
+    pub nlink: u64,
+    pub ino: u64,
+    pub dev: u64,
+    pub uid: u32,
+    pub gid: u32,
+    /// The owner's name as the entry's source records it, such as a tar header's;
+    /// None to look it up from `uid`
+    pub owner: Option<String>,
+    /// The group's name as the entry's source records it; None to look it up from `gid`
+    pub group: Option<String>,
+    /// Windows file attributes such as hidden, read-only and system; always 0 elsewhere
+    pub attributes: u32,
+    pub modified: Option<SystemTime>,
+    pub accessed: Option<SystemTime>,
+    /// When the entry's status last changed
+    pub changed: Option<SystemTime>,
+    /// When the entry was created, None if the file system does not record it
+    pub created: Option<SystemTime>,
+}
+
+impl Metadata {
+    pub fn is_dir(&self) -> bool {
+        self.file_type == FileType::Directory
+    }
+
+    pub fn is_file(&self) -> bool {
+        self.file_type == FileType::File
+    }
+
+    pub fn is_symlink(&self) -> bool {
+        self.file_type == FileType::Symlink
+    }
+
+    /// Whether any of the execute bits is set
+    pub fn is_executable(&self) -> bool {
+        self.mode & 0o111 != 0
+    }
+
+    /// Whether Windows marks the entry hidden, which hides it like a leading dot does
+    pub fn is_hidden(&self) -> bool {
+        self.attributes & FILE_ATTRIBUTE_HIDDEN != 0
+    }
+}
+
+impl From<fs::Metadata> for Metadata {
+    fn from(metadata: fs::Metadata) -> Self {
+        platform::from_std(&metadata)
+    }
+}
+
+/// The contents of a file opened through [`FileSystem::open`]
+pub trait FileReader: Read + Seek + Send {}
+
+impl<T: Read + Seek + Send> FileReader for T {}
+
+/// How much of a stream [`Rewindable`] keeps, enough for the image and media headers
+/// that are read out of order and for what buffered readers seek back over
+const REWIND_LEN: usize = 64 * 1024;
+
+/// Makes a stream that can only be read from the start, such as a compressed
+/// archive entry or a download, seekable
+///
+/// Seeking forward skips what lies in between. The last 64KB read are kept, so
+/// seeking back into them works, and seeking back any further fails.
+pub struct Rewindable<R> {
+    inner: R,
+    /// The last bytes read from the stream
+    recent: VecDeque<u8>,
+    /// How far the stream has been read
+    read: u64,
+    /// Where the next read starts, behind `read` after seeking back
+    position: u64,
+}
+
+impl<R: Read> Rewindable<R> {
+    pub fn new(inner: R) -> Self {
+        Rewindable { inner, recent: VecDeque::new(), read: 0, position: 0 }
+    }
+
+    /// Reads on from the end of what was read, keeping the most recent bytes
+    fn fill(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.inner.read(buf)?;
+        self.recent.extend(&buf[..len]);
+        self.recent.drain(..self.recent.len().saturating_sub(REWIND_LEN));
+        self.read += len as u64;
+        Ok(len)
+    }
+}
+
+impl<R: Read> Read for Rewindable<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.position < self.read {
+            // Seeking made sure the position is among the bytes kept
+            let start = self.recent.len() - (self.read - self.position) as usize;
+            let kept = self.recent.range(start..);
+            let len = kept.len().min(buf.len());
+            buf.iter_mut().zip(kept).for_each(|(to, &from)| *to = from);
+            self.position += len as u64;
+            return Ok(len);
+        }
+        // Skip to a position sought past what was read
+        let mut skipped = [0; 8192];
+        while self.read < self.position {
+            let len = (self.position - self.read).min(skipped.len() as u64) as usize;
+            if self.fill(&mut skipped[..len])? == 0 {
+                return Ok(0);
+            }
+        }
+        let len = self.fill(buf)?;
+        self.position = self.read;
+        Ok(len)
+    }
+}
+
+impl<R: Read> Seek for Rewindable<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+            SeekFrom::End(_) => None,
+        };
+        match position {
+            Some(position) if position >= self.read - self.recent.len() as u64 => {
+                self.position = position;
+                Ok(position)
+            }
+            _ => Err(io::Error::new(io::ErrorKind::Unsupported, "cannot seek that far back in a stream")),
+        }
+    }
+}
+
+/// The local file system, read through `std::fs`, honoring `--assert-readonly`
+#[derive(Clone, Copy, Debug, Default)]
+pub struct StdFileSystem;
+
+impl FileSystem for StdFileSystem {
+    fn read_dir(&self, path: &Path) -> io::Result<Box<dyn Iterator<Item = io::Result<DirEntry>> + '_>> {
+        let entries = readonly::read_dir(path)?;
+        Ok(Box::new(entries.map(|entry| {
+            entry.map(|entry| DirEntry {
+                name: entry.file_name().to_os_string(),
+                path: entry.path().to_path_buf(),
+            })
+        })))
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<Metadata> {
+        platform::metadata(path)
+    }
+
+    fn symlink_metadata(&self, path: &Path) -> io::Result<Metadata> {
+        platform::symlink_metadata(path)
+    }
+
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+        fs::read_link(path)
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        fs::canonicalize(path)
+    }
+
+    fn open(&self, path: &Path) -> io::Result<Box<dyn FileReader>> {
+        Ok(Box::new(readonly::open_file(path)?))
+    }
+
+    fn is_local(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        dir_utils::{list_entries, list_files_detailed, EntryErrors, FileKind, ListOptions, SortKey, SortSpec},
+        filter::Filter,
+    };
+    use std::collections::{BTreeMap, BTreeSet};
+
+    /// A fixed tree of entries held in memory
+    #[derive(Debug, Default)]
+    struct MemoryFileSystem {
+        entries: BTreeMap<PathBuf, Metadata>,
+        links: BTreeMap<PathBuf, PathBuf>,
+        /// Entries that are listed but whose metadata cannot be read
+        unreadable: BTreeSet<PathBuf>,
+    }
+
+    impl MemoryFileSystem {
+        fn add(mut self, path: &str, file_type: FileType, len: u64) -> Self {
+            let mode = if file_type == FileType::Directory { 0o040755 } else { 0o100644 };
+            self.entries.insert(PathBuf::from(path), Metadata { file_type, mode, len, nlink: 1, ..Default::default() });
+            self
+        }
+
+        fn link(mut self, path: &str, target: &str) -> Self {
+            self.links.insert(PathBuf::from(path), PathBuf::from(target));
+            self.add(path, FileType::Symlink, target.len() as u64)
+        }
+
+        fn unreadable(mut self, path: &str) -> Self {
+            self.unreadable.insert(PathBuf::from(path));
+            self
+        }
+
+        fn not_found() -> io::Error {
+            io::Error::from(io::ErrorKind::NotFound)
+        }
+    }
+
+    impl FileSystem for MemoryFileSystem {
+        fn read_dir(&self, path: &Path) -> io::Result<Box<dyn Iterator<Item = io::Result<DirEntry>> + '_>> {
+            if !self.symlink_metadata(path)?.is_dir() {
+                return Err(io::Error::from(io::ErrorKind::NotADirectory));
+            }
+            let path = path.to_path_buf();
+            Ok(Box::new(self.entries.keys().filter(move |entry| entry.parent() == Some(&path)).map(|entry| {
+                Ok(DirEntry {
+                    name: entry.file_name().unwrap_or_default().to_os_string(),
+                    path: entry.clone(),
+                })
+            })))
+        }
+
+        fn metadata(&self, path: &Path) -> io::Result<Metadata> {
+            self.symlink_metadata(&self.canonicalize(path)?)
+        }
+
+        fn symlink_metadata(&self, path: &Path) -> io::Result<Metadata> {
+            if self.unreadable.contains(path) {
+                return Err(io::Error::from(io::ErrorKind::PermissionDenied));
+            }
+            self.entries.get(path).cloned().ok_or_else(Self::not_found)
+        }
+
+        fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+            self.links.get(path).cloned().ok_or_else(Self::not_found)
+        }
+
+        fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+            match self.links.get(path) {
+                Some(target) => self.canonicalize(&path.parent().unwrap_or(path).join(target)),
+                None => self.entries.contains_key(path).then(|| path.to_path_buf()).ok_or_else(Self::not_found),
+            }
+        }
+    }
+
+    #[test]
+    fn test_memory_file_system() {
+        let memory = MemoryFileSystem::default()
+            .add("/archive", FileType::Directory, 0)
+            .add("/archive/small.txt", FileType::File, 10)
+            .add("/archive/large.bin", FileType::File, 5000)
+            .add("/archive/docs", FileType::Directory, 0)
+            .link("/archive/latest", "large.bin")
+            .link("/archive/stale", "gone");
+        let options = ListOptions::default().sort(SortSpec::by(vec![SortKey::Size])).file_system(memory);
+
+        let entries = list_entries("/archive", &options).unwrap();
+        let names: Vec<&str> = entries.iter().map(|entry| entry.name.as_str()).collect();
+        assert_eq!(names, ["large.bin", "small.txt", "latest", "stale", "docs"]);
+        assert_eq!(entries[2].kind, FileKind::Symlink);
+        assert_eq!(entries[2].target_kind, Some(FileKind::File));
+        assert_eq!(entries[3].kind, FileKind::BrokenSymlink);
+
+        let files = list_files_detailed("/archive", &options).unwrap();
+        assert_eq!(files[2].link_target.as_deref(), Some(Path::new("large.bin")));
+        assert!(list_entries("/archive/small.txt", &options).is_err());
+    }
+
+    #[test]
+    fn test_git_ignore_skips_other_file_systems() {
+        let memory = MemoryFileSystem::default().add("/repo", FileType::Directory, 0).add("/repo/build.log", FileType::File, 1);
+        let options = ListOptions::default().filter(Filter { git_ignore: true, ..Default::default() }).file_system(memory);
+        let names: Vec<String> = list_entries("/repo", &options).unwrap().into_iter().map(|entry| entry.name).collect();
+        assert_eq!(names, ["build.log"]);
+    }
+
+    #[test]
+    fn test_entry_errors() {
+        let memory = MemoryFileSystem::default().add("/d", FileType::Directory, 0).add("/d/a", FileType::File, 1).add("/d/locked", FileType::File, 1).unreadable("/d/locked");
+        let options = ListOptions::default().file_system(memory);
+        let names: Vec<String> = list_entries("/d", &options).unwrap().into_iter().map(|entry| entry.name).collect();
+        assert_eq!(names, ["a"]);
+        let errors = options.errors.take();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].path, Path::new("/d/locked"));
+        assert!(options.errors.take().is_empty());
+
+        // Copies share their errors, while options with their own keep them apart
+        list_entries("/d", &options.clone().classify(true)).unwrap();
+        let separate = ListOptions { errors: EntryErrors::default(), ..options.clone() };
+        list_entries("/d", &separate).unwrap();
+        assert_eq!(options.errors.take().len(), 1);
+        assert_eq!(separate.errors.take().len(), 1);
+    }
+
+    #[test]
+    fn test_hidden_attribute() {
+        let memory = || {
+            let mut memory = MemoryFileSystem::default().add("/c", FileType::Directory, 0).add("/c/boot.ini", FileType::File, 0).add("/c/notes.txt", FileType::File, 0);
+            memory.entries.get_mut(Path::new("/c/boot.ini")).unwrap().attributes = FILE_ATTRIBUTE_HIDDEN | FILE_ATTRIBUTE_SYSTEM;
+            memory
+        };
+        let names = |options: ListOptions| list_entries("/c", &options.file_system(memory())).unwrap().into_iter().map(|entry| entry.name).collect::<Vec<_>>();
+        assert_eq!(names(ListOptions::default()), ["notes.txt"]);
+        assert_eq!(names(ListOptions::default().almost_all(true)), ["boot.ini", "notes.txt"]);
+    }
+
+    #[test]
+    fn test_attribute_letters() {
+        assert_eq!(attribute_letters(0), "---");
+        assert_eq!(attribute_letters(FILE_ATTRIBUTE_HIDDEN | FILE_ATTRIBUTE_SYSTEM), "-hs");
+        assert_eq!(attribute_letters(FILE_ATTRIBUTE_READONLY | 0x20), "r--");
+    }
+
+    #[test]
+    fn test_rewindable() {
+        let data: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+        let mut reader = Rewindable::new(&data[..]);
+        let read = |reader: &mut Rewindable<&[u8]>, pos: SeekFrom| {
+            let mut buf = [0; 4];
+            let at = reader.seek(pos).unwrap() as usize;
+            reader.read_exact(&mut buf).unwrap();
+            assert_eq!(buf, data[at..at + 4]);
+        };
+        read(&mut reader, SeekFrom::Start(10));
+        read(&mut reader, SeekFrom::Start(0));
+        read(&mut reader, SeekFrom::Start(150_000));
+        read(&mut reader, SeekFrom::Current(-100));
+        // Only the last 64KB read are kept
+        assert!(reader.seek(SeekFrom::Start(0)).is_err());
+        assert!(reader.seek(SeekFrom::End(0)).is_err());
+
+        let mut rest = Vec::new();
+        reader.read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, data[149_908..]);
+    }
+}
@@ -0,0 +1,623 @@
+use std::{
+    collections::HashSet,
+    ffi::OsString,
+    path::Path,
+    str::FromStr,
+    time::{Duration, SystemTime},
+};
+
+use chrono::{DateTime, Datelike, Local, NaiveDate, NaiveDateTime, Weekday};
+use clap::ValueEnum;
+use regex::Regex;
+
+use crate::{
+    dir_utils::parse_size,
+    file_system::{FileSystem, Metadata},
+};
+
+/// Criteria an entry must satisfy to appear in a listing
+#[derive(Default, Debug, Clone)]
+pub struct Filter {
+    /// Only keep entries modified at or after this time
+    pub modified_after: Option<SystemTime>,
+    /// Only keep entries modified before this time
+    pub modified_before: Option<SystemTime>,
+    /// Hide entries whose name matches any of these shell globs
+    pub ignore: Vec<String>,
+    /// Hide entries ignored by git: `.gitignore` files, `.git/info/exclude` and the global excludes
+    pub git_ignore: bool,
+    /// Only keep entries of one of these types; empty keeps every type
+    pub types: Vec<EntryType>,
+    /// Only keep entries whose size is within every one of these bounds
+    pub size: Vec<SizeBound>,
+    /// Only keep entries whose permission bits satisfy every one of these specs
+    pub perms: Vec<PermSpec>,
+    /// Only keep symlinks whose target does not exist
+    pub broken_links: bool,
+    /// Only keep entries whose name matches one of these shell globs; empty keeps every name
+    pub names: Vec<String>,
+    /// Only keep entries whose name matches this regular expression
+    pub regex: Option<Regex>,
+}
+
+impl Filter {
+    /// Checks whether an entry with the given metadata passes every criterion
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of the entry to check, used to resolve symlink targets
+    /// * `metadata` - Metadata of the entry to check, not following symlinks
+    /// * `file_system` - Where the entry lives
+    ///
+    /// # Returns
+    ///
+    /// true if the entry should be listed
+    pub fn matches(&self, path: &Path, metadata: &Metadata, file_system: &dyn FileSystem) -> bool {
+        let modified = metadata.modified.unwrap_or(SystemTime::now());
+        self.modified_after.is_none_or(|after| modified >= after)
+            && self.modified_before.is_none_or(|before| modified < before)
+            && (self.types.is_empty() || self.types.iter().any(|entry_type| entry_type.matches(metadata)))
+            && self.size.iter().all(|bound| bound.matches(metadata.len))
+            && self.perms.iter().all(|spec| spec.matches(metadata.mode))
+            && (!self.broken_links || (metadata.is_symlink() && file_system.metadata(path).is_err()))
+    }
+
+    /// Checks whether any criterion looks at more than the entry's name, so entries
+    /// have to be stat'ed before they can be kept or dropped
+    pub fn needs_metadata(&self) -> bool {
+        self.modified_after.is_some()
+            || self.modified_before.is_some()
+            || !self.types.is_empty()
+            || !self.size.is_empty()
+            || !self.perms.is_empty()
+            || self.broken_links
+    }
+
+    /// Keeps only the criteria that decide whether a directory is descended into, dropping
+    /// those on time, type, size, permissions, link state and name selection that only decide
+    /// whether an entry is shown
+    ///
+    /// # Returns
+    ///
+    /// The name-based part of this filter
+    pub fn for_walking(&self) -> Filter {
+        Filter {
+            modified_after: None,
+            modified_before: None,
+            types: Vec::new(),
+            size: Vec::new(),
+            perms: Vec::new(),
+            broken_links: false,
+            names: Vec::new(),
+            regex: None,
+            ..self.clone()
+        }
+    }
+
+    /// Checks whether an entry's name escapes every ignore pattern and is selected
+    /// by the name globs and regular expression
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - File name of the entry to check
+    ///
+    /// # Returns
+    ///
+    /// true if the entry should be listed
+    pub fn matches_name(&self, name: &str) -> bool {
+        !self.ignore.iter().any(|pattern| glob_match(pattern, name))
+            && (self.names.is_empty() || self.names.iter().any(|pattern| glob_match(pattern, name)))
+            && self.regex.as_ref().is_none_or(|regex| regex.is_match(name))
+    }
+}
+
+impl Filter {
+    /// Collects the names in a directory that git does not ignore, for `--git-ignore`
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - The directory about to be listed
+    /// * `file_system` - Where the directory is read from
+    ///
+    /// # Returns
+    ///
+    /// The names to keep, or None if git ignore rules are not applied. They are only
+    /// applied on the local file system; outside a git repository every name is kept.
+    pub fn git_visible(&self, dir: &Path, file_system: &dyn FileSystem) -> Option<HashSet<OsString>> {
+        if !self.git_ignore || !file_system.is_local() {
+            return None;
+        }
+        let walk = ignore::WalkBuilder::new(dir)
+            .max_depth(Some(1))
+            .hidden(false)
+            .ignore(false)
+            .parents(true)
+            .git_ignore(true)
+            .git_exclude(true)
+            .git_global(true)
+            .build();
+        Some(
+            walk.filter_map(Result::ok)
+                .filter(|entry| entry.depth() == 1)
+                .map(|entry| entry.file_name().to_os_string())
+                .collect(),
+        )
+    }
+}
+
+/// Matches a name against a shell glob supporting `*`, `?` and `[...]` classes
+///
+/// # Arguments
+///
+/// * `pattern` - The glob, such as `*.o` or `[!.]*`
+/// * `name` - The file name to test
+///
+/// # Returns
+///
+/// true if the whole name matches the pattern
+pub fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    // Where to resume after the most recent `*` if the rest fails to match
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while n < name.len() {
+        let step = match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p + 1, n));
+                p += 1;
+                continue;
+            }
+            Some('?') => Some(p + 1),
+            Some('[') => match_class(&pattern, p, name[n]),
+            Some('\\') if p + 1 < pattern.len() => (pattern[p + 1] == name[n]).then_some(p + 2),
+            Some(&literal) => (literal == name[n]).then_some(p + 1),
+            None => None,
+        };
+        match (step, backtrack) {
+            (Some(next), _) => {
+                p = next;
+                n += 1;
+            }
+            (None, Some((star, start))) => {
+                backtrack = Some((star, start + 1));
+                p = star;
+                n = start + 1;
+            }
+            (None, None) => return false,
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Matches one character against the `[...]` class starting at `start`
+///
+/// # Returns
+///
+/// The pattern index just past the class if it matches, or None. An unterminated
+/// `[` is treated as a literal bracket.
+fn match_class(pattern: &[char], start: usize, c: char) -> Option<usize> {
+    let mut i = start + 1;
+    let negated = matches!(pattern.get(i), Some('!') | Some('^'));
+    if negated {
+        i += 1;
+    }
+    let mut matched = false;
+    let mut first = true;
+    while i < pattern.len() && (first || pattern[i] != ']') {
+        first = false;
+        if i + 2 < pattern.len() && pattern[i + 1] == '-' && pattern[i + 2] != ']' {
+            matched |= (pattern[i]..=pattern[i + 2]).contains(&c);
+            i += 3;
+        } else {
+            matched |= pattern[i] == c;
+            i += 1;
+        }
+    }
+    if i >= pattern.len() {
+        return (c == '[').then_some(start + 1);
+    }
+    (matched != negated).then_some(i + 1)
+}
+
+/// Entry types that `--type` can restrict a listing to
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum EntryType {
+    /// Regular files, including executables
+    #[value(name = "f", alias = "file")]
+    File,
+    /// Directories
+    #[value(name = "d", alias = "dir")]
+    Directory,
+    /// Symbolic links, whether or not their target exists
+    #[value(name = "l", alias = "link")]
+    Symlink,
+    /// Regular files with an execute bit set
+    #[value(name = "x", alias = "executable")]
+    Executable,
+}
+
+impl EntryType {
+    /// Checks whether an entry is of this type
+    ///
+    /// # Arguments
+    ///
+    /// * `metadata` - Metadata of the entry, not following symlinks
+    pub fn matches(&self, metadata: &Metadata) -> bool {
+        match self {
+            EntryType::File => metadata.is_file(),
+            EntryType::Directory => metadata.is_dir(),
+            EntryType::Symlink => metadata.is_symlink(),
+            EntryType::Executable => metadata.is_file() && metadata.is_executable(),
+        }
+    }
+}
+
+/// A bound on entry sizes in find's syntax: `+SIZE` for larger, `-SIZE` for smaller,
+/// or a plain `SIZE` for exactly that size
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SizeBound {
+    Above(u64),
+    Below(u64),
+    Exactly(u64),
+}
+
+impl SizeBound {
+    /// Checks whether a size in bytes is within this bound
+    pub fn matches(&self, size: u64) -> bool {
+        match *self {
+            SizeBound::Above(bound) => size > bound,
+            SizeBound::Below(bound) => size < bound,
+            SizeBound::Exactly(bound) => size == bound,
+        }
+    }
+}
+
+impl FromStr for SizeBound {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let (bound, size): (fn(u64) -> SizeBound, &str) = match text.as_bytes().first() {
+            Some(b'+') => (SizeBound::Above, &text[1..]),
+            Some(b'-') => (SizeBound::Below, &text[1..]),
+            _ => (SizeBound::Exactly, text),
+        };
+        parse_size(size)
+            .map(bound)
+            .ok_or_else(|| format!("invalid size '{}' (expected e.g. +10M, -1k or 512)", text))
+    }
+}
+
+/// How a `--perm` spec's bits are compared with an entry's mode
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PermMatch {
+    /// The permission bits equal the spec's bits
+    Exact,
+    /// All of the spec's bits are set (`-` prefix)
+    All,
+    /// Any of the spec's bits is set (`/` prefix)
+    Any,
+}
+
+/// A permission test in find's `-perm` syntax: octal such as `644`, or symbolic
+/// such as `u=rw,go=r`, optionally prefixed with `-` (all bits) or `/` (any bit)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PermSpec {
+    pub compare: PermMatch,
+    /// Permission bits, including the setuid, setgid and sticky bits
+    pub bits: u32,
+}
+
+impl PermSpec {
+    /// Checks whether a mode satisfies this spec
+    pub fn matches(&self, mode: u32) -> bool {
+        let mode = mode & 0o7777;
+        match self.compare {
+            PermMatch::Exact => mode == self.bits,
+            PermMatch::All => mode & self.bits == self.bits,
+            // Like find, `/000` matches every entry
+            PermMatch::Any => self.bits == 0 || mode & self.bits != 0,
+        }
+    }
+}
+
+impl FromStr for PermSpec {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let (compare, mode) = match text.as_bytes().first() {
+            Some(b'-') => (PermMatch::All, &text[1..]),
+            Some(b'/') => (PermMatch::Any, &text[1..]),
+            _ => (PermMatch::Exact, text),
+        };
+        let bits = if !mode.is_empty() && mode.bytes().all(|b| b.is_ascii_digit()) {
+            u32::from_str_radix(mode, 8).ok().filter(|&bits| bits <= 0o7777)
+        } else {
+            symbolic_mode(mode)
+        };
+        bits.map(|bits| PermSpec { compare, bits })
+            .ok_or_else(|| format!("invalid permissions '{}' (expected e.g. 644, -g+w or /u+s)", text))
+    }
+}
+
+/// Parses a symbolic mode such as `u+s` or `u=rw,go=r`, starting from no bits set
+fn symbolic_mode(text: &str) -> Option<u32> {
+    let mut bits = 0;
+    for clause in text.split(',') {
+        let op_at = clause.find(['+', '-', '='])?;
+        let who = match &clause[..op_at] {
+            "" => "ugo",
+            who if who.chars().all(|c| "ugoa".contains(c)) => who,
+            _ => return None,
+        };
+        // Bits for each permission letter, masked down to the classes named in `who`
+        let mut class_mask = 0;
+        for c in who.chars() {
+            class_mask |= match c {
+                'u' => 0o4700,
+                'g' => 0o2070,
+                'o' => 0o1007,
+                _ => 0o7777,
+            };
+        }
+        let mut perms = 0;
+        for c in clause[op_at + 1..].chars() {
+            perms |= match c {
+                'r' => 0o444,
+                'w' => 0o222,
+                'x' => 0o111,
+                's' => 0o6000,
+                't' => 0o1000,
+                _ => return None,
+            };
+        }
+        let perms = perms & class_mask;
+        match &clause[op_at..op_at + 1] {
+            "+" => bits |= perms,
+            "-" => bits &= !perms,
+            _ => bits = (bits & !class_mask) | perms,
+        }
+    }
+    Some(bits)
+}
+
+/// Named time boundaries in the local timezone
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TimePreset {
+    Today,
+    ThisWeek,
+    ThisMonth,
+}
+
+impl TimePreset {
+    /// Computes the start of the period this preset covers
+    ///
+    /// # Returns
+    ///
+    /// Local midnight of today, of this week's Monday, or of the first of the month
+    pub fn start(&self) -> SystemTime {
+        let today = Local::now().date_naive();
+        let date = match self {
+            TimePreset::Today => today,
+            TimePreset::ThisWeek => today.week(Weekday::Mon).first_day(),
+            TimePreset::ThisMonth => today.with_day(1).unwrap_or(today),
+        };
+        local_midnight(date)
+    }
+}
+
+/// Parses the argument of `--newer-than` or `--older-than`: either a duration before
+/// now such as `2d`, `3 weeks` or `1h30m`, or a local date or time such as `2024-01-31`,
+/// `2024-01-31 14:00` or an RFC 3339 timestamp
+///
+/// # Arguments
+///
+/// * `text` - The duration or timestamp
+///
+/// # Returns
+///
+/// The instant it stands for, or a message describing the accepted forms
+pub fn parse_time_bound(text: &str) -> Result<SystemTime, String> {
+    let text = text.trim();
+    if let Some(age) = parse_duration(text) {
+        return Ok(SystemTime::now().checked_sub(age).unwrap_or(SystemTime::UNIX_EPOCH));
+    }
+    if let Ok(time) = DateTime::parse_from_rfc3339(text) {
+        return Ok(time.into());
+    }
+    for format in ["%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M", "%Y-%m-%dT%H:%M:%S", "%Y-%m-%dT%H:%M"] {
+        if let Some(time) = NaiveDateTime::parse_from_str(text, format).ok().and_then(|time| time.and_local_timezone(Local).earliest()) {
+            return Ok(time.into());
+        }
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(text, "%Y-%m-%d") {
+        return Ok(local_midnight(date));
+    }
+    Err(format!("invalid time '{}' (expected a duration like 2d or \"3 weeks\", or a date like 2024-01-31)", text))
+}
+
+/// Parses a duration made of one or more `<number><unit>` parts, such as `90s`, `3 weeks` or `1h30m`
+fn parse_duration(text: &str) -> Option<Duration> {
+    let mut total = Duration::ZERO;
+    let mut rest = text;
+    while !rest.is_empty() {
+        let digits = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+        let count: u64 = rest[..digits].parse().ok()?;
+        rest = rest[digits..].trim_start();
+        let letters = rest.find(|c: char| !c.is_ascii_alphabetic()).unwrap_or(rest.len());
+        let seconds_per = match rest[..letters].to_ascii_lowercase().as_str() {
+            "s" | "sec" | "secs" | "second" | "seconds" => 1,
+            "m" | "min" | "mins" | "minute" | "minutes" => 60,
+            "h" | "hour" | "hours" => 60 * 60,
+            "d" | "day" | "days" => 24 * 60 * 60,
+            "w" | "week" | "weeks" => 7 * 24 * 60 * 60,
+            "mo" | "month" | "months" => 30 * 24 * 60 * 60,
+            "y" | "year" | "years" => 365 * 24 * 60 * 60,
+            _ => return None,
+        };
+        total += Duration::from_secs(count.checked_mul(seconds_per)?);
+        rest = rest[letters..].trim_start();
+    }
+    (total > Duration::ZERO).then_some(total)
+}
+
+/// Converts a calendar date to the instant of its local midnight
+fn local_midnight(date: NaiveDate) -> SystemTime {
+    date.and_hms_opt(0, 0, 0)
+        .and_then(|midnight| midnight.and_local_timezone(Local).earliest())
+        .map(SystemTime::from)
+        .unwrap_or(SystemTime::UNIX_EPOCH)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::file_system::StdFileSystem;
+    use std::{fs, os::unix::fs::PermissionsExt};
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("*.o", "main.o"));
+        assert!(!glob_match("*.o", "main.rs"));
+        assert!(glob_match("target", "target"));
+        assert!(!glob_match("target", "targets"));
+        assert!(glob_match("?.txt", "a.txt"));
+        assert!(!glob_match("?.txt", "ab.txt"));
+        assert!(glob_match("*~", "notes.md~"));
+        assert!(glob_match("a*b*c", "aXbYbc"));
+        assert!(glob_match("[abc]*", "beta"));
+        assert!(!glob_match("[!abc]*", "beta"));
+        assert!(glob_match("file[0-9]", "file7"));
+        assert!(glob_match("[", "["));
+        assert!(glob_match("\\*", "*"));
+        assert!(!glob_match("\\*", "x"));
+
+        let filter = Filter { ignore: vec!["*.o".to_string(), "target".to_string()], ..Default::default() };
+        assert!(!filter.matches_name("main.o"));
+        assert!(!filter.matches_name("target"));
+        assert!(filter.matches_name("main.rs"));
+
+        let filter = Filter {
+            names: vec!["*.rs".to_string(), "*.toml".to_string()],
+            regex: Some(Regex::new("^ma").unwrap()),
+            ..Default::default()
+        };
+        assert!(filter.matches_name("main.rs"));
+        assert!(!filter.matches_name("lib.rs"));
+        assert!(!filter.matches_name("main.o"));
+        assert!(filter.for_walking().matches_name("lib.rs"));
+    }
+
+    #[test]
+    fn test_entry_types() {
+        let temp_dir = tempfile::tempdir().expect("Unable to create temporary directory");
+        let dir = temp_dir.path();
+        fs::File::create(dir.join("plain")).expect("Unable to create plain");
+        fs::File::create(dir.join("script")).expect("Unable to create script");
+        fs::set_permissions(dir.join("script"), fs::Permissions::from_mode(0o755)).expect("Unable to chmod script");
+        std::os::unix::fs::symlink("plain", dir.join("link")).expect("Unable to create link");
+
+        let types_of = |name: &str| {
+            let metadata = StdFileSystem.symlink_metadata(&dir.join(name)).expect("Unable to read metadata");
+            EntryType::value_variants().iter().copied().filter(|entry_type| entry_type.matches(&metadata)).collect::<Vec<_>>()
+        };
+        assert_eq!(types_of("plain"), [EntryType::File]);
+        assert_eq!(types_of("script"), [EntryType::File, EntryType::Executable]);
+        assert_eq!(types_of("link"), [EntryType::Symlink]);
+        assert_eq!(types_of("."), [EntryType::Directory]);
+
+        let filter = Filter { types: vec![EntryType::Directory, EntryType::Symlink], ..Default::default() };
+        let matches = |filter: &Filter, name: &str| filter.matches(&dir.join(name), &StdFileSystem.symlink_metadata(&dir.join(name)).unwrap(), &StdFileSystem);
+        assert!(!matches(&filter, "plain"));
+        assert!(matches(&filter, "link"));
+
+        std::os::unix::fs::symlink("missing", dir.join("dangling")).expect("Unable to create dangling");
+        let filter = Filter { broken_links: true, ..Default::default() };
+        assert!(matches(&filter, "dangling"));
+        assert!(!matches(&filter, "link"));
+        assert!(!matches(&filter, "plain"));
+    }
+
+    #[test]
+    fn test_size_bounds() {
+        assert_eq!("+10M".parse(), Ok(SizeBound::Above(10 << 20)));
+        assert_eq!("-1k".parse(), Ok(SizeBound::Below(1024)));
+        assert_eq!("512".parse(), Ok(SizeBound::Exactly(512)));
+        assert!("+lots".parse::<SizeBound>().is_err());
+
+        let filter = Filter { size: vec![SizeBound::Above(100), SizeBound::Below(1024)], ..Default::default() };
+        assert!(filter.size.iter().all(|bound| bound.matches(500)));
+        assert!(!SizeBound::Above(100).matches(100));
+        assert!(!SizeBound::Below(1024).matches(2048));
+    }
+
+    #[test]
+    fn test_parse_time_bound() {
+        assert_eq!(parse_duration("2d"), Some(Duration::from_secs(2 * 86400)));
+        assert_eq!(parse_duration("3 weeks"), Some(Duration::from_secs(21 * 86400)));
+        assert_eq!(parse_duration("1h30m"), Some(Duration::from_secs(90 * 60)));
+        assert_eq!(parse_duration("1 fortnight"), None);
+        assert_eq!(parse_duration("2024-01-31"), None);
+
+        let hour_ago = parse_time_bound("1h").unwrap();
+        let age = SystemTime::now().duration_since(hour_ago).unwrap();
+        assert!(age >= Duration::from_secs(3600) && age < Duration::from_secs(3660));
+        assert_eq!(parse_time_bound("2024-01-31").unwrap(), local_midnight(NaiveDate::from_ymd_opt(2024, 1, 31).unwrap()));
+        assert_eq!(
+            parse_time_bound("2024-01-31T12:00:00Z").unwrap(),
+            SystemTime::UNIX_EPOCH + Duration::from_secs(1_706_702_400)
+        );
+        assert!(parse_time_bound("2024-01-31 14:00").is_ok());
+        assert!(parse_time_bound("yesterday").is_err());
+    }
+
+    #[test]
+    fn test_perm_specs() {
+        let spec = |text: &str| text.parse::<PermSpec>().unwrap();
+        assert_eq!(spec("644"), PermSpec { compare: PermMatch::Exact, bits: 0o644 });
+        assert_eq!(spec("-g+w"), PermSpec { compare: PermMatch::All, bits: 0o020 });
+        assert_eq!(spec("/u+s"), PermSpec { compare: PermMatch::Any, bits: 0o4000 });
+        assert_eq!(spec("u=rw,go=r").bits, 0o644);
+        assert_eq!(spec("a+x").bits, 0o111);
+        assert_eq!(spec("+t").bits, 0o1000);
+        assert!("9".parse::<PermSpec>().is_err());
+        assert!("u+q".parse::<PermSpec>().is_err());
+
+        assert!(spec("644").matches(0o100644));
+        assert!(!spec("644").matches(0o100664));
+        assert!(spec("-o+w").matches(0o100666));
+        assert!(!spec("-o+w").matches(0o100644));
+        assert!(spec("/u+s,g+s").matches(0o102755));
+        assert!(!spec("/u+s,g+s").matches(0o100755));
+    }
+
+    #[test]
+    fn test_git_visible() {
+        let temp_dir = tempfile::tempdir().expect("Unable to create temporary directory");
+        let repo = temp_dir.path();
+        fs::create_dir_all(repo.join(".git/info")).expect("Unable to create .git");
+        fs::write(repo.join(".gitignore"), "target/\n*.log\n").expect("Unable to write .gitignore");
+        fs::write(repo.join(".git/info/exclude"), "scratch\n").expect("Unable to write exclude");
+        fs::create_dir_all(repo.join("target")).expect("Unable to create target");
+        fs::create_dir_all(repo.join("src")).expect("Unable to create src");
+        for name in ["main.rs", "build.log", "scratch"] {
+            fs::File::create(repo.join(name)).expect("Unable to create file");
+        }
+        fs::File::create(repo.join("src/debug.log")).expect("Unable to create src/debug.log");
+        fs::File::create(repo.join("src/lib.rs")).expect("Unable to create src/lib.rs");
+
+        assert_eq!(Filter::default().git_visible(repo, &StdFileSystem), None);
+        let filter = Filter { git_ignore: true, ..Default::default() };
+        let visible = filter.git_visible(repo, &StdFileSystem).unwrap();
+        for name in ["main.rs", "src", ".gitignore"] {
+            assert!(visible.contains(&OsString::from(name)), "{} should be visible", name);
+        }
+        for name in ["target", "build.log", "scratch"] {
+            assert!(!visible.contains(&OsString::from(name)), "{} should be ignored", name);
+        }
+        // Rules from parent directories apply to subdirectories too
+        let visible = filter.git_visible(&repo.join("src"), &StdFileSystem).unwrap();
+        assert_eq!(visible, HashSet::from([OsString::from("lib.rs")]));
+    }
+}
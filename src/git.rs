@@ -0,0 +1,291 @@
+use std::{
+    collections::HashMap,
+    ffi::OsStr,
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Status shown for tracked entries without changes
+pub const CLEAN: &str = "-";
+
+/// Data loaded per repository root or directory; None marks one whose data could not be read
+type Cache<T> = Mutex<Option<HashMap<PathBuf, Option<Arc<T>>>>>;
+
+/// Working-tree statuses of every repository seen so far
+static REPOS: Cache<Repo> = Mutex::new(None);
+
+/// Last commits below every directory whose history was read, by directory
+static LOGS: Cache<History> = Mutex::new(None);
+
+/// The most recent commit that touched an entry
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Commit {
+    /// Abbreviated commit hash
+    pub hash: String,
+    pub author: String,
+    /// Commit date
+    pub time: SystemTime,
+}
+
+/// Looks up an entry's git working-tree status, such as `M`, `A`, `??` or `I`
+///
+/// Directories show the most significant status of anything changed inside them.
+///
+/// # Arguments
+///
+/// * `path` - The entry's path
+///
+/// # Returns
+///
+/// The status, [`CLEAN`] for unchanged tracked entries, or None if the entry is not
+/// inside a git repository
+pub fn status(path: &Path) -> Option<&'static str> {
+    let (root, path) = locate(path)?;
+    let repo = cached(&REPOS, &root, || Repo::load(&root))?;
+    Some(repo.status(path.strip_prefix(&root).ok()?, path.is_dir()))
+}
+
+/// Looks up the most recent commit that touched an entry, or anything inside a directory
+///
+/// Only the history of the entry's own directory is read, once for all its entries.
+///
+/// # Arguments
+///
+/// * `path` - The entry's path
+///
+/// # Returns
+///
+/// The commit, or None if the entry is not inside a git repository or was never committed
+pub fn last_commit(path: &Path) -> Option<Commit> {
+    let (root, path) = locate(path)?;
+    let dir = path.parent()?;
+    let history = cached(&LOGS, dir, || History::load(&root, dir.strip_prefix(&root).ok()?))?;
+    history.commits.get(path.strip_prefix(&root).ok()?).cloned()
+}
+
+/// Finds the repository containing a path
+///
+/// # Returns
+///
+/// The repository root and the path made absolute, or None outside a repository
+fn locate(path: &Path) -> Option<(PathBuf, PathBuf)> {
+    // Resolve the parent only, so a symlink is looked up as itself
+    let parent = fs::canonicalize(path.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."))).ok()?;
+    let path = parent.join(path.file_name()?);
+    let root = parent.ancestors().find(|dir| dir.join(".git").exists())?.to_path_buf();
+    Some((root, path))
+}
+
+/// Fetches a repository's or directory's data from a cache, loading it on first use
+fn cached<T>(cache: &Cache<T>, key: &Path, load: impl FnOnce() -> Option<T>) -> Option<Arc<T>> {
+    let mut cache = cache.lock().unwrap_or_else(|err| err.into_inner());
+    cache
+        .get_or_insert_with(HashMap::new)
+        .entry(key.to_path_buf())
+        .or_insert_with(|| load().map(Arc::new))
+        .clone()
+}
+
+/// Runs git in a repository without taking the index lock
+///
+/// # Returns
+///
+/// Its standard output, or None if git is missing or failed
+fn git<S: AsRef<OsStr>>(root: &Path, args: &[S]) -> Option<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(root)
+        .arg("--no-optional-locks")
+        .args(args)
+        .output()
+        .ok()
+        .filter(|output| output.status.success())?;
+    Some(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// The changed, untracked and ignored paths of one repository
+#[derive(Debug, Default)]
+struct Repo {
+    /// Status by path relative to the root; untracked or ignored directories are
+    /// listed as a whole
+    changes: HashMap<PathBuf, &'static str>,
+}
+
+impl Repo {
+    /// Reads a repository's status with `git status`
+    fn load(root: &Path) -> Option<Repo> {
+        git(root, &["status", "--porcelain=v1", "-z", "--ignored=matching"]).map(|porcelain| Repo::parse(&porcelain))
+    }
+
+    /// Parses `git status --porcelain=v1 -z` output
+    fn parse(porcelain: &str) -> Repo {
+        let mut changes = HashMap::new();
+        let mut records = porcelain.split('\0').filter(|record| !record.is_empty());
+        while let Some(record) = records.next() {
+            let (Some(xy), Some(path)) = (record.get(..2), record.get(3..)) else {
+                continue;
+            };
+            if xy.starts_with(['R', 'C']) {
+                // Renames and copies are followed by their original path
+                records.next();
+            }
+            changes.insert(PathBuf::from(path.trim_end_matches('/')), code(xy));
+        }
+        Repo { changes }
+    }
+
+    /// Finds the status of a path relative to the repository root
+    fn status(&self, path: &Path, is_dir: bool) -> &'static str {
+        // The entry itself, or an untracked or ignored directory containing it
+        if let Some(code) = path.ancestors().find_map(|ancestor| self.changes.get(ancestor)) {
+            return code;
+        }
+        if !is_dir {
+            return CLEAN;
+        }
+        self.changes
+            .iter()
+            .filter(|(changed, &code)| changed.starts_with(path) && code != "I")
+            .map(|(_, &code)| code)
+            .max_by_key(|code| significance(code))
+            .unwrap_or(CLEAN)
+    }
+}
+
+/// The last commit of every path below one directory of a repository
+#[derive(Debug, Default)]
+struct History {
+    /// Last commit by path relative to the root, including every directory above a
+    /// committed file
+    commits: HashMap<PathBuf, Commit>,
+}
+
+/// Marks the start of each commit in the `git log` output parsed by [`History::parse`]
+const COMMIT_MARKER: char = '\u{1}';
+
+impl History {
+    /// Reads the history of one directory with `git log`, newest commit first
+    ///
+    /// # Arguments
+    ///
+    /// * `root` - The repository root
+    /// * `dir` - The directory, relative to the root; commits touching nothing below it are skipped
+    fn load(root: &Path, dir: &Path) -> Option<History> {
+        let format = format!("--format={}%h%x09%an%x09%ct", COMMIT_MARKER);
+        let dir = if dir.as_os_str().is_empty() { Path::new(".") } else { dir };
+        let args = ["--literal-pathspecs", "-c", "core.quotePath=false", "log", &format, "--name-only", "--"].map(OsStr::new);
+        git(root, &[&args[..], &[dir.as_os_str()]].concat()).map(|log| History::parse(&log))
+    }
+
+    /// Parses `git log --name-only` output whose commit lines are `<marker>hash\tauthor\ttimestamp`
+    fn parse(log: &str) -> History {
+        let mut commits: HashMap<PathBuf, Commit> = HashMap::new();
+        let mut current = None;
+        for line in log.lines().filter(|line| !line.is_empty()) {
+            if let Some(header) = line.strip_prefix(COMMIT_MARKER) {
+                let mut fields = header.splitn(3, '\t');
+                current = match (fields.next(), fields.next(), fields.next().and_then(|time| time.parse().ok())) {
+                    (Some(hash), Some(author), Some(seconds)) => Some(Commit {
+                        hash: hash.to_string(),
+                        author: author.to_string(),
+                        time: UNIX_EPOCH + Duration::from_secs(seconds),
+                    }),
+                    _ => None,
+                };
+                continue;
+            }
+            let Some(commit) = &current else {
+                continue;
+            };
+            // The newest commit is seen first, so the first one recorded for a path wins
+            for path in Path::new(line).ancestors().filter(|path| !path.as_os_str().is_empty()) {
+                if commits.contains_key(path) {
+                    break;
+                }
+                commits.insert(path.to_path_buf(), commit.clone());
+            }
+        }
+        History { commits }
+    }
+}
+
+/// Condenses a porcelain `XY` status into the code shown in listings
+fn code(xy: &str) -> &'static str {
+    match xy {
+        "??" => "??",
+        "!!" => "I",
+        _ if xy.starts_with('A') => "A",
+        _ if xy.contains('D') => "D",
+        _ if xy.starts_with('R') => "R",
+        _ => "M",
+    }
+}
+
+/// Ranks codes so a directory shows the most significant change inside it
+fn significance(code: &str) -> u8 {
+    match code {
+        "M" => 5,
+        "A" => 4,
+        "D" => 3,
+        "R" => 2,
+        "??" => 1,
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_status() {
+        let repo = Repo::parse(" M src/main.rs\0A  new.rs\0R  renamed.rs\0old.rs\0?? scratch/\0!! target/\0");
+        assert_eq!(repo.status(Path::new("src/main.rs"), false), "M");
+        assert_eq!(repo.status(Path::new("src/lib.rs"), false), CLEAN);
+        assert_eq!(repo.status(Path::new("new.rs"), false), "A");
+        assert_eq!(repo.status(Path::new("renamed.rs"), false), "R");
+        assert_eq!(repo.status(Path::new("old.rs"), false), CLEAN);
+        assert_eq!(repo.status(Path::new("scratch/notes.txt"), false), "??");
+        assert_eq!(repo.status(Path::new("target"), true), "I");
+        assert_eq!(repo.status(Path::new("src"), true), "M");
+        assert_eq!(repo.status(Path::new("docs"), true), CLEAN);
+    }
+
+    #[test]
+    fn test_parse_history() {
+        let log = "\u{1}bbb2222\tBob\t200\n\nsrc/main.rs\n\u{1}aaa1111\tAlice\t100\n\nsrc/main.rs\nsrc/lib.rs\nREADME.md\n";
+        let history = History::parse(log);
+        let commit = |path: &str| history.commits.get(Path::new(path)).map(|commit| (commit.hash.as_str(), commit.author.as_str()));
+        assert_eq!(commit("src/main.rs"), Some(("bbb2222", "Bob")));
+        assert_eq!(commit("src/lib.rs"), Some(("aaa1111", "Alice")));
+        assert_eq!(commit("src"), Some(("bbb2222", "Bob")));
+        assert_eq!(commit("README.md"), Some(("aaa1111", "Alice")));
+        assert_eq!(history.commits[Path::new("src")].time, UNIX_EPOCH + Duration::from_secs(200));
+        assert_eq!(commit("missing.rs"), None);
+    }
+
+    #[test]
+    fn test_load_history_of_dir() {
+        let temp_dir = tempfile::tempdir().expect("Unable to create temporary directory");
+        let root = temp_dir.path();
+        let run = |args: &[&str]| {
+            let output = Command::new("git").arg("-C").arg(root).args(["-c", "user.name=Alice", "-c", "user.email=alice@example.com"]).args(args).output();
+            assert!(output.expect("Unable to run git").status.success());
+        };
+        run(&["init", "-q"]);
+        fs::create_dir(root.join("docs")).expect("Unable to create docs");
+        fs::write(root.join("docs/guide.md"), "guide").expect("Unable to write guide.md");
+        fs::write(root.join("main.rs"), "fn main() {}").expect("Unable to write main.rs");
+        run(&["add", "."]);
+        run(&["commit", "-qm", "first"]);
+
+        let docs = History::load(root, Path::new("docs")).expect("Unable to read history");
+        assert!(docs.commits.contains_key(Path::new("docs/guide.md")));
+        assert!(!docs.commits.contains_key(Path::new("main.rs")));
+        let all = History::load(root, Path::new("")).expect("Unable to read history");
+        assert_eq!(all.commits[Path::new("main.rs")].author, "Alice");
+    }
+}
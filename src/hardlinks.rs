@@ -0,0 +1,88 @@
+use std::collections::HashMap;
+
+use clap::ValueEnum;
+
+/// How `--hardlinks` presents entries that are hard links to the same file
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum HardlinkMode {
+    /// Note after each later link which entry it is the same file as
+    Annotate,
+    /// Annotate, and also move every link right after the first entry for its file
+    Group,
+}
+
+/// Finds, for every entry, the earlier entry of the listing it is a hard link of
+///
+/// # Arguments
+///
+/// * `items` - The entries, in listing order
+/// * `file_id` - The entry's device and inode, or None if it has a single link
+///
+/// # Returns
+///
+/// The index of the first entry for the same file, or None for entries that come first
+pub fn originals<T>(items: &[T], file_id: impl Fn(&T) -> Option<(u64, u64)>) -> Vec<Option<usize>> {
+    let mut first: HashMap<(u64, u64), usize> = HashMap::new();
+    items
+        .iter()
+        .enumerate()
+        .map(|(index, item)| {
+            let id = file_id(item)?;
+            match first.get(&id) {
+                Some(&original) => Some(original),
+                None => {
+                    first.insert(id, index);
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+/// Moves every hard link right after the first entry for its file, keeping the
+/// listing order otherwise
+///
+/// # Arguments
+///
+/// * `items` - The entries to reorder in place
+/// * `file_id` - The entry's device and inode, or None if it has a single link
+pub fn group<T>(items: &mut Vec<T>, file_id: impl Fn(&T) -> Option<(u64, u64)>) {
+    let originals = originals(items, file_id);
+    // Each entry is ranked by where its file first appears, then by its own position
+    let mut ranked: Vec<((usize, usize), T)> = originals
+        .iter()
+        .enumerate()
+        .map(|(index, original)| (original.unwrap_or(index), index))
+        .zip(items.drain(..))
+        .collect();
+    ranked.sort_by_key(|(rank, _)| *rank);
+    items.extend(ranked.into_iter().map(|(_, item)| item));
+}
+
+/// The note appended to a later hard link, naming the entry it is the same file as
+///
+/// # Arguments
+///
+/// * `original` - The displayed name of the first entry for the file
+/// * `ascii` - Whether to avoid the non-ASCII arrow
+pub fn annotation(original: &str, ascii: bool) -> String {
+    format!(" {} same as {}", if ascii { "=>" } else { "\u{21d2}" }, original)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_originals() {
+        let ids = [Some((1, 10)), None, Some((1, 11)), Some((1, 10)), Some((2, 10)), Some((1, 10))];
+        assert_eq!(originals(&ids, |id| *id), [None, None, None, Some(0), None, Some(0)]);
+    }
+
+    #[test]
+    fn test_group() {
+        let mut items = vec![("a", Some((1, 10))), ("b", None), ("c", Some((1, 11))), ("d", Some((1, 10))), ("e", Some((1, 11)))];
+        group(&mut items, |(_, id)| *id);
+        assert_eq!(items.iter().map(|(name, _)| *name).collect::<Vec<_>>(), ["a", "d", "b", "c", "e"]);
+    }
+}
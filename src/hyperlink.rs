@@ -0,0 +1,78 @@
+use std::{
+    fmt::Write as _,
+    io::{self, IsTerminal},
+    path::{self, Path},
+};
+
+use clap::ValueEnum;
+
+/// When to wrap file names in OSC 8 terminal hyperlinks
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum HyperlinkMode {
+    /// Always emit hyperlinks
+    Always,
+    /// Emit hyperlinks when stdout is a terminal
+    Auto,
+    /// Never emit hyperlinks
+    #[default]
+    Never,
+}
+
+impl HyperlinkMode {
+    /// Decides whether file names should be hyperlinked in this mode
+    pub fn enabled(&self) -> bool {
+        match self {
+            HyperlinkMode::Always => true,
+            HyperlinkMode::Auto => io::stdout().is_terminal(),
+            HyperlinkMode::Never => false,
+        }
+    }
+}
+
+/// Builds the `file://` URI of a path, percent-encoding everything but unreserved characters and `/`
+///
+/// # Arguments
+///
+/// * `path` - The path to link to; relative paths are made absolute against the current directory
+pub fn file_uri(path: &Path) -> String {
+    let absolute = path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    let bytes = absolute.as_os_str().as_encoded_bytes();
+    let mut uri = String::from("file://");
+    // Windows paths start with a drive letter rather than a separator
+    if !bytes.starts_with(b"/") {
+        uri.push('/');
+    }
+    for &byte in bytes {
+        let byte = if path::is_separator(byte.into()) { b'/' } else { byte };
+        if byte.is_ascii_alphanumeric() || b"/-._~".contains(&byte) {
+            uri.push(byte as char);
+        } else {
+            let _ = write!(uri, "%{:02X}", byte);
+        }
+    }
+    uri
+}
+
+/// Wraps text in an OSC 8 hyperlink to the given path
+///
+/// # Arguments
+///
+/// * `text` - The visible text, usually a (possibly colored) file name
+/// * `path` - The file the link points to
+pub fn wrap(text: &str, path: &Path) -> String {
+    format!("\x1b]8;;{}\x1b\\{}\x1b]8;;\x1b\\", file_uri(path), text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_uri() {
+        assert_eq!(file_uri(Path::new("/tmp/a b/café.txt")), "file:///tmp/a%20b/caf%C3%A9.txt");
+        assert_eq!(
+            wrap("x", Path::new("/x")),
+            "\x1b]8;;file:///x\x1b\\x\x1b]8;;\x1b\\"
+        );
+    }
+}
@@ -0,0 +1,140 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+
+use crate::dir_utils::FileKind;
+
+/// Built-in icons for well-known file names, checked before extensions
+const FILENAME_ICONS: &[(&str, &str)] = &[
+    ("Cargo.lock", "\u{e7a8}"),
+    ("Cargo.toml", "\u{e7a8}"),
+    ("Dockerfile", "\u{f308}"),
+    ("LICENSE", "\u{f718}"),
+    ("Makefile", "\u{e779}"),
+    ("README.md", "\u{f48a}"),
+    (".gitignore", "\u{f1d3}"),
+];
+
+/// Built-in icons by lowercase extension
+const EXTENSION_ICONS: &[(&str, &str)] = &[
+    ("c", "\u{e61e}"),
+    ("css", "\u{e749}"),
+    ("gif", "\u{f1c5}"),
+    ("go", "\u{e626}"),
+    ("gz", "\u{f410}"),
+    ("h", "\u{e61e}"),
+    ("html", "\u{e736}"),
+    ("jpg", "\u{f1c5}"),
+    ("js", "\u{e74e}"),
+    ("json", "\u{e60b}"),
+    ("lock", "\u{f023}"),
+    ("md", "\u{f48a}"),
+    ("pdf", "\u{f1c1}"),
+    ("png", "\u{f1c5}"),
+    ("py", "\u{e606}"),
+    ("rs", "\u{e7a8}"),
+    ("sh", "\u{f489}"),
+    ("tar", "\u{f410}"),
+    ("toml", "\u{e615}"),
+    ("ts", "\u{e628}"),
+    ("txt", "\u{f15c}"),
+    ("yaml", "\u{e615}"),
+    ("yml", "\u{e615}"),
+    ("zip", "\u{f410}"),
+];
+
+const DIRECTORY_ICON: &str = "\u{f115}";
+const SYMLINK_ICON: &str = "\u{f0c1}";
+const EXECUTABLE_ICON: &str = "\u{f489}";
+const FILE_ICON: &str = "\u{f15b}";
+
+/// Icon overrides from the theme file, consulted before the built-in maps
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Icons {
+    /// Icons by extension (without the dot)
+    pub extensions: HashMap<String, String>,
+    /// Icons by exact file name, such as `Dockerfile`
+    pub filenames: HashMap<String, String>,
+}
+
+impl Icons {
+    /// Picks the icon for an entry: exact file name first, then kind, then extension
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The entry's file name
+    /// * `kind` - The entry's kind
+    pub fn icon(&self, name: &str, kind: FileKind) -> &str {
+        self.resolve(name, kind).1
+    }
+
+    /// Names the rule that picked an entry's icon, such as `extension:rs`, `filename:Makefile`
+    /// or `directory`, so other front ends can map it to their own artwork
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The entry's file name
+    /// * `kind` - The entry's kind
+    pub fn icon_id(&self, name: &str, kind: FileKind) -> String {
+        self.resolve(name, kind).0
+    }
+
+    /// Finds an entry's icon along with the identifier of the rule that matched
+    fn resolve(&self, name: &str, kind: FileKind) -> (String, &str) {
+        if let Some(icon) = self.filenames.get(name).map(String::as_str).or_else(|| builtin(FILENAME_ICONS, name)) {
+            return (format!("filename:{}", name), icon);
+        }
+        match kind {
+            FileKind::Directory => ("directory".to_string(), DIRECTORY_ICON),
+            FileKind::Symlink | FileKind::BrokenSymlink => ("symlink".to_string(), SYMLINK_ICON),
+            _ => {
+                let by_extension = name
+                    .rsplit_once('.')
+                    .filter(|(stem, _)| !stem.is_empty())
+                    .map(|(_, ext)| ext.to_ascii_lowercase())
+                    .and_then(|ext| {
+                        let icon = self.extensions.get(&ext).map(String::as_str).or_else(|| builtin(EXTENSION_ICONS, &ext))?;
+                        Some((format!("extension:{}", ext), icon))
+                    });
+                match by_extension {
+                    Some(found) => found,
+                    None if kind == FileKind::Executable => ("executable".to_string(), EXECUTABLE_ICON),
+                    None => ("file".to_string(), FILE_ICON),
+                }
+            }
+        }
+    }
+}
+
+/// Looks up a key in one of the built-in icon tables
+fn builtin(table: &'static [(&str, &'static str)], key: &str) -> Option<&'static str> {
+    table.iter().find(|&&(candidate, _)| candidate == key).map(|&(_, icon)| icon)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_icon_overrides() {
+        let mut icons = Icons::default();
+        assert_eq!(icons.icon("main.rs", FileKind::File), "\u{e7a8}");
+        assert_eq!(icons.icon("Makefile", FileKind::File), "\u{e779}");
+        assert_eq!(icons.icon("src", FileKind::Directory), DIRECTORY_ICON);
+        assert_eq!(icons.icon("notes", FileKind::File), FILE_ICON);
+
+        icons.extensions.insert("rs".to_string(), "R".to_string());
+        icons.filenames.insert("Makefile".to_string(), "M".to_string());
+        icons.filenames.insert("justfile".to_string(), "J".to_string());
+        assert_eq!(icons.icon("main.RS", FileKind::File), "R");
+        assert_eq!(icons.icon("Makefile", FileKind::File), "M");
+        assert_eq!(icons.icon("justfile", FileKind::File), "J");
+        assert_eq!(icons.icon("lib.py", FileKind::File), "\u{e606}");
+
+        assert_eq!(icons.icon_id("main.RS", FileKind::File), "extension:rs");
+        assert_eq!(icons.icon_id("justfile", FileKind::File), "filename:justfile");
+        assert_eq!(icons.icon_id("src", FileKind::Directory), "directory");
+        assert_eq!(icons.icon_id("notes", FileKind::File), "file");
+    }
+}
@@ -0,0 +1,111 @@
+use std::{
+    collections::HashMap,
+    sync::{mpsc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use crate::platform;
+
+/// How long a single user or group lookup may take by default before falling back to numeric ids
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_millis(500);
+
+static USERS: Mutex<NameCache> = Mutex::new(NameCache::new(DEFAULT_TIMEOUT));
+static GROUPS: Mutex<NameCache> = Mutex::new(NameCache::new(DEFAULT_TIMEOUT));
+
+/// Sets how long user and group lookups may take before name resolution is given up on
+pub fn set_timeout(timeout: Duration) {
+    for cache in [&USERS, &GROUPS] {
+        cache.lock().unwrap_or_else(|err| err.into_inner()).timeout = timeout;
+    }
+}
+
+/// Resolves a uid to a user name, falling back to the numeric id
+pub fn user_name(uid: u32) -> String {
+    USERS
+        .lock()
+        .unwrap_or_else(|err| err.into_inner())
+        .lookup(uid, platform::user_name)
+}
+
+/// Resolves a gid to a group name, falling back to the numeric id
+pub fn group_name(gid: u32) -> String {
+    GROUPS
+        .lock()
+        .unwrap_or_else(|err| err.into_inner())
+        .lookup(gid, platform::group_name)
+}
+
+/// Remembers resolved names, and whether the name service stopped answering in time
+struct NameCache {
+    timeout: Duration,
+    /// Set once a lookup timed out; every later id is shown numerically
+    unavailable: bool,
+    names: Option<HashMap<u32, String>>,
+}
+
+impl NameCache {
+    const fn new(timeout: Duration) -> Self {
+        NameCache {
+            timeout,
+            unavailable: false,
+            names: None,
+        }
+    }
+
+    /// Resolves an id to a name, caching both names and failures
+    ///
+    /// The lookup runs on a helper thread so a stalled name service (a missing NSS module
+    /// in a static build, an unreachable directory server) costs at most one timeout.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The uid or gid to resolve
+    /// * `resolve` - Looks up the name for an id
+    fn lookup(&mut self, id: u32, resolve: fn(u32) -> Option<String>) -> String {
+        let names = self.names.get_or_insert_with(HashMap::new);
+        if let Some(name) = names.get(&id) {
+            return name.clone();
+        }
+        if self.unavailable {
+            return id.to_string();
+        }
+
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let _ = sender.send(resolve(id));
+        });
+        let name = match receiver.recv_timeout(self.timeout) {
+            Ok(name) => name.unwrap_or_else(|| id.to_string()),
+            Err(_) => {
+                self.unavailable = true;
+                id.to_string()
+            }
+        };
+        names.insert(id, name.clone());
+        name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lookup_falls_back_after_timeout() {
+        let mut cache = NameCache::new(Duration::from_millis(20));
+        assert_eq!(cache.lookup(1, |_| Some("alice".to_string())), "alice");
+        assert_eq!(cache.lookup(2, |_| None), "2");
+
+        let slow = |_| {
+            thread::sleep(Duration::from_millis(500));
+            Some("slow".to_string())
+        };
+        assert_eq!(cache.lookup(3, slow), "3");
+        assert!(cache.unavailable);
+
+        // Later lookups skip the name service, but earlier answers stay cached
+        assert_eq!(cache.lookup(4, |_| Some("bob".to_string())), "4");
+        assert_eq!(cache.lookup(1, |_| None), "alice");
+    }
+}
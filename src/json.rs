@@ -0,0 +1,89 @@
+use std::{fmt::Write, path::Path, time::UNIX_EPOCH};
+
+use crate::{ascii, color::Theme, dir_utils::Entry};
+
+/// Renders one entry as a single-line JSON object for `--json`
+///
+/// Besides the entry's metadata, the object carries the visual semantics the
+/// terminal listing would use, so graphical front ends can reproduce them:
+/// the `-F` indicator, the `LS_COLORS` class the name is colored by, and the icon.
+///
+/// # Arguments
+///
+/// * `entry` - The entry to render
+/// * `path` - The path to report, as `--absolute` or `--relative-to` may rewrite it
+/// * `theme` - The theme supplying colors and icons
+///
+/// # Returns
+///
+/// JSON such as `{"name":"src","path":"./src","kind":"directory",...}`
+pub fn entry(entry: &Entry, path: &Path, theme: &Theme) -> String {
+    let color_kind = theme.color_kind(entry.kind, entry.target_kind);
+    let modified = entry.modified_time.duration_since(UNIX_EPOCH).map_or(0, |age| age.as_secs());
+    format!(
+        "{{\"name\":{},\"path\":{},\"kind\":{},\"size\":{},\"modified\":{},\"indicator\":{},\"color_class\":{},\"icon\":{},\"icon_id\":{}}}",
+        string(&entry.name),
+        string(&ascii::os_text(path.as_os_str())),
+        string(entry.kind.description().unwrap_or("file")),
+        entry.metadata.len,
+        modified,
+        string(entry.kind.indicator()),
+        string(&theme.name_class(&entry.name, color_kind)),
+        string(theme.icons.icon(&entry.name, entry.kind)),
+        string(&theme.icons.icon_id(&entry.name, entry.kind)),
+    )
+}
+
+/// Quotes and escapes text as a JSON string
+pub fn string(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = write!(quoted, "\\u{:04x}", c as u32);
+            }
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dir_utils::{self, ListOptions};
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_string_escapes() {
+        assert_eq!(string("plain"), "\"plain\"");
+        assert_eq!(string("a \"b\"\\c\n\u{1b}"), "\"a \\\"b\\\"\\\\c\\n\\u001b\"");
+    }
+
+    #[test]
+    fn test_entry_exposes_visual_semantics() {
+        let temp_dir = tempdir().expect("Unable to create temporary directory");
+        fs::create_dir(temp_dir.path().join("src")).expect("Unable to create src");
+        fs::write(temp_dir.path().join("main.rs"), "fn main() {}\n").expect("Unable to write main.rs");
+
+        let entries = dir_utils::list_entries(temp_dir.path().to_str().unwrap(), &ListOptions::default()).unwrap();
+        let mut theme = Theme::default();
+        theme.apply_ls_colors("*.rs=33");
+        let lines: Vec<String> = entries.iter().map(|e| entry(e, &e.path, &theme)).collect();
+
+        assert!(lines[0].starts_with("{\"name\":\"main.rs\","));
+        assert!(lines[0].contains("\"kind\":\"file\",\"size\":13,"));
+        assert!(lines[0].contains("\"indicator\":\"\",\"color_class\":\"*.rs\","));
+        assert!(lines[0].ends_with("\"icon_id\":\"extension:rs\"}"));
+        assert!(lines[1].contains("\"kind\":\"directory\""));
+        assert!(lines[1].contains("\"indicator\":\"/\",\"color_class\":\"di\",\"icon\":\"\u{f115}\",\"icon_id\":\"directory\"}"));
+    }
+}
@@ -0,0 +1,175 @@
+//! The listing engine behind the `ls_oxide` command, for use from other Rust programs
+//!
+//! [`DirLister`] reads a directory the way the command's short listing does, with the
+//! same hidden file handling, sort keys and filters, and yields typed [`Entry`] values:
+//!
+//! ```no_run
+//! use ls_oxide::{DirLister, ListOptions, SortKey, SortSpec};
+//!
+//! let options = ListOptions::default().almost_all(true).sort(SortSpec::by(vec![SortKey::Size]));
+//! for entry in DirLister::new("src").options(options).iter()? {
+//!     println!("{} {}", entry.metadata.len, entry.name);
+//! }
+//! # Ok::<(), ls_oxide::LsError>(())
+//! ```
+//!
+//! Sorting needs the whole directory in memory. [`DirLister::stream`] instead yields
+//! entries as they are read, in directory order, so even huge directories can be
+//! processed with flat memory use:
+//!
+//! ```no_run
+//! use ls_oxide::DirLister;
+//!
+//! for entry in DirLister::new("/var/spool").stream() {
+//!     match entry {
+//!         Ok(entry) => println!("{}", entry.name),
+//!         Err(err) => eprintln!("{}", err),
+//!     }
+//! }
+//! ```
+//!
+//! The modules below are the command's own building blocks and are public so it can
+//! be built on them, but only the items re-exported here are meant as a stable API.
+
+use std::path::{Path, PathBuf};
+
+pub mod args;
+pub mod archive;
+pub mod ascii;
+pub mod bench;
+pub mod capabilities;
+pub mod checksum;
+pub mod color;
+pub mod columns;
+pub mod config;
+pub mod content;
+pub mod cycle;
+pub mod dir_utils;
+pub mod file_system;
+pub mod filter;
+pub mod git;
+pub mod hardlinks;
+pub mod hyperlink;
+pub mod icons;
+pub mod ids;
+pub mod json;
+pub mod links;
+pub mod media;
+pub mod mounts;
+pub mod output;
+pub mod paths;
+pub mod platform;
+pub mod progress;
+pub mod quoting;
+pub mod readonly;
+pub mod render;
+#[cfg(feature = "s3")]
+pub mod s3;
+pub mod stats;
+pub mod terminal;
+pub mod tree_size;
+
+pub use dir_utils::{DotfileOrder, Entry, EntryErrors, FileKind, ListOptions, LsError, Operand, OperandLinks, SortKey, SortSpec};
+pub use file_system::{DirEntry, FileSystem, FileType, Metadata, StdFileSystem};
+pub use filter::{EntryType, Filter};
+
+/// Lists a directory's entries
+#[derive(Clone, Debug)]
+pub struct DirLister {
+    path: PathBuf,
+    options: ListOptions,
+}
+
+impl DirLister {
+    /// Prepares to list a directory with the default options: visible entries only, sorted by name
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The directory to list
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        DirLister { path: path.as_ref().to_path_buf(), options: ListOptions::default() }
+    }
+
+    /// Replaces the options the directory is listed with
+    ///
+    /// # Arguments
+    ///
+    /// * `options` - Which entries to include, how to order them and whether to classify them
+    pub fn options(mut self, options: ListOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Reads the directory
+    ///
+    /// Entries that cannot be read are left out, as the command leaves them out of its
+    /// listings; the options' [`errors`](ListOptions::errors) record what went wrong with them.
+    ///
+    /// # Returns
+    ///
+    /// The entries in listing order, or an error if the directory cannot be read
+    pub fn iter(&self) -> Result<std::vec::IntoIter<Entry>, LsError> {
+        Ok(dir_utils::list_entries(&self.path, &self.options)?.into_iter())
+    }
+
+    /// Reads the directory lazily, one entry at a time, in directory order
+    ///
+    /// The sort options are ignored. Entries that cannot be read are yielded as errors
+    /// in their place, and a directory that cannot be read yields a single error.
+    ///
+    /// # Returns
+    ///
+    /// An iterator over each entry or the error it could not be read with
+    pub fn stream(&self) -> impl Iterator<Item = Result<Entry, LsError>> + '_ {
+        let (entries, error) = match dir_utils::try_stream_entries(&self.path, &self.options) {
+            Ok(entries) => (Some(entries), None),
+            Err(err) => (None, Some(err)),
+        };
+        error.map(Err).into_iter().chain(entries.into_iter().flatten())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_dir_lister() {
+        let temp_dir = tempdir().expect("Unable to create temporary directory");
+        fs::write(temp_dir.path().join("small.txt"), "1").expect("Unable to write small.txt");
+        fs::write(temp_dir.path().join("large.txt"), "12345").expect("Unable to write large.txt");
+        fs::write(temp_dir.path().join(".hidden"), "").expect("Unable to write .hidden");
+        fs::create_dir(temp_dir.path().join("dir")).expect("Unable to create dir");
+
+        let names = |lister: DirLister| lister.iter().unwrap().map(|entry| entry.display_name).collect::<Vec<_>>();
+        assert_eq!(names(DirLister::new(temp_dir.path())), ["dir", "large.txt", "small.txt"]);
+
+        let options = ListOptions::default()
+            .almost_all(true)
+            .classify(true)
+            .sort(SortSpec::by(vec![SortKey::Size]).reverse(true))
+            .filter(Filter { types: vec![EntryType::File], ..Default::default() });
+        assert_eq!(names(DirLister::new(temp_dir.path()).options(options)), [".hidden", "small.txt", "large.txt"]);
+
+        assert!(DirLister::new(temp_dir.path().join("missing")).iter().is_err());
+    }
+
+    #[test]
+    fn test_dir_lister_stream() {
+        let temp_dir = tempdir().expect("Unable to create temporary directory");
+        for name in ["b.txt", "a.txt", ".hidden"] {
+            fs::write(temp_dir.path().join(name), "").expect("Unable to write file");
+        }
+
+        let lister = DirLister::new(temp_dir.path()).options(ListOptions::default().sort(SortSpec::default().reverse(true)));
+        let mut streamed: Vec<String> = lister.stream().map(|entry| entry.unwrap().name).collect();
+        streamed.sort();
+        assert_eq!(streamed, ["a.txt", "b.txt"]);
+
+        let missing = DirLister::new(temp_dir.path().join("missing"));
+        let errors: Vec<_> = missing.stream().collect();
+        assert!(matches!(errors[..], [Err(_)]));
+    }
+}
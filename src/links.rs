@@ -0,0 +1,109 @@
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{ascii, readonly};
+
+/// Why a symlink was reported by the link check
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkIssue {
+    /// The link target does not exist
+    Broken,
+    /// The link resolves to a location outside the scanned root
+    Escapes,
+}
+
+/// A symlink that failed validation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LinkProblem {
+    pub path: PathBuf,
+    pub target: PathBuf,
+    pub issue: LinkIssue,
+}
+
+impl fmt::Display for LinkProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let reason = match self.issue {
+            LinkIssue::Broken => "broken",
+            LinkIssue::Escapes => "escapes root",
+        };
+        write!(f, "{} -> {} ({})", ascii::os_text(self.path.as_os_str()), ascii::os_text(self.target.as_os_str()), reason)
+    }
+}
+
+/// Scans a directory for broken symlinks and, optionally, links escaping it
+///
+/// # Arguments
+///
+/// * `root` - Directory to scan
+/// * `recursive` - Whether to descend into subdirectories (symlinked directories are not followed)
+/// * `check_escaping` - Whether to report links resolving outside `root`
+///
+/// # Returns
+///
+/// Every problematic link found, in directory order
+pub fn check_links(root: &Path, recursive: bool, check_escaping: bool) -> Vec<LinkProblem> {
+    let canonical_root = fs::canonicalize(root).unwrap_or_else(|_| root.to_path_buf());
+    let mut problems = Vec::new();
+    scan(root, &canonical_root, recursive, check_escaping, &mut problems);
+    problems
+}
+
+fn scan(dir: &Path, root: &Path, recursive: bool, check_escaping: bool, problems: &mut Vec<LinkProblem>) {
+    let Ok(entries) = readonly::read_dir(dir) else {
+        return;
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path().to_path_buf();
+        let Ok(file_type) = entry.metadata().map(|metadata| metadata.file_type()) else {
+            continue;
+        };
+
+        if file_type.is_symlink() {
+            let target = fs::read_link(&path).unwrap_or_default();
+            match fs::canonicalize(&path) {
+                Err(_) => problems.push(LinkProblem { path, target, issue: LinkIssue::Broken }),
+                Ok(resolved) if check_escaping && !resolved.starts_with(root) => {
+                    problems.push(LinkProblem { path, target, issue: LinkIssue::Escapes })
+                }
+                Ok(_) => {}
+            }
+        } else if recursive && file_type.is_dir() {
+            scan(&path, root, recursive, check_escaping, problems);
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::symlink;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_check_links() {
+        let outside = tempdir().expect("Unable to create temporary directory");
+        let temp_dir = tempdir().expect("Unable to create temporary directory");
+        let dir_path = temp_dir.path();
+
+        fs::create_dir(dir_path.join("sub")).expect("Unable to create sub");
+        fs::write(dir_path.join("real.txt"), "").expect("Unable to create real.txt");
+        symlink("real.txt", dir_path.join("ok")).expect("Unable to create ok link");
+        symlink("missing.txt", dir_path.join("sub/dangling")).expect("Unable to create dangling link");
+        symlink(outside.path(), dir_path.join("away")).expect("Unable to create away link");
+
+        let problems = check_links(dir_path, false, false);
+        assert!(problems.is_empty());
+
+        let problems = check_links(dir_path, true, false);
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].issue, LinkIssue::Broken);
+        assert_eq!(problems[0].path, dir_path.join("sub/dangling"));
+
+        let problems = check_links(dir_path, true, true);
+        assert_eq!(problems.len(), 2);
+        assert!(problems.iter().any(|p| p.issue == LinkIssue::Escapes && p.path == dir_path.join("away")));
+    }
+}
@@ -1,125 +1,1399 @@
-use std::path::Path;
+use std::{
+    borrow::Cow,
+    env,
+    ffi::{OsStr, OsString},
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    slice,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
 
-use args::Args;
 use clap::Parser;
-use tabled::{settings::Style, Table};
-
-pub mod args;
-pub mod dir_utils;
+use ls_oxide::{
+    archive::{self, ArchiveFileSystem},
+    args::{self, Args, FlatOrder},
+    ascii,
+    capabilities,
+    checksum::{self, HashAlgorithm},
+    color::{Painter, Theme},
+    columns::{self, Column},
+    config::{self, ConfigError},
+    content,
+    cycle::{CycleGuard, CyclePolicy, Visited},
+    dir_utils::{self, ColumnWidths, Entry, FileInfo, FileKind, ListOptions, LsError, Operand},
+    file_system::{FileSystem, StdFileSystem},
+    filter::Filter,
+    git,
+    hardlinks::{self, HardlinkMode},
+    hyperlink,
+    icons::Icons,
+    ids,
+    json,
+    links,
+    media,
+    output::{self, Output},
+    progress::Progress,
+    quoting,
+    readonly,
+    render::{self, RenderOptions},
+    stats::Stats,
+    terminal,
+    tree_size,
+};
+use tabled::settings::Style;
 
 /// Lists files and directories with formatting based on command line arguments
 ///
 /// # Arguments
 ///
 /// * `path` - Path to list contents from
+/// * `file_system` - Where the path is read from
 /// * `args` - Command line arguments controlling listing format options
-fn list_directory(path: &str, args: &Args) {
+/// * `prepared` - What `--uniform-columns` already gathered for this path, if anything
+/// * `columns` - Long format columns to show instead of the default set
+/// * `decor` - Colors and icons to apply
+/// * `output` - Where the listing's sections are written
+///
+/// # Returns
+///
+/// Whether everything below the path could be read, or an error if the listing could
+/// not be written
+fn list_directory(path: &Path, file_system: &Arc<dyn FileSystem>, args: &Args, prepared: Option<Prepared>, columns: Option<&[Column]>, decor: &Decorations, output: &Output) -> io::Result<ExitStatus> {
+    if args.recursive {
+        // Recursive listing
+        let mut listing = RecursiveListing::new(args, path, file_system, columns, decor, output);
+        listing.widths = prepared.map(|prepared| prepared.widths);
+        let status = listing.run(path)?;
+        if args.stats {
+            // Entries the listing could not read were already reported, so these options' are dropped
+            let options = ListOptions { file_system: Arc::clone(file_system), ..args.list_options() };
+            let stats = Stats::from_files(&long_listing(path, &options, args)?, SystemTime::now());
+            let mut out = output.section(path, path)?;
+            write!(out, "\n{}", stats)?;
+            out.finish()?;
+        }
+        return Ok(status);
+    }
+
+    // Recursive listings apply each directory's overrides as they reach it
+    let overridden = dir_args(path, args);
+    let args = overridden.as_ref().unwrap_or(args);
+    let options = ListOptions { file_system: Arc::clone(file_system), ..args.list_options() };
+    let (prepared, mut errors) = prepared.map_or((None, Vec::new()), |prepared| (prepared.files, prepared.errors));
+    let mut out = output.section(path, path)?;
+    let mut stats = None;
     if args.long {
         // Long format listing
-        let show_hidden = args.all || args.almost_all;
-        let files = dir_utils::list_files_detailed(
-            path, 
-            show_hidden, 
-            args.almost_all, 
-            args.human_readable, 
-            args.sort_time, 
-            args.sort_size, 
-            args.reverse, 
-            args.unsorted
-        );
-        let table = Table::new(files).with(Style::blank()).to_string();
-        println!("{}", table)
-    } else if args.recursive {
-        // Recursive listing
-        let show_hidden = args.all || args.almost_all;
-        list_recursive(path, show_hidden, args.almost_all, args.classify, args.sort_time, args.sort_size, args.reverse, args.unsorted, args.one_per_line);
+        let mut files = match prepared {
+            Some(files) => files,
+            None => long_listing(path, &options, args)?,
+        };
+        if args.stats {
+            stats = Some(Stats::from_files(&files, SystemTime::now()));
+        }
+        let total = args.sample.map(|count| dir_utils::sample_entries(&mut files, count, args.seed));
+        let shown = files.len();
+        let omitted = args.limit.map_or(0, |limit| dir_utils::limit_entries(&mut files, limit));
+        write_long(&mut out, files, &**file_system, args, columns, decor)?;
+        write_limit_note(&mut out, omitted, args.ascii)?;
+        write_sample_note(&mut out, shown, total)?;
+    } else if args.unsorted && args.sample.is_none() && args.hardlinks.is_none() {
+        // Unsorted listings are written as the directory is read, so memory stays flat
+        let mut names: Box<dyn Iterator<Item = String>> = if args.plain_names() && !options.filter.needs_metadata() {
+            let names = dir_utils::stream_names(path, &options)?;
+            Box::new(names.map(|(name, path)| entry_text(&name, &path, args)))
+        } else {
+            let entries = dir_utils::stream_entries(path, &options)?;
+            Box::new(entries.map(|entry| render_name(&entry, args, decor)))
+        };
+        write_layout(&mut out, names.by_ref().take(args.limit.unwrap_or(usize::MAX)), args)?;
+        write_limit_note(&mut out, names.count(), args.ascii)?;
+    } else if args.plain_names() {
+        // Names printed as read need no metadata unless sorting or filtering asks for it
+        let mut names = dir_utils::list_names(path, &options.clone().classify(false))?;
+        let total = args.sample.map(|count| dir_utils::sample_entries(&mut names, count, args.seed));
+        let shown = names.len();
+        let omitted = args.limit.map_or(0, |limit| dir_utils::limit_entries(&mut names, limit));
+        write_layout(&mut out, names.iter().map(|(name, path)| entry_text(name, path, args)), args)?;
+        write_limit_note(&mut out, omitted, args.ascii)?;
+        write_sample_note(&mut out, shown, total)?;
     } else {
         // Short listing
-        let show_hidden = args.all || args.almost_all;
-        let files = dir_utils::list_files(
-            path, 
-            show_hidden, 
-            args.almost_all, 
-            args.classify, 
-            args.sort_time, 
-            args.sort_size, 
-            args.reverse, 
-            args.unsorted
-        );
-        
-        if args.one_per_line {
-            for file in files {
-                println!("{}", file);
+        let mut files = dir_utils::list_entries(path, &options)?;
+        let total = args.sample.map(|count| dir_utils::sample_entries(&mut files, count, args.seed));
+        let shown = files.len();
+        let omitted = args.limit.map_or(0, |limit| dir_utils::limit_entries(&mut files, limit));
+        write_names(&mut out, &files, args, decor)?;
+        write_limit_note(&mut out, omitted, args.ascii)?;
+        write_sample_note(&mut out, shown, total)?;
+    }
+
+    errors.extend(options.errors.take());
+    let status = report_entry_errors(errors, args);
+    if args.stats {
+        let stats = match stats {
+            Some(stats) => stats,
+            None => {
+                let stats = Stats::from_files(&long_listing(path, &options, args)?, SystemTime::now());
+                // Entries the listing could not read were already reported
+                options.errors.take();
+                stats
             }
-        } else {
-            for file in files {
-                print!("{}  ", file);
+        };
+        write!(out, "\n{}", stats)?;
+    }
+    out.finish()?;
+    Ok(status)
+}
+
+/// Writes entries as a long format table, decorating their names first
+///
+/// # Arguments
+///
+/// * `out` - Destination of the listing
+/// * `files` - The entries to write, already sorted and trimmed
+/// * `file_system` - Where the entries' contents are read from, for the columns that need them
+/// * `args` - Command line arguments controlling listing format options
+/// * `columns` - Long format columns to show instead of the default set
+/// * `decor` - Colors and icons to apply
+fn write_long(out: &mut dyn Write, mut files: Vec<FileInfo>, file_system: &dyn FileSystem, args: &Args, columns: Option<&[Column]>, decor: &Decorations) -> io::Result<()> {
+    fill_columns(&mut files, file_system, args, columns);
+    write_table(out, files, args, columns, decor)
+}
+
+/// Fills in the long format columns that read the entries' contents or walk below them
+///
+/// # Arguments
+///
+/// * `files` - The entries to fill in
+/// * `file_system` - Where the entries' contents are read from
+/// * `args` - Command line arguments selecting the sizes and hash to compute
+/// * `columns` - Long format columns to show instead of the default set
+fn fill_columns(files: &mut [FileInfo], file_system: &dyn FileSystem, args: &Args, columns: Option<&[Column]>) {
+    if args.total_size {
+        tree_size::apply(files, file_system);
+    } else {
+        args.dir_size.apply(files, file_system);
+    }
+    // A hash column set up in the config file uses SHA-256 unless --hash picks another
+    let hash = args.hash.or_else(|| columns.is_some_and(|columns| columns.contains(&Column::Hash)).then_some(HashAlgorithm::Sha256));
+    if let Some(algorithm) = hash {
+        checksum::apply(files, algorithm, args.hash_max_size, file_system);
+    }
+    let shown = columns.unwrap_or(&columns::DEFAULT_COLUMNS);
+    if shown.contains(&Column::Kind) {
+        content::apply(files, file_system);
+    }
+    if shown.contains(&Column::Media) {
+        media::apply(files, file_system);
+    }
+}
+
+/// Writes entries whose columns are already filled in as a long format table,
+/// decorating their names first
+///
+/// # Arguments
+///
+/// * `out` - Destination of the listing
+/// * `files` - The entries to write, already sorted and trimmed
+/// * `args` - Command line arguments controlling listing format options
+/// * `columns` - Long format columns to show instead of the default set
+/// * `decor` - Colors and icons to apply
+fn write_table(out: &mut dyn Write, mut files: Vec<FileInfo>, args: &Args, columns: Option<&[Column]>, decor: &Decorations) -> io::Result<()> {
+    if args.hardlinks == Some(HardlinkMode::Group) {
+        hardlinks::group(&mut files, FileInfo::file_id);
+    }
+    for file in &mut files {
+        file.name = entry_text(&file.name, &file.path, args);
+    }
+    // Noted from the plain names, before colors and icons are added
+    let originals = if args.hardlinks.is_some() { hardlinks::originals(&files, FileInfo::file_id) } else { Vec::new() };
+    let notes: Vec<Option<String>> = originals
+        .iter()
+        .map(|original| original.map(|original| hardlinks::annotation(&files[original].name, args.ascii)))
+        .collect();
+    if args.slash_dirs && !args.classify {
+        files.iter_mut().filter(|file| file.is_dir).for_each(|file| file.name.push('/'));
+    }
+    if let Some(painter) = &decor.painter {
+        files.iter_mut().for_each(|file| painter.paint_file_name(file));
+    }
+    if let Some(icons) = &decor.icons {
+        for file in &mut files {
+            let file_name = ascii::os_text(file.path.file_name().unwrap_or_default());
+            file.name = format!("{} {}", icons.icon(&file_name, file.kind), file.name);
+        }
+    }
+    if args.hyperlink.enabled() {
+        files.iter_mut().for_each(|file| file.name = hyperlink::wrap(&file.name, &file.path));
+    }
+    files.iter_mut().for_each(|file| file.append_link_target(args.ascii, |target| name_text(target, args)));
+    for (file, note) in files.iter_mut().zip(&notes) {
+        if let Some(note) = note {
+            file.name.push_str(note);
+        }
+    }
+    if args.screen_reader {
+        // Symlinks already read as `name -> target`, marked `(broken)` where needed
+        for file in files.iter_mut().filter(|file| !matches!(file.kind, FileKind::Symlink | FileKind::BrokenSymlink)) {
+            if let Some(description) = file.kind.description() {
+                file.name = format!("{} ({})", file.name, description);
             }
-            println!();
         }
     }
+    let options = RenderOptions { human_readable: args.human_readable, ascii: args.ascii, painter: decor.painter.as_ref(), time_style: args.time_style };
+    let table = render::table(&files, columns.unwrap_or(&columns::DEFAULT_COLUMNS), &options).with(Style::blank()).to_string();
+    writeln!(out, "{}", table)
+}
+
+/// Notes how many entries a sampled listing was drawn from
+///
+/// # Arguments
+///
+/// * `out` - Destination of the listing
+/// * `shown` - How many entries were listed
+/// * `total` - How many entries there were before sampling, or None without `--sample`
+fn write_sample_note(out: &mut dyn Write, shown: usize, total: Option<usize>) -> io::Result<()> {
+    match total {
+        Some(total) if total > shown => writeln!(out, "(sample of {} out of {} entries)", shown, total),
+        _ => Ok(()),
+    }
+}
+
+/// Notes how many entries `--limit` left out
+///
+/// # Arguments
+///
+/// * `out` - Destination of the listing
+/// * `omitted` - How many entries were not listed
+/// * `ascii` - Whether to avoid the non-ASCII ellipsis
+fn write_limit_note(out: &mut dyn Write, omitted: usize, ascii: bool) -> io::Result<()> {
+    if omitted == 0 {
+        return Ok(());
+    }
+    writeln!(out, "{} and {} more", if ascii { "..." } else { "\u{2026}" }, omitted)
+}
+
+/// Writes entry names in the short listing layout
+///
+/// # Arguments
+///
+/// * `out` - Destination of the listing
+/// * `entries` - The entries to write
+/// * `args` - Command line arguments controlling listing format options
+/// * `decor` - Colors and icons to apply
+fn write_names(out: &mut dyn Write, entries: &[Entry], args: &Args, decor: &Decorations) -> io::Result<()> {
+    let mut entries: Vec<&Entry> = entries.iter().collect();
+    if args.hardlinks == Some(HardlinkMode::Group) {
+        hardlinks::group(&mut entries, |entry| entry.file_id());
+    }
+    let originals = if args.hardlinks.is_some() { hardlinks::originals(&entries, |entry| entry.file_id()) } else { Vec::new() };
+    let names = entries.iter().enumerate().map(|(index, entry)| {
+        let mut name = render_name(entry, args, decor);
+        if let Some(original) = originals.get(index).copied().flatten() {
+            name.push_str(&hardlinks::annotation(&entry_text(&entries[original].name, &entries[original].path, args), args.ascii));
+        }
+        name
+    });
+    write_layout(out, names, args)
 }
 
-/// Recursively lists files and directories starting from the given path
+/// Writes already rendered names one per line, comma separated or side by side,
+/// wrapping the last two at the line width
 ///
 /// # Arguments
 ///
-/// * `path` - Path to start listing from
-/// * `show_hidden` - Whether to show hidden files (starting with .)
-/// * `almost_all` - Whether to exclude . and .. from listing
-/// * `classify` - Whether to add file type indicators
-/// * `sort_time` - Whether to sort by modification time
-/// * `sort_size` - Whether to sort by file size
-/// * `reverse` - Whether to reverse the sort order
-/// * `unsorted` - Whether to skip sorting entirely
-/// * `one_per_line` - Whether to list one file per line
-fn list_recursive(path: &str, show_hidden: bool, almost_all: bool, classify: bool, sort_time: bool, sort_size: bool, reverse: bool, unsorted: bool, one_per_line: bool) {
-    println!("\n{}:", path);
-    let files = dir_utils::list_files(path, show_hidden, almost_all, classify, sort_time, sort_size, reverse, unsorted);
-    
-    if one_per_line {
-        for file in &files {
-            println!("{}", file);
+/// * `out` - Destination of the listing
+/// * `names` - The names to write
+/// * `args` - Command line arguments selecting the layout and line width
+fn write_layout<S: AsRef<str>>(out: &mut dyn Write, names: impl Iterator<Item = S>, args: &Args) -> io::Result<()> {
+    let fits = |column: usize, width: usize| args.line_width.is_none_or(|line_width| column + width <= line_width);
+    let mut column = 0;
+    if args.one_per_line {
+        for name in names {
+            writeln!(out, "{}", name.as_ref())?;
+        }
+    } else if args.commas {
+        for (i, name) in names.enumerate() {
+            let name = name.as_ref();
+            let width = terminal::visible_width(name);
+            if i > 0 {
+                // The comma stays on the line it ends
+                if fits(column + 2, width) {
+                    write!(out, ", ")?;
+                    column += 2;
+                } else {
+                    writeln!(out, ",")?;
+                    column = 0;
+                }
+            }
+            write!(out, "{}", name)?;
+            column += width;
         }
+        writeln!(out)?;
     } else {
-        for file in &files {
-            print!("{}  ", file);
+        for name in names {
+            let name = name.as_ref();
+            let width = terminal::visible_width(name);
+            // The two spaces after each name count too, so they never spill onto a line of their own
+            if column > 0 && !fits(column, width + 2) {
+                writeln!(out)?;
+                column = 0;
+            }
+            write!(out, "{}  ", name)?;
+            column += width + 2;
         }
-        println!();
+        writeln!(out)?;
     }
+    Ok(())
+}
 
-    // Recursively list subdirectories
-    for file in files {
-        // Remove file type indicator to get actual filename for path construction
-        let clean_filename = if classify && (file.ends_with('/') || file.ends_with('*')) {
-            &file[..file.len() - 1]
-        } else {
-            &file
+/// Colors and icons applied to entry names on top of the plain listing
+struct Decorations {
+    painter: Option<Painter>,
+    icons: Option<Icons>,
+}
+
+impl Decorations {
+    /// Loads the theme once if colors or icons are wanted
+    ///
+    /// # Arguments
+    ///
+    /// * `args` - Command line arguments selecting colors, icons and the theme file
+    fn new(args: &Args) -> Self {
+        let color = args.color.enabled();
+        let Some(mut theme) = (color || args.icons).then(|| load_theme(args)) else {
+            return Decorations { painter: None, icons: None };
         };
-        
-        let full_path = Path::new(path).join(clean_filename);
-        if full_path.is_dir() {
-            list_recursive(full_path.to_str().unwrap(), show_hidden, almost_all, classify, sort_time, sort_size, reverse, unsorted, one_per_line);
+        let icons = args.icons.then(|| std::mem::take(&mut theme.icons));
+        let painter = color.then(|| {
+            let mut painter = Painter::new(theme, args.color_scale.clone(), args.color_scale_names);
+            painter.highlight_foreign = !args.no_highlight_foreign;
+            painter
+        });
+        Decorations { painter, icons }
+    }
+}
+
+/// Collects the long format entries for a single path
+///
+/// # Arguments
+///
+/// * `path` - Path to list contents from
+/// * `options` - How the path is read, and where entries that cannot be read are recorded
+/// * `args` - Command line arguments controlling listing format options
+///
+/// # Returns
+///
+/// The entries, or an error if the path cannot be read
+fn long_listing(path: &Path, options: &ListOptions, args: &Args) -> Result<Vec<FileInfo>, LsError> {
+    let mut files = dir_utils::list_files_detailed(path, options)?;
+    if args.ascii {
+        files.iter_mut().for_each(ascii::escape_file_info);
+    }
+    Ok(files)
+}
+
+/// Prepares text for printing, hiding control characters under `-q` and escaping
+/// non-ASCII characters under `--ascii`
+fn display_text<'a>(text: &'a str, args: &Args) -> Cow<'a, str> {
+    let text = if args.hide_control_chars { ascii::hide_control(text) } else { Cow::Borrowed(text) };
+    if !args.ascii {
+        return text;
+    }
+    match ascii::escape(&text) {
+        Cow::Owned(escaped) => Cow::Owned(escaped),
+        Cow::Borrowed(_) => text,
+    }
+}
+
+/// Prepares a path for printing, escaping bytes that are not valid UTF-8 and otherwise
+/// as `display_text` does
+fn path_text(path: &Path, args: &Args) -> String {
+    display_text(&ascii::os_text(path.as_os_str()), args).into_owned()
+}
+
+/// Prepares a file name or listed path for printing: hides control characters under `-q`,
+/// quotes it in the chosen quoting style and escapes non-ASCII characters under `--ascii`.
+/// Every listing format prints names through this, so they all quote alike.
+fn name_text(name: &OsStr, args: &Args) -> String {
+    let style = args.quoting_style();
+    let hidden;
+    let name = if args.hide_control_chars && !style.escapes_control() {
+        hidden = ascii::hide_unprintable(name);
+        OsStr::new(hidden.as_ref())
+    } else {
+        name
+    };
+    let quoted = quoting::quote(name, style);
+    if args.ascii {
+        ascii::escape(&quoted).into_owned()
+    } else {
+        quoted.into_owned()
+    }
+}
+
+/// Prepares the text an entry is listed as: its name, or its path under `--absolute`
+/// and `--relative-to`, printed as `name_text` prints names
+fn entry_text(name: &str, path: &Path, args: &Args) -> String {
+    match args.path_style() {
+        Some(style) => name_text(style.render(path).as_os_str(), args),
+        // Quoted from the bytes the file system stores, unless the name is not the
+        // path's own, as for `.` and `..`
+        None => match path.file_name().filter(|file_name| ascii::os_text(file_name) == name) {
+            Some(file_name) => name_text(file_name, args),
+            None => name_text(OsStr::new(name), args),
+        },
+    }
+}
+
+/// Renders an entry's display name for the short listing
+///
+/// # Arguments
+///
+/// * `entry` - The entry to render
+/// * `args` - Command line arguments controlling quoting, escaping and hyperlinks
+/// * `decor` - Colors and icons to apply
+fn render_name(entry: &Entry, args: &Args, decor: &Decorations) -> String {
+    style_entry_text(&format!("{}{}", entry_text(&entry.name, &entry.path, args), entry.indicator()), entry, args, decor)
+}
+
+/// Colors text standing for an entry (its name or full path) the way its name would be
+/// colored, prefixes its icon, and links it to the entry when hyperlinks are on
+fn style_entry_text(text: &str, entry: &Entry, args: &Args, decor: &Decorations) -> String {
+    let mut text = text.to_string();
+    if args.slash_dirs && !args.classify && entry.kind == FileKind::Directory {
+        text.push('/');
+    }
+    if let Some(painter) = &decor.painter {
+        let kind = painter.theme.color_kind(entry.kind, entry.target_kind);
+        text = painter.paint_name(&text, kind, entry.modified_time, entry.metadata.len);
+    }
+    if let Some(icons) = &decor.icons {
+        text = format!("{} {}", icons.icon(&entry.name, entry.kind), text);
+    }
+    if let Some(description) = entry.kind.description().filter(|_| args.screen_reader) {
+        text = format!("{} ({})", text, description);
+    }
+    if args.hyperlink.enabled() {
+        hyperlink::wrap(&text, &entry.path)
+    } else {
+        text
+    }
+}
+
+/// Where a command line path is read from, or why it cannot be listed
+type Opened = Result<Arc<dyn FileSystem>, LsError>;
+
+/// What `--uniform-columns` gathers about a command line path before its turn to be listed
+#[derive(Default)]
+struct Prepared {
+    /// Its long format entries, padded to the shared widths; None if they could not be
+    /// read or the path is listed recursively
+    files: Option<Vec<FileInfo>>,
+    /// The entries left out of them because they could not be read
+    errors: Vec<LsError>,
+    /// The owner and group widths every section is padded to
+    widths: ColumnWidths,
+}
+
+/// Collects the long format entries for every path up front, padding the
+/// owner and group columns to the widest name seen in any section
+///
+/// Recursive listings walk each tree once first, so the sections of every
+/// subdirectory are measured too.
+///
+/// # Arguments
+///
+/// * `args` - Command line arguments controlling listing format options
+/// * `columns` - Long format columns to show instead of the default set
+/// * `decor` - Colors and icons to apply
+/// * `output` - Where the listing's sections are to be written
+///
+/// # Returns
+///
+/// Each path's file system, or why it cannot be listed, with what was gathered for it,
+/// so archives are not opened again to list them
+fn collect_uniform_sections(args: &Args, columns: Option<&[Column]>, decor: &Decorations, output: &Output) -> Vec<(Opened, Prepared)> {
+    let mut widths = ColumnWidths::default();
+    let mut sections: Vec<(Opened, Prepared)> = args
+        .paths
+        .iter()
+        .map(|path| {
+            // Paths that cannot be read are reported when their turn comes to be listed, and
+            // those that are not directories are only measured
+            let file_system = open_operand(path);
+            let Ok(opened) = &file_system else {
+                return (file_system, Prepared::default());
+            };
+            match classify_operand(path, opened, args) {
+                Ok(Operand::Directory) => {}
+                Ok(Operand::File(entry)) => {
+                    widths.update(slice::from_ref(&operand_details(&entry, opened, args)));
+                    return (file_system, Prepared::default());
+                }
+                Err(_) => return (file_system, Prepared::default()),
+            }
+            if args.recursive {
+                RecursiveListing::new(args, path, opened, columns, decor, output).measure(path, &mut widths);
+                return (file_system, Prepared::default());
+            }
+            let options = ListOptions { file_system: Arc::clone(opened), ..args.list_options() };
+            let files = long_listing(path, &options, args).ok();
+            widths.update(files.as_deref().unwrap_or_default());
+            (file_system, Prepared { files, errors: options.errors.take(), ..Default::default() })
+        })
+        .collect();
+
+    for (_, prepared) in &mut sections {
+        if let Some(files) = &mut prepared.files {
+            widths.apply(files);
         }
+        prepared.widths = widths;
     }
+    sections
+}
+
+/// State shared across one recursive listing
+struct RecursiveListing<'a> {
+    args: &'a Args,
+    /// The command line path the listing started from
+    operand: &'a Path,
+    /// How each directory is read; its filter keeps every directory so hidden ones are
+    /// still walked
+    options: ListOptions,
+    /// The full filter, deciding which entries are shown; directories it hides are
+    /// still descended into
+    shown: Filter,
+    /// Long format columns to show instead of the default set
+    columns: Option<&'a [Column]>,
+    decor: &'a Decorations,
+    output: &'a Output,
+    progress: Progress,
+    cycles: CycleGuard,
+    /// How serious the worst problem met so far was
+    status: ExitStatus,
+    /// The device of the listed operand under `--one-file-system`
+    device: Option<u64>,
+    /// Flat lines held back for `--flat-order=depth`, with their depth and path
+    deferred: Vec<(usize, String, String)>,
+    /// The owner and group widths every long format section is padded to, under `--uniform-columns`
+    widths: Option<ColumnWidths>,
+    /// Whether the walk only measures those widths, writing and reporting nothing
+    measuring: bool,
+}
+
+impl<'a> RecursiveListing<'a> {
+    /// Prepares a recursive listing
+    ///
+    /// # Arguments
+    ///
+    /// * `args` - Command line arguments controlling listing format options
+    /// * `operand` - The command line path the listing starts from
+    /// * `file_system` - Where the operand is read from
+    /// * `columns` - Long format columns to show instead of the default set
+    /// * `decor` - Colors and icons to apply
+    /// * `output` - Where each directory's section is written
+    fn new(args: &'a Args, operand: &'a Path, file_system: &Arc<dyn FileSystem>, columns: Option<&'a [Column]>, decor: &'a Decorations, output: &'a Output) -> Self {
+        RecursiveListing {
+            args,
+            operand,
+            options: ListOptions { file_system: Arc::clone(file_system), ..args.list_options() }.filter(args.filter().for_walking()),
+            shown: args.filter(),
+            columns,
+            decor,
+            output,
+            progress: Progress::new(args.osc_progress),
+            cycles: CycleGuard::default(),
+            status: ExitStatus::Success,
+            device: None,
+            deferred: Vec::new(),
+            widths: None,
+            measuring: false,
+        }
+    }
+
+    /// Recursively lists files and directories starting from the given path
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to start listing from
+    /// * `depth` - How many levels below the listed operand `path` is
+    ///
+    /// # Returns
+    ///
+    /// An error if output could not be written, or if a directory cycle was found under `--cycle=error`
+    fn list(&mut self, path: &Path, depth: usize) -> io::Result<()> {
+        let readable = if self.measuring { self.options.file_system.read_dir(path).is_ok() } else { can_open(path, &*self.options.file_system, self.args) };
+        if !readable {
+            self.status.raise(ExitStatus::Minor);
+            return Ok(());
+        }
+        let overridden = dir_args(path, self.args);
+        let args = overridden.as_ref().unwrap_or(self.args);
+        let options = overridden.as_ref().map(|args| {
+            self.options
+                .clone()
+                .all(args.all)
+                .almost_all(args.almost_all)
+                .classify(args.classify)
+                .sort(args.sort_spec())
+                .dereference(args.dereference)
+                .target_info(args.dereference_target_info)
+        });
+        let entries = dir_utils::list_entries(path, options.as_ref().unwrap_or(&self.options))?;
+        // Overridden options are copies, recording into the same place
+        let errors = self.options.errors.take();
+        if !self.measuring {
+            self.status.raise(report_entry_errors(errors, args));
+        }
+        let (mut entries, hidden): (Vec<Entry>, Vec<Entry>) = entries.into_iter().partition(|entry| self.shown.matches_name(&entry.name) && self.shown.matches(&entry.path, &entry.metadata, &*self.options.file_system));
+        // Only sampled and listed subdirectories are descended into
+        let total = args.sample.map(|count| dir_utils::sample_entries(&mut entries, count, args.seed));
+        let shown = entries.len();
+        let omitted = args.limit.map_or(0, |limit| dir_utils::limit_entries(&mut entries, limit));
+
+        if args.min_depth.is_some_and(|min| depth < min) {
+            // Too shallow to show, but still walked
+        } else if self.measuring {
+            if args.long && !args.flat {
+                let files = long_files(&entries, options.as_ref().unwrap_or(&self.options), args);
+                self.widths.get_or_insert_with(ColumnWidths::default).update(&files);
+            }
+        } else if args.flat && args.flat_order == FlatOrder::Depth {
+            // Held back until the whole tree is walked, then sorted by depth and path
+            for entry in &entries {
+                let line = self.flat_line(entry, depth);
+                self.deferred.push((depth, entry.display_path(), line));
+            }
+        } else {
+            let mut out = self.output.section(self.operand, path)?;
+            if args.flat {
+                // Flat listing: one full path per line
+                for entry in &entries {
+                    writeln!(out, "{}", self.flat_line(entry, depth))?;
+                }
+            } else {
+                // Split sections are identified by their file name instead of a header
+                if self.output.has_headers() {
+                    writeln!(out, "\n{}:", name_text(path.as_os_str(), args))?;
+                }
+                if args.long {
+                    let mut files = long_files(&entries, options.as_ref().unwrap_or(&self.options), args);
+                    if let Some(widths) = &self.widths {
+                        widths.apply(&mut files);
+                    }
+                    write_long(&mut out, files, &*self.options.file_system, args, self.columns, self.decor)?;
+                } else {
+                    write_names(&mut out, &entries, args, self.decor)?;
+                }
+            }
+            write_limit_note(&mut out, omitted, args.ascii)?;
+            write_sample_note(&mut out, shown, total)?;
+            out.finish()?;
+        }
+
+        if args.max_depth.is_some_and(|max| depth >= max) {
+            return Ok(());
+        }
+
+        // Recursively list subdirectories
+        let hidden_dirs = hidden.into_iter().filter(|entry| entry.kind == FileKind::Directory);
+        for entry in entries.into_iter().chain(hidden_dirs).filter(|entry| !entry.is_dot()) {
+            // Symlinked directories are only descended into under -L
+            let metadata = dir_utils::entry_metadata(&entry.path, &self.options).ok();
+            if !self.measuring {
+                self.progress.record(metadata.as_ref().map_or(0, |m| m.len));
+            }
+
+            let Some(metadata) = metadata.filter(|m| m.is_dir()) else {
+                continue;
+            };
+            if self.device.is_some_and(|device| metadata.dev != device) {
+                continue;
+            }
+            if !self.cycles.enter(&metadata) {
+                if !self.measuring {
+                    report_cycle(&entry.path, args)?;
+                }
+                continue;
+            }
+            let result = self.list(&entry.path, depth + 1);
+            self.cycles.leave();
+            result?;
+        }
+        Ok(())
+    }
+
+    /// Lists the whole tree under a listed operand
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The operand to list
+    ///
+    /// # Returns
+    ///
+    /// Whether every subdirectory and entry could be read, or an error if output could not be written
+    fn run(mut self, path: &Path) -> io::Result<ExitStatus> {
+        self.enter_operand(path);
+        let result = self.list(path, 0);
+        self.progress.finish();
+        result?;
+
+        if !self.deferred.is_empty() {
+            self.deferred.sort_by(|(a_depth, a_path, _), (b_depth, b_path, _)| a_depth.cmp(b_depth).then_with(|| a_path.cmp(b_path)));
+            let mut out = self.output.section(self.operand, path)?;
+            for (_, _, line) in &self.deferred {
+                writeln!(out, "{}", line)?;
+            }
+            out.finish()?;
+        }
+        Ok(self.status)
+    }
+
+    /// Walks the whole tree under a listed operand as `run` would, only widening the
+    /// owner and group widths to fit each long format section it would show
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The operand to measure
+    /// * `widths` - The widths gathered so far, from this and other operands
+    fn measure(mut self, path: &Path, widths: &mut ColumnWidths) {
+        self.measuring = true;
+        self.widths = Some(*widths);
+        self.enter_operand(path);
+        // Nothing is written while measuring, so the walk cannot fail
+        let _ = self.list(path, 0);
+        *widths = self.widths.unwrap_or(*widths);
+    }
+
+    /// Notes the operand the walk starts from, for cycle detection and `--one-file-system`
+    fn enter_operand(&mut self, path: &Path) {
+        if let Ok(metadata) = self.options.file_system.metadata(path) {
+            self.cycles.enter(&metadata);
+            self.device = self.args.one_file_system.then_some(metadata.dev);
+        }
+    }
+
+    /// Renders one line of the flat listing: the optional depth field and indentation, then the styled path
+    ///
+    /// # Arguments
+    ///
+    /// * `entry` - The entry the path belongs to
+    /// * `depth` - How many levels below the listed operand the entry's directory is
+    fn flat_line(&self, entry: &Entry, depth: usize) -> String {
+        let args = self.args;
+        let path = args.path_style().map_or_else(|| entry.path.clone(), |style| style.render(&entry.path));
+        let text = format!("{}{}", name_text(path.as_os_str(), args), entry.indicator());
+        let field = if args.depth_field { format!("{}\t", depth) } else { String::new() };
+        let indent = if args.indent_depth { "  ".repeat(depth) } else { String::new() };
+        let git = if args.git { format!("{:<2} ", git::status(&entry.path).unwrap_or("")) } else { String::new() };
+        format!("{}{}{}{}", field, git, indent, style_entry_text(&text, entry, args, self.decor))
+    }
+}
+
+/// Checks that recursion can read a directory it reached, warning if it cannot
+///
+/// # Arguments
+///
+/// * `path` - The directory about to be listed
+/// * `file_system` - Where the directory is read from
+/// * `args` - Command line arguments controlling escaping
+///
+/// # Returns
+///
+/// Whether the directory can be read
+fn can_open(path: &Path, file_system: &dyn FileSystem, args: &Args) -> bool {
+    match file_system.read_dir(path) {
+        Ok(_) => true,
+        Err(err) => {
+            report_unreadable(&LsError::new(path, err), args);
+            false
+        }
+    }
+}
+
+/// Opens the file system a command line path is read from: the archive it names or
+/// lies in, S3 for `s3://` URIs when built with the `s3` feature, or the local one
+///
+/// # Arguments
+///
+/// * `path` - The command line path
+///
+/// # Returns
+///
+/// Where the path is read from, or an error if its archive cannot be opened
+fn open_operand(path: &Path) -> Opened {
+    Ok(match archive::find(path) {
+        Some((archive, format)) => Arc::new(ArchiveFileSystem::open(archive, format).map_err(|err| LsError::new(path, err))?),
+        #[cfg(feature = "s3")]
+        None if ls_oxide::s3::is_uri(path) => Arc::new(ls_oxide::s3::S3FileSystem::default()),
+        None => Arc::new(StdFileSystem),
+    })
+}
+
+/// Looks up a command line path in its file system to decide how it is listed
+///
+/// # Arguments
+///
+/// * `path` - The command line path
+/// * `file_system` - Where the path is read from
+/// * `args` - Command line arguments selecting which symlinks are followed
+///
+/// # Returns
+///
+/// Whether the path is listed as a directory or as an entry, or an error if it cannot be accessed
+fn classify_operand(path: &Path, file_system: &Arc<dyn FileSystem>, args: &Args) -> Result<Operand, LsError> {
+    let options = ListOptions { file_system: Arc::clone(file_system), ..args.list_options() };
+    dir_utils::operand(path, &options, args.operand_links())
+}
+
+/// Prints why a directory could not be listed
+///
+/// # Arguments
+///
+/// * `err` - The error naming the directory
+/// * `args` - Command line arguments controlling escaping
+fn report_unreadable(err: &LsError, args: &Args) {
+    eprintln!("ls_oxide: {}", display_text(&err.to_string(), args));
+}
+
+/// Prints the entries a listing left out because they could not be read
+///
+/// # Arguments
+///
+/// * `errors` - What went wrong with the entries, taken from the listing's options
+/// * `args` - Command line arguments controlling escaping
+///
+/// # Returns
+///
+/// A minor problem if any entry was left out
+fn report_entry_errors(errors: Vec<LsError>, args: &Args) -> ExitStatus {
+    errors.iter().for_each(|err| report_unreadable(err, args));
+    if errors.is_empty() {
+        ExitStatus::Success
+    } else {
+        ExitStatus::Minor
+    }
+}
+
+/// Reports a directory that recursion reached again, as `--cycle` asks
+///
+/// # Arguments
+///
+/// * `path` - The directory that is not listed again
+/// * `args` - Command line arguments selecting the cycle policy
+///
+/// # Returns
+///
+/// An error under `--cycle=error`; otherwise the directory is to be skipped
+fn report_cycle(path: &Path, args: &Args) -> io::Result<()> {
+    let shown = path_text(path, args);
+    match args.cycle {
+        CyclePolicy::Skip => Ok(()),
+        CyclePolicy::Warn => {
+            eprintln!("ls_oxide: {}: not listing already-listed directory", shown);
+            Ok(())
+        }
+        CyclePolicy::Error => Err(io::Error::other(format!("{}: directory cycle detected", shown))),
+    }
+}
+
+/// Reports broken (and optionally escaping) symlinks under every path
+///
+/// # Arguments
+///
+/// * `args` - Command line arguments selecting the paths and scan scope
+///
+/// # Returns
+///
+/// true if any problematic link was found, or an error if the report could not be written
+fn run_link_check(args: &Args) -> io::Result<bool> {
+    let mut out = io::BufWriter::new(io::stdout().lock());
+    let mut found = false;
+    for path in &args.paths {
+        for problem in links::check_links(path, args.recursive, args.check_links_escaping) {
+            writeln!(out, "{}", display_text(&problem.to_string(), args))?;
+            found = true;
+        }
+    }
+    out.flush()?;
+    Ok(found)
+}
+
+/// Applies a directory's `.lsoxide.toml` overrides, warning about (and ignoring) unusable files
+///
+/// # Arguments
+///
+/// * `path` - The directory about to be listed
+/// * `args` - The arguments in effect before looking at the directory
+///
+/// # Returns
+///
+/// The arguments to list the directory with, or None to keep `args`
+fn dir_args(path: &Path, args: &Args) -> Option<Args> {
+    if args.no_dir_config {
+        return None;
+    }
+    let flags = match config::load_dir_flags(path) {
+        Ok(flags) => flags?,
+        Err(err) => {
+            eprintln!("ls_oxide: {}", err);
+            return None;
+        }
+    };
+    match args.with_overrides(&flags) {
+        Ok(args) => Some(args),
+        Err(err) => {
+            let message = err.to_string();
+            let reason = message.lines().next().unwrap_or_default().trim_start_matches("error: ");
+            eprintln!("ls_oxide: ignoring '{}': {}", path.join(config::DIR_CONFIG_FILE).display(), reason);
+            None
+        }
+    }
+}
+
+/// Parses the command line on top of the defaults from the configuration file, exiting on failure
+///
+/// Flags from the config file come first, so the same flags given on the command line override them.
+fn parse_args() -> Args {
+    let defaults = config::load().and_then(|config| {
+        config.default_flags().map_err(|name| {
+            ConfigError::Default(config::config_path().unwrap_or_default(), name)
+        })
+    });
+    let defaults = defaults.unwrap_or_else(|err| {
+        eprintln!("ls_oxide: {}", err);
+        std::process::exit(2);
+    });
+
+    let mut argv = env::args_os();
+    let program = argv.next().unwrap_or_default();
+    if let Err(message) = args::check_short_options(env::args_os().skip(1)) {
+        eprintln!("ls_oxide: {}", message);
+        std::process::exit(2);
+    }
+    if let Err(err) = Args::try_parse_from(std::iter::once(program.clone()).chain(defaults.iter().map(OsString::from))) {
+        let path = config::config_path().unwrap_or_default();
+        eprintln!("ls_oxide: invalid [defaults] in config '{}'", path.display());
+        err.exit();
+    }
+    let base = std::iter::once(program).chain(defaults.into_iter().map(OsString::from)).collect();
+    Args::parse_layered(base, argv.collect())
+}
+
+/// Looks up a named column preset in the configuration file, exiting on failure
+///
+/// # Arguments
+///
+/// * `name` - The preset name given to `--preset`
+fn resolve_preset(name: &str) -> Vec<Column> {
+    let mut config = config::load().unwrap_or_else(|err| {
+        eprintln!("ls_oxide: {}", err);
+        std::process::exit(2);
+    });
+    config.preset.remove(name).unwrap_or_else(|| {
+        eprintln!("ls_oxide: unknown preset '{}'", name);
+        std::process::exit(2);
+    })
+}
+
+/// Loads the color theme from `--theme`, or the config directory plus `LS_COLORS`, exiting on failure
+///
+/// # Arguments
+///
+/// * `args` - Command line arguments possibly naming a theme file
+fn load_theme(args: &Args) -> Theme {
+    let mut theme = Theme::load(args.theme.as_deref()).unwrap_or_else(|err| {
+        eprintln!("ls_oxide: {}", err);
+        std::process::exit(2);
+    });
+    // An explicitly chosen theme file wins over the environment
+    if args.theme.is_none() {
+        if let Ok(spec) = env::var("LS_COLORS") {
+            theme.apply_ls_colors(&spec);
+        }
+    }
+    theme
 }
 
 fn main() {
-    let args = Args::parse();
-    
-    // If only one path and it's the default ".", list it without header
-    if args.paths.len() == 1 && args.paths[0] == "." {
-        list_directory(&args.paths[0], &args);
+    let mut args = parse_args();
+    args.resolve_implied_flags();
+    if args.assert_readonly {
+        readonly::enable();
+    }
+    if let Some(base) = &args.relative_to {
+        match fs::canonicalize(base) {
+            Ok(base) => args.relative_to = Some(base),
+            Err(err) => {
+                eprintln!("ls_oxide: cannot access '{}': {}", path_text(base, &args), err);
+                std::process::exit(2);
+            }
+        }
+    }
+    // Changes the environment, so it has to happen while this is the only thread
+    if let Some(zone) = &args.time_zone {
+        if let Err(err) = dir_utils::set_time_zone(zone) {
+            eprintln!("ls_oxide: {}", err);
+            std::process::exit(2);
+        }
+    }
+    if let Some(jobs) = args.jobs {
+        dir_utils::set_jobs(jobs.into());
+    }
+
+    ids::set_timeout(Duration::from_millis(args.nss_timeout));
+
+    let output = match open_output(&args) {
+        Ok(output) => output,
+        Err(err) => {
+            eprintln!("ls_oxide: {}", err);
+            std::process::exit(2);
+        }
+    };
+
+    if args.capabilities {
+        finish(write_capabilities(&output).and_then(|()| output.finish()).map(|()| ExitStatus::Success));
+        return;
+    }
+
+    if args.json {
+        finish(list_json(&args, &output).and_then(|status| output.finish().map(|()| status)));
+        return;
+    }
+
+    if args.check_links || args.check_links_escaping {
+        let found = run_link_check(&args);
+        // The report goes straight to stdout, so an output file's temporary file is discarded
+        drop(output);
+        finish(found.map(|found| if found { ExitStatus::Minor } else { ExitStatus::Success }));
+        return;
+    }
+
+    // Resolve the column preset up front so a bad name fails before any output
+    let mut columns = args.preset.as_deref().map(resolve_preset).unwrap_or_else(|| columns::DEFAULT_COLUMNS.to_vec());
+    if args.exact_size && args.human_readable {
+        columns = columns::with_exact_size(&columns);
+    }
+    if let Some(placement) = args.octal_permissions {
+        columns = columns::with_octal(&columns, placement);
+    }
+    if args.created {
+        columns = columns::with_created(&columns);
+    }
+    if args.allocated {
+        columns = columns::with_allocated(&columns);
+    }
+    if args.no_group {
+        columns.retain(|&column| column != Column::Group);
+    }
+    // Columns flags add right before the name, in the order they are shown
+    let before_name: &[(bool, &[Column])] = &[
+        (args.filesystem, &[Column::Filesystem]),
+        (args.hash.is_some(), &[Column::Hash]),
+        (args.content_kind, &[Column::Kind]),
+        (args.media, &[Column::Media]),
+        (args.git, &[Column::Git]),
+        (args.git_log, &[Column::Committed, Column::Commit, Column::Author]),
+        #[cfg(windows)]
+        (args.attributes, &[Column::Attributes]),
+        #[cfg(target_os = "macos")]
+        (args.mac_metadata, &[Column::Flags, Column::Xattrs, Column::Quarantine]),
+    ];
+    let columns = before_name
+        .iter()
+        .filter(|&&(shown, _)| shown)
+        .fold(columns, |columns, &(_, added)| columns::insert_before_name(&columns, added));
+    let decor = Decorations::new(&args);
+
+    // Uniform columns need every section's widths before anything is printed
+    let mut prepared = if args.long && args.uniform_columns {
+        collect_uniform_sections(&args, Some(&columns), &decor, &output)
     } else {
-        // Multiple paths, show headers for each
-        for (i, path) in args.paths.iter().enumerate() {
-            if i > 0 {
-                println!(); // Add blank line between multiple path outputs
+        Vec::new()
+    }
+    .into_iter();
+    let listed = list_paths(&args, &mut prepared, Some(&columns), &decor, &output);
+    finish(listed.and_then(|status| output.finish().map(|()| status)));
+}
+
+/// How a run went, as reported by the exit status
+///
+/// Like GNU ls, problems that still let the listing go on, such as a subdirectory or
+/// entry that could not be read, are minor; a command line path that could not be
+/// accessed is serious.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum ExitStatus {
+    Success = 0,
+    Minor = 1,
+    Serious = 2,
+}
+
+impl ExitStatus {
+    /// Records a problem, keeping the status of the most serious one met so far
+    fn raise(&mut self, problem: ExitStatus) {
+        *self = (*self).max(problem);
+    }
+}
+
+/// Exits with a listing's status, or reports the error that stopped it
+///
+/// A reader that closed the pipe early, as `ls_oxide | head` does, ends the listing
+/// quietly with status 0; any other error exits with status 2.
+///
+/// # Arguments
+///
+/// * `result` - The exit status, or the error the listing stopped with
+fn finish(result: io::Result<ExitStatus>) {
+    match result {
+        Ok(ExitStatus::Success) => {}
+        Ok(status) => std::process::exit(status as i32),
+        Err(err) if err.kind() == io::ErrorKind::BrokenPipe => {}
+        Err(err) => {
+            eprintln!("ls_oxide: {}", err);
+            std::process::exit(2);
+        }
+    }
+}
+
+/// Writes the `--capabilities` report, as a section of the working directory
+///
+/// # Arguments
+///
+/// * `output` - Where the report is written
+fn write_capabilities(output: &Output) -> io::Result<()> {
+    let here = Path::new(".");
+    let mut out = output.section(here, here)?;
+    writeln!(out, "{}", capabilities::report())?;
+    out.finish()
+}
+
+/// Prints every listed entry as a JSON object per line, descending into
+/// subdirectories with `-R`
+///
+/// # Arguments
+///
+/// * `args` - Command line arguments selecting paths, filtering, sorting and the theme
+/// * `output` - Where each directory's lines are written
+///
+/// # Returns
+///
+/// The exit status, as for the other listings, or an error if the output could not be written
+fn list_json(args: &Args, output: &Output) -> io::Result<ExitStatus> {
+    let theme = load_theme(args);
+    let mut options = args.list_options().classify(false);
+    // Each directory still to list, the operand it was reached from, whether it is that
+    // operand, and the operand's device
+    let mut pending: Vec<(PathBuf, &Path, bool, Option<u64>)> = args.paths.iter().rev().map(|path| (path.clone(), path.as_path(), true, None)).collect();
+    let mut visited = Visited::default();
+    let mut status = ExitStatus::Success;
+    while let Some((path, root, operand, device)) = pending.pop() {
+        if operand {
+            // Operands may name archives, whose subdirectories are then read from the same archive
+            let operand = open_operand(&path).and_then(|file_system| {
+                options.file_system = file_system;
+                dir_utils::operand(&path, &options, args.operand_links())
+            });
+            match operand {
+                Ok(Operand::Directory) => {}
+                Ok(Operand::File(entry)) => {
+                    let mut out = output.section(root, &path)?;
+                    let path = args.path_style().map_or_else(|| entry.path.clone(), |style| style.render(&entry.path));
+                    writeln!(out, "{}", json::entry(&entry, &path, &theme))?;
+                    out.finish()?;
+                    continue;
+                }
+                Err(err) => {
+                    report_unreadable(&err, args);
+                    status.raise(ExitStatus::Serious);
+                    continue;
+                }
             }
-            if args.paths.len() > 1 {
-                println!("{}:", path);
+        }
+        let metadata = options.file_system.metadata(&path).ok();
+        // Under -L a symlink can lead back to a directory that was already listed
+        if args.recursive && metadata.as_ref().is_some_and(|metadata| !visited.first_visit(metadata)) {
+            report_cycle(&path, args)?;
+            continue;
+        }
+        let device = device.or_else(|| metadata.filter(|_| args.one_file_system).map(|metadata| metadata.dev));
+        let entries = match dir_utils::list_entries(&path, &options) {
+            Ok(entries) => entries,
+            Err(err) => {
+                // Operands are reported like the other listings do; subdirectories are a lesser failure
+                report_unreadable(&err, args);
+                status.raise(if operand { ExitStatus::Serious } else { ExitStatus::Minor });
+                continue;
             }
-            list_directory(path, &args);
+        };
+        status.raise(report_entry_errors(options.errors.take(), args));
+        let mut out = output.section(root, &path)?;
+        for entry in &entries {
+            let path = args.path_style().map_or_else(|| entry.path.clone(), |style| style.render(&entry.path));
+            writeln!(out, "{}", json::entry(entry, &path, &theme))?;
+        }
+        out.finish()?;
+        if args.recursive {
+            let subdirs = entries
+                .iter()
+                .filter(|entry| entry.kind == FileKind::Directory && !entry.is_dot())
+                .filter(|entry| device.is_none_or(|device| entry.metadata.dev == device));
+            pending.extend(subdirs.rev().map(|entry| (entry.path.clone(), root, false, device)));
         }
     }
+    Ok(status)
+}
+
+/// Chooses where the listing is written, refusing destinations inside a listed directory
+///
+/// # Arguments
+///
+/// * `args` - Command line arguments naming the output file or split output directory
+///
+/// # Returns
+///
+/// The output, or an error if the destination would appear in its own listing,
+/// writing was ruled out by `--assert-readonly`, or it cannot be created
+fn open_output(args: &Args) -> io::Result<Output> {
+    let destination = args.output_file.as_deref().or(args.split_output.as_deref());
+    if let Some(destination) = destination.filter(|_| readonly::is_enabled()) {
+        return Err(io::Error::other(format!("refusing to write '{}' under --assert-readonly", destination.display())));
+    }
+    if let Some(destination) = destination.filter(|_| !args.allow_self_write) {
+        if let Some(operand) = output::enclosing_operand(destination, &args.paths) {
+            return Err(io::Error::other(format!(
+                "refusing to write '{}' inside listed directory '{}' (use --allow-self-write to allow it)",
+                destination.display(),
+                operand.display()
+            )));
+        }
+    }
+    match (&args.output_file, &args.split_output) {
+        (Some(path), _) => Output::file(path.clone()),
+        // JSON sections are named for what they hold, so tools can pick them up by extension
+        (None, Some(root)) => Ok(Output::split(root.clone(), if args.json { "json" } else { "txt" })),
+        (None, None) => Ok(Output::stdout()),
+    }
+}
+
+/// Lists every path given on the command line, with headers when there are several
+///
+/// Like GNU ls, the paths that are not directories are listed first, together in one
+/// section, and each directory follows in its own. A path that cannot be accessed is
+/// reported before anything is listed, one that cannot be read where its section would
+/// have appeared, and the remaining paths are still listed.
+///
+/// # Arguments
+///
+/// * `args` - Command line arguments controlling listing format options
+/// * `prepared` - The paths already opened and what was gathered for them, in order
+/// * `columns` - Long format columns to show instead of the default set
+/// * `decor` - Colors and icons to apply
+/// * `output` - Where the listing's sections are written
+///
+/// # Returns
+///
+/// How the listing went, or an error if it could not be written
+fn list_paths(args: &Args, prepared: &mut impl Iterator<Item = (Opened, Prepared)>, columns: Option<&[Column]>, decor: &Decorations, output: &Output) -> io::Result<ExitStatus> {
+    let mut status = ExitStatus::Success;
+    let mut files = Vec::new();
+    let mut dirs = Vec::new();
+    let mut widths = None;
+    for path in &args.paths {
+        let (file_system, prepared) = match prepared.next() {
+            Some((file_system, prepared)) => (file_system, Some(prepared)),
+            None => (open_operand(path), None),
+        };
+        widths = widths.or(prepared.as_ref().map(|prepared| prepared.widths));
+        let operand = file_system.and_then(|file_system| Ok((classify_operand(path, &file_system, args)?, file_system)));
+        match operand {
+            Ok((Operand::File(entry), file_system)) => files.push((*entry, file_system)),
+            Ok((Operand::Directory, file_system)) => dirs.push((path, file_system, prepared)),
+            Err(err) => {
+                report_unreadable(&err, args);
+                status.raise(ExitStatus::Serious);
+            }
+        }
+    }
+
+    let mut listed = 0;
+    if !files.is_empty() {
+        list_operand_files(files, args, widths, columns, decor, output)?;
+        listed += 1;
+    }
+    for (path, file_system, prepared) in dirs {
+        if let Err(err) = file_system.read_dir(path) {
+            report_unreadable(&LsError::new(path, err), args);
+            status.raise(ExitStatus::Serious);
+            continue;
+        }
+        // Show headers when there are several paths (split sections are named after their path instead)
+        if output.has_headers() && args.paths.len() > 1 {
+            let mut out = output.section(path, path)?;
+            if listed > 0 {
+                writeln!(out)?; // Add blank line between multiple path outputs
+            }
+            writeln!(out, "{}:", name_text(path.as_os_str(), args))?;
+            out.finish()?;
+        }
+        status.raise(list_directory(path, &file_system, args, prepared, columns, decor, output)?);
+        listed += 1;
+    }
+    Ok(status)
+}
+
+/// Lists the command line paths that are not directories in one section, named after
+/// the first of them, ordered as a directory's entries would be
+///
+/// # Arguments
+///
+/// * `files` - Each path's entry, with the file system it was read from
+/// * `args` - Command line arguments controlling listing format options
+/// * `widths` - The owner and group widths to pad to under `--uniform-columns`
+/// * `columns` - Long format columns to show instead of the default set
+/// * `decor` - Colors and icons to apply
+/// * `output` - Where the listing's sections are written
+fn list_operand_files(files: Vec<(Entry, Arc<dyn FileSystem>)>, args: &Args, widths: Option<ColumnWidths>, columns: Option<&[Column]>, decor: &Decorations, output: &Output) -> io::Result<()> {
+    let section = files[0].0.path.clone();
+    let mut out = output.section(&section, &section)?;
+    if args.long {
+        // Each path's columns are filled in from its own file system
+        let mut details: Vec<FileInfo> = files
+            .iter()
+            .map(|(entry, file_system)| {
+                let mut details = operand_details(entry, file_system, args);
+                fill_columns(slice::from_mut(&mut details), &**file_system, args, columns);
+                details
+            })
+            .collect();
+        dir_utils::sort_entries(&mut details, &args.sort_spec());
+        if let Some(widths) = &widths {
+            widths.apply(&mut details);
+        }
+        write_table(&mut out, details, args, columns, decor)?;
+    } else {
+        let mut entries: Vec<Entry> = files.into_iter().map(|(entry, _)| entry).collect();
+        dir_utils::sort_entries(&mut entries, &args.sort_spec());
+        write_names(&mut out, &entries, args, decor)?;
+    }
+    out.finish()
+}
+
+/// Gathers the long format entries of one section, escaped under `--ascii`
+fn long_files(entries: &[Entry], options: &ListOptions, args: &Args) -> Vec<FileInfo> {
+    let mut files = dir_utils::detailed_entries(entries, options);
+    if args.ascii {
+        files.iter_mut().for_each(ascii::escape_file_info);
+    }
+    files
+}
+
+/// Gathers the long format entry of a command line path that is not a directory,
+/// escaped under `--ascii`
+fn operand_details(entry: &Entry, file_system: &Arc<dyn FileSystem>, args: &Args) -> FileInfo {
+    let options = ListOptions { file_system: Arc::clone(file_system), ..args.list_options() };
+    long_files(slice::from_ref(entry), &options, args).remove(0)
 }
@@ -0,0 +1,321 @@
+use std::{
+    io::{BufReader, Read, Seek, SeekFrom},
+    path::Path,
+    time::{Duration, SystemTime},
+};
+
+use chrono::{FixedOffset, Local, NaiveDate, TimeZone};
+use exif::{In, Tag, Value};
+use rayon::iter::{IntoParallelRefMutIterator, ParallelIterator};
+
+use crate::{
+    dir_utils::{FileInfo, FileKind},
+    file_system::FileSystem,
+};
+
+/// Fills in the `media` column for every regular file, reading files in parallel:
+/// image dimensions like `1920x1080`, or the running time of audio and video like
+/// `3:25`; anything else is left blank
+///
+/// # Arguments
+///
+/// * `files` - The long format entries to update
+/// * `file_system` - Where the files are read from
+pub fn apply(files: &mut [FileInfo], file_system: &dyn FileSystem) {
+    files
+        .par_iter_mut()
+        .filter(|file| matches!(file.kind, FileKind::File | FileKind::Executable))
+        .for_each(|file| {
+            file.media = match dimensions(&file.path, file_system) {
+                Some((width, height)) => Some(format!("{}x{}", width, height)),
+                None => duration(&file.path, file_system).map(format_duration),
+            };
+        });
+}
+
+/// Reads an image's width and height from its header, without decoding it
+///
+/// # Arguments
+///
+/// * `path` - The file to look at
+/// * `file_system` - Where the file is read from
+///
+/// # Returns
+///
+/// The size in pixels, or None if the file is not an image this can read
+pub fn dimensions(path: &Path, file_system: &dyn FileSystem) -> Option<(usize, usize)> {
+    let size = imagesize::reader_size(BufReader::new(file_system.open(path).ok()?)).ok()?;
+    Some((size.width, size.height))
+}
+
+/// Reads the running time of a WAV, FLAC, MP4, M4A or MOV file from its headers
+///
+/// # Arguments
+///
+/// * `path` - The file to look at
+/// * `file_system` - Where the file is read from
+///
+/// # Returns
+///
+/// The duration, or None for other formats and files that cannot be read
+pub fn duration(path: &Path, file_system: &dyn FileSystem) -> Option<Duration> {
+    let mut file = BufReader::new(file_system.open(path).ok()?);
+    let mut magic = [0; 12];
+    file.read_exact(&mut magic).ok()?;
+    file.rewind().ok()?;
+    match &magic {
+        [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'A', b'V', b'E'] => wav_duration(&mut file),
+        [b'f', b'L', b'a', b'C', ..] => flac_duration(&mut file),
+        [_, _, _, _, b'f', b't', b'y', b'p', ..] => mp4_duration(&mut file),
+        _ => None,
+    }
+}
+
+/// Divides a WAV file's data size by its byte rate
+fn wav_duration(file: &mut (impl Read + Seek)) -> Option<Duration> {
+    file.seek(SeekFrom::Start(12)).ok()?;
+    let mut byte_rate = None;
+    loop {
+        let (id, size) = chunk_header(file, false)?;
+        match &id {
+            b"fmt " => {
+                let mut format = [0; 12];
+                file.read_exact(&mut format).ok()?;
+                byte_rate = Some(u32::from_le_bytes(format[8..12].try_into().ok()?));
+                file.seek(SeekFrom::Current(size as i64 - 12)).ok()?;
+            }
+            b"data" => {
+                let byte_rate = byte_rate.filter(|&rate| rate > 0)?;
+                return Some(Duration::from_secs_f64(size as f64 / f64::from(byte_rate)));
+            }
+            // Chunks are padded to an even length
+            _ => {
+                file.seek(SeekFrom::Current((size + size % 2) as i64)).ok()?;
+            }
+        }
+    }
+}
+
+/// Divides a FLAC stream's sample count, from its STREAMINFO block, by its sample rate
+fn flac_duration(file: &mut impl Read) -> Option<Duration> {
+    let mut header = [0; 4 + 4 + 18];
+    file.read_exact(&mut header).ok()?;
+    let info = &header[8..];
+    let sample_rate = (u32::from(info[10]) << 12) | (u32::from(info[11]) << 4) | (u32::from(info[12]) >> 4);
+    let samples = (u64::from(info[13] & 0x0f) << 32) | u64::from(u32::from_be_bytes(info[14..18].try_into().ok()?));
+    (sample_rate > 0 && samples > 0).then(|| Duration::from_secs_f64(samples as f64 / f64::from(sample_rate)))
+}
+
+/// Reads the movie header box inside an MP4 family file's `moov` box
+fn mp4_duration(file: &mut (impl Read + Seek)) -> Option<Duration> {
+    let mut end = u64::MAX;
+    loop {
+        let start = file.stream_position().ok()?;
+        if start >= end {
+            return None;
+        }
+        let (kind, size) = chunk_header(file, true)?;
+        let body = file.stream_position().ok()?;
+        match &kind {
+            // Descend into the movie box, whose header holds the duration
+            b"moov" => end = body + size,
+            b"mvhd" => {
+                let mut version = [0; 4];
+                file.read_exact(&mut version).ok()?;
+                let (timescale, duration) = if version[0] == 1 {
+                    let mut fields = [0; 28];
+                    file.read_exact(&mut fields).ok()?;
+                    (u32::from_be_bytes(fields[16..20].try_into().ok()?), u64::from_be_bytes(fields[20..28].try_into().ok()?))
+                } else {
+                    let mut fields = [0; 16];
+                    file.read_exact(&mut fields).ok()?;
+                    (u32::from_be_bytes(fields[8..12].try_into().ok()?), u64::from(u32::from_be_bytes(fields[12..16].try_into().ok()?)))
+                };
+                return (timescale > 0).then(|| Duration::from_secs_f64(duration as f64 / f64::from(timescale)));
+            }
+            _ => {
+                file.seek(SeekFrom::Start(body + size)).ok()?;
+            }
+        }
+    }
+}
+
+/// Reads a chunk or box header: a four byte tag and the size of what follows it,
+/// in RIFF (tag first, little-endian) or MP4 (size first, big-endian) order
+fn chunk_header(file: &mut impl Read, mp4: bool) -> Option<([u8; 4], u64)> {
+    let mut header = [0; 8];
+    file.read_exact(&mut header).ok()?;
+    if !mp4 {
+        return Some((header[..4].try_into().ok()?, u64::from(u32::from_le_bytes(header[4..].try_into().ok()?))));
+    }
+    let kind: [u8; 4] = header[4..].try_into().ok()?;
+    match u32::from_be_bytes(header[..4].try_into().ok()?) {
+        // A 64-bit size follows the tag, and counts both headers
+        1 => {
+            let mut large = [0; 8];
+            file.read_exact(&mut large).ok()?;
+            Some((kind, u64::from_be_bytes(large).checked_sub(16)?))
+        }
+        size => Some((kind, u64::from(size).checked_sub(8)?)),
+    }
+}
+
+/// Formats a running time like `3:25`, or `1:02:03` past an hour
+pub fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs_f64().round() as u64;
+    match seconds / 3600 {
+        0 => format!("{}:{:02}", seconds / 60, seconds % 60),
+        hours => format!("{}:{:02}:{:02}", hours, seconds / 60 % 60, seconds % 60),
+    }
+}
+
+/// When a photo was taken, from its EXIF `DateTimeOriginal`, for `--sort=captured`
+///
+/// The digitized and modified times stand in when the original time is missing. The time is read in its recorded UTC offset if there is one, and otherwise as
+/// local time.
+///
+/// # Arguments
+///
+/// * `path` - The file to look at
+/// * `file_system` - Where the file is read from
+///
+/// # Returns
+///
+/// The capture time, or None if the file has no EXIF capture date
+pub fn capture_time(path: &Path, file_system: &dyn FileSystem) -> Option<SystemTime> {
+    if !file_system.metadata(path).ok()?.is_file() {
+        return None;
+    }
+    let mut reader = BufReader::new(file_system.open(path).ok()?);
+    let exif = exif::Reader::new().read_from_container(&mut reader).ok()?;
+    let ascii = |tag| match &exif.get_field(tag, In::PRIMARY)?.value {
+        Value::Ascii(values) => values.first().cloned(),
+        _ => None,
+    };
+    // Cameras that omit the original time usually still record when the image was written
+    let (mut taken, offset) = [
+        (Tag::DateTimeOriginal, Tag::OffsetTimeOriginal),
+        (Tag::DateTimeDigitized, Tag::OffsetTimeDigitized),
+        (Tag::DateTime, Tag::OffsetTime),
+    ]
+    .into_iter()
+    .find_map(|(time, offset)| Some((exif::DateTime::from_ascii(&ascii(time)?).ok()?, offset)))?;
+    if let Some(offset) = ascii(offset) {
+        let _ = taken.parse_offset(&offset);
+    }
+
+    let naive = NaiveDate::from_ymd_opt(taken.year.into(), taken.month.into(), taken.day.into())?.and_hms_opt(
+        taken.hour.into(),
+        taken.minute.into(),
+        taken.second.into(),
+    )?;
+    let time = match taken.offset {
+        Some(minutes) => FixedOffset::east_opt(i32::from(minutes) * 60)?.from_local_datetime(&naive).single()?.into(),
+        None => Local.from_local_datetime(&naive).earliest()?.into(),
+    };
+    Some(time)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        dir_utils::{list_entries, list_files_detailed, ListOptions, SortKey, SortSpec},
+        file_system::StdFileSystem,
+    };
+    use std::fs;
+    use tempfile::tempdir;
+
+    /// A 3x2 PNG header, enough for the dimensions to be read
+    const PNG: &[u8] = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR\0\0\0\x03\0\0\0\x02\x08\x02\0\0\0";
+
+    #[test]
+    fn test_dimensions() {
+        let temp_dir = tempdir().unwrap();
+        fs::write(temp_dir.path().join("a.png"), PNG).unwrap();
+        fs::write(temp_dir.path().join("a.txt"), "not an image").unwrap();
+        assert_eq!(dimensions(&temp_dir.path().join("a.png"), &StdFileSystem), Some((3, 2)));
+        assert_eq!(dimensions(&temp_dir.path().join("a.txt"), &StdFileSystem), None);
+
+        let mut files = list_files_detailed(temp_dir.path(), &ListOptions::default()).unwrap();
+        apply(&mut files, &StdFileSystem);
+        assert_eq!(files[0].media.as_deref(), Some("3x2"));
+        assert_eq!(files[1].media, None);
+    }
+
+    #[test]
+    fn test_wav_duration() {
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().join("a.wav");
+        // Mono 8kHz 8-bit, so 8000 bytes a second, with 2.5 seconds of data
+        let mut wav = b"RIFF\0\0\0\0WAVE".to_vec();
+        wav.extend(b"fmt \x10\0\0\0\x01\0\x01\0\x40\x1f\0\0\x40\x1f\0\0\x01\0\x08\0");
+        wav.extend(b"LIST\x03\0\0\0abc\0");
+        wav.extend(b"data");
+        wav.extend(20_000u32.to_le_bytes());
+        fs::write(&path, wav).unwrap();
+        assert_eq!(duration(&path, &StdFileSystem), Some(Duration::from_millis(2500)));
+    }
+
+    #[test]
+    fn test_mp4_duration() {
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().join("a.mp4");
+        let mut mp4 = b"\0\0\0\x10ftypisom\0\0\0\0".to_vec();
+        mp4.extend(b"\0\0\0\x10free\0\0\0\0\0\0\0\0");
+        mp4.extend(b"\0\0\0\x24moov\0\0\0\x1cmvhd\0\0\0\0\0\0\0\0\0\0\0\0");
+        mp4.extend(1000u32.to_be_bytes());
+        mp4.extend(90_500u32.to_be_bytes());
+        fs::write(&path, mp4).unwrap();
+        assert_eq!(duration(&path, &StdFileSystem).map(format_duration), Some("1:31".to_string()));
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(Duration::from_secs(5)), "0:05");
+        assert_eq!(format_duration(Duration::from_secs(205)), "3:25");
+        assert_eq!(format_duration(Duration::from_secs(3723)), "1:02:03");
+    }
+
+    /// A JPEG holding only an EXIF block, whose Exif IFD has the original time and offset
+    fn exif_jpeg() -> Vec<u8> {
+        let mut tiff = b"II*\0\x08\0\0\0".to_vec();
+        tiff.extend(b"\x01\0\x69\x87\x04\0\x01\0\0\0\x1a\0\0\0\0\0\0\0");
+        tiff.extend(b"\x02\0\x03\x90\x02\0\x14\0\0\0\x38\0\0\0\x11\x90\x02\0\x07\0\0\0\x4c\0\0\0\0\0\0\0");
+        tiff.extend(b"2016:05:04 03:02:01\0+02:00\0");
+        let mut jpeg = b"\xff\xd8\xff\xe1".to_vec();
+        jpeg.extend((tiff.len() as u16 + 8).to_be_bytes());
+        jpeg.extend(b"Exif\0\0");
+        jpeg.extend(tiff);
+        jpeg.extend(b"\xff\xd9");
+        jpeg
+    }
+
+    #[test]
+    fn test_capture_time() {
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().join("a.jpg");
+        fs::write(&path, exif_jpeg()).unwrap();
+        assert_eq!(capture_time(&path, &StdFileSystem), Some(SystemTime::UNIX_EPOCH + Duration::from_secs(1_462_323_721)));
+    }
+
+    #[test]
+    fn test_capture_time_without_exif() {
+        let temp_dir = tempdir().unwrap();
+        fs::write(temp_dir.path().join("a.png"), PNG).unwrap();
+        assert_eq!(capture_time(&temp_dir.path().join("a.png"), &StdFileSystem), None);
+        assert_eq!(capture_time(temp_dir.path(), &StdFileSystem), None);
+    }
+
+    #[test]
+    fn test_sort_by_capture_time() {
+        let temp_dir = tempdir().unwrap();
+        fs::write(temp_dir.path().join("a.png"), PNG).unwrap();
+        fs::write(temp_dir.path().join("b.jpg"), exif_jpeg()).unwrap();
+        let options = ListOptions::default().sort(SortSpec::by(vec![SortKey::Captured]));
+        let files = list_files_detailed(temp_dir.path(), &options).unwrap();
+        assert_eq!(files.iter().map(|file| file.name.as_str()).collect::<Vec<_>>(), ["b.jpg", "a.png"]);
+        let entries = list_entries(temp_dir.path(), &options).unwrap();
+        assert_eq!(entries.iter().map(|entry| entry.name.as_str()).collect::<Vec<_>>(), ["b.jpg", "a.png"]);
+    }
+}
@@ -0,0 +1,148 @@
+use std::{fs, path::Path, sync::OnceLock};
+
+/// A mounted file system, as listed by the kernel
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mount {
+    /// The device number entries on it report as `st_dev`
+    pub device: u64,
+    /// Where it is mounted
+    pub mount_point: String,
+    /// The file system type, such as `ext4`, `tmpfs` or `nfs4`
+    pub fs_type: String,
+}
+
+/// The mount table, read once on first use
+static MOUNTS: OnceLock<Vec<Mount>> = OnceLock::new();
+
+/// Describes the file system an entry lives on, like `/tmp (tmpfs)`
+///
+/// # Arguments
+///
+/// * `path` - The entry's path, used to pick between bind mounts of the same device
+/// * `device` - The entry's `st_dev`
+///
+/// # Returns
+///
+/// The mount point and type, or an empty string if the mount is not known
+pub fn describe(path: &Path, device: u64) -> String {
+    let mounts = MOUNTS.get_or_init(|| fs::read_to_string("/proc/self/mountinfo").map(|text| parse(&text)).unwrap_or_default());
+    find(mounts, path, device)
+        .map(|mount| format!("{} ({})", mount.mount_point, mount.fs_type))
+        .unwrap_or_default()
+}
+
+/// Finds the mount an entry lives on, preferring the deepest mount point above it
+/// when a device is mounted in more than one place
+fn find<'a>(mounts: &'a [Mount], path: &Path, device: u64) -> Option<&'a Mount> {
+    let mut candidates = mounts.iter().filter(|mount| mount.device == device);
+    candidates
+        .clone()
+        .filter(|mount| path.starts_with(&mount.mount_point))
+        .max_by_key(|mount| mount.mount_point.len())
+        .or_else(|| candidates.next())
+}
+
+/// Parses `/proc/self/mountinfo`, which unlike `/proc/mounts` carries each mount's
+/// device number, so entries can be matched without stat-ing every mount point
+///
+/// # Arguments
+///
+/// * `text` - The file's contents
+///
+/// # Returns
+///
+/// The mounts in the order listed, skipping malformed lines
+pub fn parse(text: &str) -> Vec<Mount> {
+    text.lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split(' ').collect();
+            let (major, minor) = fields.get(2)?.split_once(':')?;
+            let mount_point = fields.get(4)?;
+            // Optional fields run up to a lone "-", followed by the type
+            let separator = fields.iter().position(|&field| field == "-")?;
+            let fs_type = fields.get(separator + 1)?;
+            Some(Mount {
+                device: makedev(major.parse().ok()?, minor.parse().ok()?),
+                mount_point: unescape(mount_point),
+                fs_type: fs_type.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Combines the major and minor numbers mountinfo lists into a device number
+#[cfg(target_os = "linux")]
+fn makedev(major: u64, minor: u64) -> u64 {
+    nix::sys::stat::makedev(major, minor)
+}
+
+/// Only Linux has mountinfo, so elsewhere the numbers merely need to stay apart
+#[cfg(not(target_os = "linux"))]
+fn makedev(major: u64, minor: u64) -> u64 {
+    major << 32 | minor
+}
+
+/// Decodes the `\040`-style octal escapes the kernel writes for spaces and other
+/// special characters in mount points
+fn unescape(field: &str) -> String {
+    let bytes = field.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let octal = bytes.get(i + 1..i + 4).and_then(|digits| u8::from_str_radix(std::str::from_utf8(digits).ok()?, 8).ok());
+        match octal {
+            Some(byte) if bytes[i] == b'\\' => {
+                decoded.push(byte);
+                i += 4;
+            }
+            _ => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::MetadataExt;
+
+    const MOUNTINFO: &str = "\
+22 1 8:1 / / rw,relatime shared:1 - ext4 /dev/sda1 rw
+23 22 0:24 / /tmp rw,nosuid - tmpfs tmpfs rw,size=1024k
+24 22 0:50 / /mnt/my\\040share rw shared:5 master:2 - nfs4 server:/export rw
+25 22 8:1 /srv /data rw - ext4 /dev/sda1 rw
+broken line
+";
+
+    #[test]
+    fn test_parse() {
+        let mounts = parse(MOUNTINFO);
+        assert_eq!(mounts.len(), 4);
+        assert_eq!(mounts[1].mount_point, "/tmp");
+        assert_eq!(mounts[1].fs_type, "tmpfs");
+        assert_eq!(mounts[2].mount_point, "/mnt/my share");
+        assert_eq!(mounts[2].fs_type, "nfs4");
+        assert_eq!(mounts[2].device, nix::sys::stat::makedev(0, 50));
+    }
+
+    #[test]
+    fn test_find() {
+        let mounts = parse(MOUNTINFO);
+        let disk = nix::sys::stat::makedev(8, 1);
+        assert_eq!(find(&mounts, Path::new("/data/file"), disk).unwrap().mount_point, "/data");
+        assert_eq!(find(&mounts, Path::new("/home/file"), disk).unwrap().mount_point, "/");
+        assert_eq!(find(&mounts, Path::new("/tmp/file"), nix::sys::stat::makedev(0, 24)).unwrap().fs_type, "tmpfs");
+        assert!(find(&mounts, Path::new("/x"), nix::sys::stat::makedev(9, 9)).is_none());
+    }
+
+    #[test]
+    fn test_describe_root() {
+        let device = fs::metadata("/").unwrap().dev();
+        if Path::new("/proc/self/mountinfo").exists() {
+            assert!(describe(Path::new("/"), device).starts_with('/'));
+        }
+    }
+}
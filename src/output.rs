@@ -0,0 +1,375 @@
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    ffi::{OsStr, OsString},
+    fs,
+    io::{self, BufWriter, Write},
+    path::{Component, Path, PathBuf},
+    rc::Rc,
+};
+
+use tempfile::NamedTempFile;
+
+use crate::platform;
+
+/// Where each listed directory's section is written
+///
+/// Files are written to a temporary file next to their destination and renamed into
+/// place once complete, so readers never see a partially written listing. Standard
+/// output is locked once and buffered, and flushed as each section is finished.
+#[derive(Debug)]
+pub enum Output {
+    /// Every section goes to standard output
+    Stdout(Rc<RefCell<BufWriter<io::StdoutLock<'static>>>>),
+    /// Each section goes to its own `<relative-path>.<extension>` file under this directory
+    Split {
+        root: PathBuf,
+        /// The extension of each section file, such as `txt`
+        extension: &'static str,
+        /// The directory each section file was opened for, so two directories never share one
+        claimed: RefCell<HashMap<PathBuf, PathBuf>>,
+        /// Section files already renamed into place, which later writes append to
+        written: RefCell<HashSet<PathBuf>>,
+    },
+    /// The whole listing goes to one file, renamed into place by [`Output::finish`]
+    File { path: PathBuf, temp: Rc<RefCell<BufWriter<NamedTempFile>>> },
+}
+
+impl Output {
+    /// Writes every section to standard output
+    pub fn stdout() -> Self {
+        Output::Stdout(Rc::new(RefCell::new(BufWriter::new(io::stdout().lock()))))
+    }
+
+    /// Writes each section to its own file under `root`, named with `extension`
+    pub fn split(root: PathBuf, extension: &'static str) -> Self {
+        Output::Split { root, extension, claimed: RefCell::default(), written: RefCell::default() }
+    }
+
+    /// Writes the whole listing to `path`
+    ///
+    /// # Returns
+    ///
+    /// The output, or an error if the temporary file cannot be created next to `path`
+    pub fn file(path: PathBuf) -> io::Result<Self> {
+        let temp = temp_file_for(&path)?;
+        Ok(Output::File { path, temp: Rc::new(RefCell::new(BufWriter::new(temp))) })
+    }
+
+    /// Whether operands and directories are introduced by header lines; split
+    /// sections are identified by their file name instead
+    pub fn has_headers(&self) -> bool {
+        !matches!(self, Output::Split { .. })
+    }
+
+    /// Opens the destination for one directory's section
+    ///
+    /// # Arguments
+    ///
+    /// * `operand` - The path given on the command line that the listing started from
+    /// * `dir` - The directory whose section is about to be written
+    ///
+    /// # Returns
+    ///
+    /// A writer for the section, or an error if its file cannot be created or its name
+    /// would leave the split output directory or is already taken by another directory
+    pub fn section(&self, operand: &Path, dir: &Path) -> io::Result<Section<'_>> {
+        match self {
+            Output::Stdout(out) => Ok(Section::Stdout(Rc::clone(out))),
+            Output::Split { root, extension, claimed, written } => {
+                let path = root.join(section_file_name(operand, dir, extension)?);
+                let owner = claimed.borrow_mut().entry(path.clone()).or_insert_with(|| dir.to_path_buf()).clone();
+                if owner != dir {
+                    return Err(io::Error::other(format!(
+                        "sections of '{}' and '{}' would both be written to '{}'",
+                        owner.display(),
+                        dir.display(),
+                        path.display()
+                    )));
+                }
+                if let Some(parent) = path.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                let mut temp = BufWriter::new(temp_file_for(&path)?);
+                if written.borrow().contains(&path) {
+                    io::copy(&mut fs::File::open(&path)?, &mut temp)?;
+                }
+                Ok(Section::File { temp, path, written })
+            }
+            Output::File { temp, .. } => Ok(Section::Shared(Rc::clone(temp))),
+        }
+    }
+
+    /// Renames the single output file into place once the whole listing is written
+    ///
+    /// # Returns
+    ///
+    /// An error if the file could not be flushed or renamed
+    pub fn finish(self) -> io::Result<()> {
+        match self {
+            Output::File { path, temp } => {
+                let temp = Rc::try_unwrap(temp)
+                    .map_err(|_| io::Error::other("output file is still being written"))?
+                    .into_inner()
+                    .into_inner()
+                    .map_err(|err| err.into_error())?;
+                persist(temp, &path)
+            }
+            Output::Stdout(out) => out.borrow_mut().flush(),
+            Output::Split { .. } => Ok(()),
+        }
+    }
+}
+
+/// A writer for one directory's section, to be closed with [`Section::finish`]
+///
+/// A section file dropped without being finished is discarded, leaving any earlier
+/// version of the file untouched.
+pub enum Section<'a> {
+    Stdout(Rc<RefCell<BufWriter<io::StdoutLock<'static>>>>),
+    File {
+        temp: BufWriter<NamedTempFile>,
+        path: PathBuf,
+        written: &'a RefCell<HashSet<PathBuf>>,
+    },
+    Shared(Rc<RefCell<BufWriter<NamedTempFile>>>),
+}
+
+impl Section<'_> {
+    /// Completes the section, renaming a section file into place
+    ///
+    /// # Returns
+    ///
+    /// An error if the section could not be flushed or renamed
+    pub fn finish(self) -> io::Result<()> {
+        match self {
+            // Keeps the listing in step with messages printed to stderr between sections
+            Section::Stdout(out) => out.borrow_mut().flush(),
+            Section::File { temp, path, written } => {
+                persist(temp.into_inner().map_err(|err| err.into_error())?, &path)?;
+                written.borrow_mut().insert(path);
+                Ok(())
+            }
+            Section::Shared(temp) => temp.borrow_mut().flush(),
+        }
+    }
+}
+
+impl Write for Section<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Section::Stdout(out) => out.borrow_mut().write(buf),
+            Section::File { temp, .. } => temp.write(buf),
+            Section::Shared(temp) => temp.borrow_mut().write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Section::Stdout(out) => out.borrow_mut().flush(),
+            Section::File { temp, .. } => temp.flush(),
+            Section::Shared(temp) => temp.borrow_mut().flush(),
+        }
+    }
+}
+
+/// Creates a temporary file in the same directory as `path`, so it can be renamed over it
+fn temp_file_for(path: &Path) -> io::Result<NamedTempFile> {
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let mut builder = tempfile::Builder::new();
+    // Like a plainly created file, leaving the rest to the umask
+    #[cfg(unix)]
+    builder.permissions(std::os::unix::fs::PermissionsExt::from_mode(0o666));
+    builder
+        .tempfile_in(dir)
+        .map_err(|err| io::Error::new(err.kind(), format!("cannot create '{}': {}", path.display(), err)))
+}
+
+/// Flushes a finished temporary file to disk and atomically renames it to `path`
+fn persist(temp: NamedTempFile, path: &Path) -> io::Result<()> {
+    temp.as_file().sync_all()?;
+    temp.persist(path)
+        .map(|_| ())
+        .map_err(|err| io::Error::new(err.error.kind(), format!("cannot write '{}': {}", path.display(), err.error)))
+}
+
+/// Finds the listed operand that a write destination lies inside of, where the
+/// written file would show up in (or change) its own listing
+///
+/// # Arguments
+///
+/// * `destination` - The output file or split output directory
+/// * `operands` - The paths being listed
+///
+/// # Returns
+///
+/// The first operand containing the destination, if any
+pub fn enclosing_operand<'a>(destination: &Path, operands: &'a [PathBuf]) -> Option<&'a Path> {
+    let destination = resolve(destination)?;
+    operands
+        .iter()
+        .find(|operand| fs::canonicalize(operand).is_ok_and(|operand| destination.starts_with(operand)))
+        .map(PathBuf::as_path)
+}
+
+/// Canonicalizes a path that may not exist yet by resolving its deepest existing ancestor
+fn resolve(path: &Path) -> Option<PathBuf> {
+    let path = std::path::absolute(path).ok()?;
+    let mut missing = Vec::new();
+    let mut existing = path.as_path();
+    loop {
+        if let Ok(resolved) = fs::canonicalize(existing) {
+            return Some(missing.iter().rev().fold(resolved, |path, name| path.join(name)));
+        }
+        missing.push(existing.file_name()?);
+        existing = existing.parent()?;
+    }
+}
+
+/// Builds the relative file name of a section: the operand's own name followed by
+/// the directory's path below it, each part escaped, with `.<extension>` appended
+///
+/// # Returns
+///
+/// The file name, or an error if the directory does not lie below the operand
+fn section_file_name(operand: &Path, dir: &Path, extension: &str) -> io::Result<PathBuf> {
+    // Name "." and ".." after the directory they stand for
+    let base = fs::canonicalize(operand)
+        .ok()
+        .and_then(|path| path.file_name().map(|name| name.to_os_string()))
+        .unwrap_or_else(|| OsString::from("root"));
+
+    let outside = || io::Error::other(format!("cannot name a section for '{}', which is not inside '{}'", dir.display(), operand.display()));
+    let mut name = PathBuf::from(escape_component(&base, extension));
+    for component in dir.strip_prefix(operand).map_err(|_| outside())?.components() {
+        match component {
+            Component::Normal(part) => name.push(escape_component(part, extension)),
+            Component::CurDir => {}
+            _ => return Err(outside()),
+        }
+    }
+    let mut name = name.into_os_string();
+    name.push(".");
+    name.push(extension);
+    Ok(PathBuf::from(name))
+}
+
+/// Escapes a path component so each directory maps to its own section file: `%`
+/// becomes `%25`, and the dot of a trailing `.<extension>` becomes `%2E`, so a
+/// directory named `sub.txt` cannot take the place of the section file of its sibling `sub`
+fn escape_component(name: &OsStr, extension: &str) -> OsString {
+    let mut escaped = Vec::with_capacity(name.len());
+    for &byte in name.as_encoded_bytes() {
+        if byte == b'%' {
+            escaped.extend_from_slice(b"%25");
+        } else {
+            escaped.push(byte);
+        }
+    }
+    let suffix = format!(".{}", extension);
+    if escaped.ends_with(suffix.as_bytes()) {
+        let dot = escaped.len() - suffix.len();
+        escaped.splice(dot..=dot, *b"%2E");
+    }
+    platform::os_string_from_bytes(escaped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_split_sections() {
+        let temp_dir = tempdir().expect("Unable to create temporary directory");
+        let tree = temp_dir.path().join("tree");
+        fs::create_dir_all(tree.join("sub")).expect("Unable to create tree/sub");
+        let out_dir = temp_dir.path().join("out");
+        let output = Output::split(out_dir.clone(), "txt");
+        let operand = tree.as_path();
+
+        let mut top = output.section(operand, &tree).unwrap();
+        writeln!(top, "top").unwrap();
+        // Nothing is visible until the section is finished
+        assert!(!out_dir.join("tree.txt").exists());
+        top.finish().unwrap();
+        let mut nested = output.section(operand, &tree.join("sub")).unwrap();
+        writeln!(nested, "nested").unwrap();
+        nested.finish().unwrap();
+        let mut more = output.section(operand, &tree).unwrap();
+        writeln!(more, "more").unwrap();
+        more.finish().unwrap();
+
+        assert_eq!(fs::read_to_string(out_dir.join("tree.txt")).unwrap(), "top\nmore\n");
+        assert_eq!(fs::read_to_string(out_dir.join("tree/sub.txt")).unwrap(), "nested\n");
+        assert_eq!(fs::read_dir(out_dir.join("tree")).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_split_section_names_are_unambiguous() {
+        let temp_dir = tempdir().expect("Unable to create temporary directory");
+        let tree = temp_dir.path().join("tree");
+        for dir in ["sub", "sub.txt", "100%"] {
+            fs::create_dir_all(tree.join(dir)).expect("Unable to create directory");
+        }
+        let out_dir = temp_dir.path().join("out");
+        let output = Output::split(out_dir.clone(), "txt");
+
+        for dir in ["sub", "sub.txt", "100%"] {
+            let mut section = output.section(&tree, &tree.join(dir)).unwrap();
+            writeln!(section, "{}", dir).unwrap();
+            section.finish().unwrap();
+        }
+        assert_eq!(fs::read_to_string(out_dir.join("tree/sub.txt")).unwrap(), "sub\n");
+        assert_eq!(fs::read_to_string(out_dir.join("tree/sub%2Etxt.txt")).unwrap(), "sub.txt\n");
+        assert_eq!(fs::read_to_string(out_dir.join("tree/100%25.txt")).unwrap(), "100%\n");
+
+        // Operands sharing a name, and directories outside the operand, are refused
+        let other = temp_dir.path().join("other/tree");
+        fs::create_dir_all(&other).expect("Unable to create other/tree");
+        output.section(&tree, &tree).unwrap().finish().unwrap();
+        assert!(output.section(&other, &other).is_err());
+        assert!(output.section(&tree, temp_dir.path()).is_err());
+        assert!(output.section(&tree, &tree.join("../escape")).is_err());
+
+        // Sections named with another extension escape it instead
+        let json_dir = temp_dir.path().join("json");
+        let output = Output::split(json_dir.clone(), "json");
+        for dir in ["sub", "sub.json"] {
+            output.section(&tree, &tree.join(dir)).unwrap().finish().unwrap();
+        }
+        assert!(json_dir.join("tree/sub.json").exists());
+        assert!(json_dir.join("tree/sub%2Ejson.json").exists());
+    }
+
+    #[test]
+    fn test_output_file_is_renamed_into_place() {
+        let temp_dir = tempdir().expect("Unable to create temporary directory");
+        let path = temp_dir.path().join("listing.txt");
+        fs::write(&path, "old\n").expect("Unable to write listing.txt");
+
+        let output = Output::file(path.clone()).unwrap();
+        let mut section = output.section(Path::new("."), Path::new(".")).unwrap();
+        writeln!(section, "new").unwrap();
+        section.finish().unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "old\n");
+
+        output.finish().unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new\n");
+        assert_eq!(fs::read_dir(temp_dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_enclosing_operand() {
+        let temp_dir = tempdir().expect("Unable to create temporary directory");
+        let tree = temp_dir.path().join("tree");
+        fs::create_dir(&tree).expect("Unable to create tree");
+        let operands = vec![tree.clone()];
+
+        assert_eq!(enclosing_operand(&tree.join("out/listing.txt"), &operands), Some(operands[0].as_path()));
+        assert_eq!(enclosing_operand(&temp_dir.path().join("listing.txt"), &operands), None);
+    }
+}
@@ -0,0 +1,115 @@
+use std::{
+    fs,
+    path::{Component, Path, PathBuf},
+    sync::Mutex,
+};
+
+/// How entries are shown when listed by path instead of by name
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PathStyle {
+    /// The absolute canonical path, as `--absolute` asks
+    Absolute,
+    /// The path relative to a canonical base directory, as `--relative-to` asks
+    RelativeTo(PathBuf),
+}
+
+impl PathStyle {
+    /// Rewrites an entry's path in this style
+    pub fn render(&self, path: &Path) -> PathBuf {
+        match self {
+            PathStyle::Absolute => absolute(path),
+            PathStyle::RelativeTo(base) => relative(&absolute(path), base),
+        }
+    }
+}
+
+/// The last directory canonicalized and what it resolved to, since the entries of a
+/// listing share their parent
+static LAST_DIR: Mutex<Option<(PathBuf, PathBuf)>> = Mutex::new(None);
+
+/// Finds an entry's absolute path with every directory above it resolved, leaving
+/// the entry itself as it is so symlinks are shown rather than followed
+///
+/// # Arguments
+///
+/// * `path` - The entry's path as listed
+///
+/// # Returns
+///
+/// The canonical path, or the path made absolute if it cannot be resolved
+pub fn absolute(path: &Path) -> PathBuf {
+    // `.` and `..` stand for directories, so they are resolved whole
+    let dot = matches!(path.as_os_str().as_encoded_bytes().rsplit(|&byte| std::path::is_separator(byte.into())).next(), Some(b".") | Some(b".."));
+    let resolved = match (dot, path.parent(), path.file_name()) {
+        (false, Some(parent), Some(name)) => canonical_dir(parent).map(|dir| dir.join(name)),
+        _ => fs::canonicalize(path).ok(),
+    };
+    resolved.or_else(|| std::path::absolute(path).ok()).unwrap_or_else(|| path.to_path_buf())
+}
+
+/// Canonicalizes a directory, reusing the last result
+fn canonical_dir(dir: &Path) -> Option<PathBuf> {
+    let dir = if dir.as_os_str().is_empty() { Path::new(".") } else { dir };
+    let mut last = LAST_DIR.lock().unwrap_or_else(|err| err.into_inner());
+    if let Some((_, resolved)) = last.as_ref().filter(|(path, _)| path == dir) {
+        return Some(resolved.clone());
+    }
+    let resolved = fs::canonicalize(dir).ok()?;
+    *last = Some((dir.to_path_buf(), resolved.clone()));
+    Some(resolved)
+}
+
+/// Expresses an absolute path relative to a base directory, climbing out of the base
+/// with `..` where the two part ways
+///
+/// # Arguments
+///
+/// * `path` - The absolute path to rewrite
+/// * `base` - The absolute base directory
+///
+/// # Returns
+///
+/// The relative path, or `.` for the base itself
+pub fn relative(path: &Path, base: &Path) -> PathBuf {
+    let path: Vec<Component> = path.components().collect();
+    let base: Vec<Component> = base.components().collect();
+    let common = path.iter().zip(&base).take_while(|(a, b)| a == b).count();
+    let relative: PathBuf = std::iter::repeat_n(Component::ParentDir, base.len() - common).chain(path[common..].iter().copied()).collect();
+    if relative.as_os_str().is_empty() {
+        PathBuf::from(".")
+    } else {
+        relative
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::symlink;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_relative() {
+        let base = Path::new("/home/user/project");
+        assert_eq!(relative(Path::new("/home/user/project/src/main.rs"), base), Path::new("src/main.rs"));
+        assert_eq!(relative(Path::new("/home/user/notes.txt"), base), Path::new("../notes.txt"));
+        assert_eq!(relative(Path::new("/etc/hosts"), base), Path::new("../../../etc/hosts"));
+        assert_eq!(relative(base, base), Path::new("."));
+    }
+
+    #[test]
+    fn test_absolute() {
+        let temp_dir = tempdir().unwrap();
+        let root = temp_dir.path().canonicalize().unwrap();
+        fs::create_dir(root.join("real")).unwrap();
+        symlink("real", root.join("alias")).unwrap();
+        fs::write(root.join("real").join("file"), "").unwrap();
+
+        // Directories above the entry are resolved, the entry itself is not
+        assert_eq!(absolute(&root.join("alias").join("file")), root.join("real").join("file"));
+        assert_eq!(absolute(&root.join("alias")), root.join("alias"));
+        assert_eq!(absolute(&root.join("real").join(".")), root.join("real"));
+        assert_eq!(absolute(&root.join("real").join("..")), root);
+        assert_eq!(PathStyle::RelativeTo(root.join("real")).render(&root.join("alias")), Path::new("../alias"));
+    }
+}
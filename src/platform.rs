@@ -0,0 +1,126 @@
+//! What differs between operating systems: how metadata is read and what it means,
+//! who owns entries, and how wide the terminal is
+//!
+//! Unix reports mode bits, link counts and numeric owners directly. Windows has
+//! attributes instead of mode bits, so read-only entries lose their write bits and
+//! programs are marked executable by extension; file ids and link counts come from
+//! an open handle, owners are security identifiers looked up by account name, and
+//! junctions are reparse points shown like symlinks.
+//!
+//! macOS adds BSD file flags and extended attributes such as the quarantine Gatekeeper
+//! checks and resource forks, which only the long format there can show.
+
+use std::{
+    ffi::OsString,
+    fs, io,
+    path::Path,
+};
+
+use crate::file_system::Metadata;
+
+#[cfg(unix)]
+mod unix;
+#[cfg(unix)]
+use unix as imp;
+#[cfg(windows)]
+mod windows;
+#[cfg(windows)]
+use windows as imp;
+#[cfg(target_os = "macos")]
+mod macos;
+
+/// Errors the local file system gives, for other sources of entries to report alike
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OsError {
+    NotFound,
+    NotADirectory,
+    InvalidArgument,
+    /// A chain of symlinks too long to resolve
+    TooManyLinks,
+}
+
+/// Describes an entry on the local file system, following it if it is a symlink
+pub fn metadata(path: &Path) -> io::Result<Metadata> {
+    imp::metadata(path)
+}
+
+/// Describes an entry on the local file system without following symlinks
+pub fn symlink_metadata(path: &Path) -> io::Result<Metadata> {
+    imp::symlink_metadata(path)
+}
+
+/// Converts what `std::fs` reports about an entry
+///
+/// On Windows this leaves out the file id, link count and owner, which [`metadata`]
+/// reads through a handle.
+pub fn from_std(metadata: &fs::Metadata) -> Metadata {
+    imp::from_std(metadata)
+}
+
+/// Looks up the name of the user with a uid, or on Windows of the account a uid stands for
+pub fn user_name(uid: u32) -> Option<String> {
+    imp::user_name(uid)
+}
+
+/// Looks up the name of the group with a gid, or on Windows of the account a gid stands for
+pub fn group_name(gid: u32) -> Option<String> {
+    imp::group_name(gid)
+}
+
+/// The uid of the user running the listing
+pub fn current_uid() -> u32 {
+    imp::current_uid()
+}
+
+/// The gid of the user running the listing
+pub fn current_gid() -> u32 {
+    imp::current_gid()
+}
+
+/// Asks the terminal stdout is connected to how many columns wide it is
+pub fn terminal_width() -> Option<usize> {
+    imp::terminal_width()
+}
+
+/// Turns bytes, such as a name read from an archive, into a path component, as exactly as the platform allows
+pub fn os_string_from_bytes(bytes: Vec<u8>) -> OsString {
+    imp::os_string_from_bytes(bytes)
+}
+
+/// Reads the BSD file flags set on an entry, without following symlinks
+///
+/// # Returns
+///
+/// The flags as `ls -lO` names them, like `uchg,hidden`, or `-` if none are set; None
+/// if the entry cannot be read
+#[cfg(target_os = "macos")]
+pub fn file_flags(path: &Path) -> Option<String> {
+    macos::file_flags(path)
+}
+
+/// Counts the extended attributes on an entry, resource forks included, without
+/// following symlinks
+///
+/// # Returns
+///
+/// The count, or None if they cannot be listed
+#[cfg(target_os = "macos")]
+pub fn xattr_count(path: &Path) -> Option<usize> {
+    macos::xattr_count(path)
+}
+
+/// Finds out what downloaded an entry macOS has quarantined
+///
+/// # Returns
+///
+/// The application named in the `com.apple.quarantine` attribute, such as `Safari`,
+/// or `yes` if it names none; None if the entry is not quarantined
+#[cfg(target_os = "macos")]
+pub fn quarantine(path: &Path) -> Option<String> {
+    macos::quarantine(path)
+}
+
+/// Creates the error the local file system would give, so its message reads the same
+pub fn error(error: OsError) -> io::Error {
+    imp::error(error)
+}
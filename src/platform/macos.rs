@@ -0,0 +1,80 @@
+use std::{
+    ffi::{c_char, CString},
+    fs,
+    os::{macos::fs::MetadataExt, unix::ffi::OsStrExt},
+    path::Path,
+    ptr,
+};
+
+use nix::libc;
+
+/// The extended attribute macOS sets on downloaded files until the user first opens them
+const QUARANTINE: &str = "com.apple.quarantine";
+
+/// The names `chflags` and `ls -lO` give BSD file flags, in the order they are shown
+const FLAG_NAMES: &[(u32, &str)] = &[
+    (0x0001_0000, "arch"),
+    (0x0000_0001, "nodump"),
+    (0x0000_0008, "opaque"),
+    (0x0004_0000, "sappnd"),
+    (0x0002_0000, "schg"),
+    (0x0010_0000, "sunlnk"),
+    (0x0000_0004, "uappnd"),
+    (0x0000_0002, "uchg"),
+    (0x0000_8000, "hidden"),
+    (0x0008_0000, "restricted"),
+    (0x0000_0020, "compressed"),
+];
+
+pub fn file_flags(path: &Path) -> Option<String> {
+    let flags = fs::symlink_metadata(path).ok()?.st_flags();
+    let names: Vec<&str> = FLAG_NAMES.iter().filter(|&&(flag, _)| flags & flag != 0).map(|&(_, name)| name).collect();
+    Some(if names.is_empty() { "-".to_string() } else { names.join(",") })
+}
+
+pub fn xattr_count(path: &Path) -> Option<usize> {
+    let path = CString::new(path.as_os_str().as_bytes()).ok()?;
+    // SAFETY: the path is NUL-terminated; a null buffer only asks for the names' total length
+    let len = unsafe { libc::listxattr(path.as_ptr(), ptr::null_mut(), 0, libc::XATTR_NOFOLLOW) };
+    if len <= 0 {
+        return (len == 0).then_some(0);
+    }
+    let mut names = vec![0u8; len as usize];
+    // SAFETY: the buffer is passed with its length, and listxattr writes no more than that
+    let len = unsafe { libc::listxattr(path.as_ptr(), names.as_mut_ptr().cast::<c_char>(), names.len(), libc::XATTR_NOFOLLOW) };
+    // The names are each NUL-terminated
+    (len >= 0).then(|| names[..len as usize].iter().filter(|&&byte| byte == 0).count())
+}
+
+pub fn quarantine(path: &Path) -> Option<String> {
+    let path = CString::new(path.as_os_str().as_bytes()).ok()?;
+    let name = CString::new(QUARANTINE).ok()?;
+    let mut value = [0u8; 1024];
+    // SAFETY: both strings are NUL-terminated, and the buffer is passed with its length
+    let len = unsafe { libc::getxattr(path.as_ptr(), name.as_ptr(), value.as_mut_ptr().cast(), value.len(), 0, libc::XATTR_NOFOLLOW) };
+    if len < 0 {
+        return None;
+    }
+    Some(quarantine_agent(&String::from_utf8_lossy(&value[..len as usize])))
+}
+
+/// Picks the application out of a quarantine attribute such as
+/// `0083;65a1b2c3;Safari;4A3B...`, falling back to `yes` when none is recorded
+fn quarantine_agent(value: &str) -> String {
+    match value.split(';').nth(2) {
+        Some(agent) if !agent.is_empty() => agent.to_string(),
+        _ => "yes".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quarantine_agent() {
+        assert_eq!(quarantine_agent("0083;65a1b2c3;Safari;4A3B2C1D-0000-0000-0000-000000000000"), "Safari");
+        assert_eq!(quarantine_agent("0081;65a1b2c3;;"), "yes");
+        assert_eq!(quarantine_agent("0081"), "yes");
+    }
+}
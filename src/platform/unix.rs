@@ -0,0 +1,101 @@
+use std::{
+    ffi::OsString,
+    fs, io,
+    os::{
+        fd::AsRawFd,
+        unix::{
+            ffi::OsStringExt,
+            fs::{FileTypeExt, MetadataExt},
+        },
+    },
+    path::Path,
+    time::{Duration, UNIX_EPOCH},
+};
+
+use super::OsError;
+use crate::file_system::{FileType, Metadata};
+
+nix::ioctl_read_bad!(window_size, nix::libc::TIOCGWINSZ, nix::libc::winsize);
+
+pub fn metadata(path: &Path) -> io::Result<Metadata> {
+    fs::metadata(path).map(|metadata| from_std(&metadata))
+}
+
+pub fn symlink_metadata(path: &Path) -> io::Result<Metadata> {
+    fs::symlink_metadata(path).map(|metadata| from_std(&metadata))
+}
+
+pub fn from_std(metadata: &fs::Metadata) -> Metadata {
+    let file_type = metadata.file_type();
+    let file_type = if file_type.is_dir() {
+        FileType::Directory
+    } else if file_type.is_symlink() {
+        FileType::Symlink
+    } else if file_type.is_fifo() {
+        FileType::Fifo
+    } else if file_type.is_socket() {
+        FileType::Socket
+    } else if file_type.is_block_device() {
+        FileType::BlockDevice
+    } else if file_type.is_char_device() {
+        FileType::CharDevice
+    } else {
+        FileType::File
+    };
+    let changed = (metadata.ctime() >= 0).then(|| UNIX_EPOCH + Duration::new(metadata.ctime() as u64, metadata.ctime_nsec() as u32));
+    Metadata {
+        file_type,
+        mode: metadata.mode(),
+        len: metadata.len(),
+        blocks: metadata.blocks(),
+        nlink: metadata.nlink(),
+        ino: metadata.ino(),
+        dev: metadata.dev(),
+        uid: metadata.uid(),
+        gid: metadata.gid(),
+        owner: None,
+        group: None,
+        attributes: 0,
+        modified: metadata.modified().ok(),
+        accessed: metadata.accessed().ok(),
+        changed,
+        // statx on Linux, st_birthtime on macOS and the BSDs
+        created: metadata.created().ok(),
+    }
+}
+
+pub fn user_name(uid: u32) -> Option<String> {
+    users::get_user_by_uid(uid).map(|user| user.name().to_string_lossy().into_owned())
+}
+
+pub fn group_name(gid: u32) -> Option<String> {
+    users::get_group_by_gid(gid).map(|group| group.name().to_string_lossy().into_owned())
+}
+
+pub fn current_uid() -> u32 {
+    users::get_current_uid()
+}
+
+pub fn current_gid() -> u32 {
+    users::get_current_gid()
+}
+
+pub fn terminal_width() -> Option<usize> {
+    let mut size = nix::libc::winsize { ws_row: 0, ws_col: 0, ws_xpixel: 0, ws_ypixel: 0 };
+    // SAFETY: TIOCGWINSZ only writes a winsize into the struct it is given
+    let result = unsafe { window_size(io::stdout().as_raw_fd(), &mut size) };
+    result.ok().filter(|_| size.ws_col > 0).map(|_| usize::from(size.ws_col))
+}
+
+pub fn os_string_from_bytes(bytes: Vec<u8>) -> OsString {
+    OsString::from_vec(bytes)
+}
+
+pub fn error(error: OsError) -> io::Error {
+    io::Error::from_raw_os_error(match error {
+        OsError::NotFound => nix::libc::ENOENT,
+        OsError::NotADirectory => nix::libc::ENOTDIR,
+        OsError::InvalidArgument => nix::libc::EINVAL,
+        OsError::TooManyLinks => nix::libc::ELOOP,
+    })
+}
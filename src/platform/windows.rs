@@ -0,0 +1,250 @@
+use std::{
+    ffi::OsString,
+    fs, io, iter, mem,
+    os::windows::{ffi::OsStrExt, fs::MetadataExt},
+    path::Path,
+    ptr, slice,
+    sync::Mutex,
+};
+
+use windows_sys::Win32::{
+    Foundation::{CloseHandle, LocalFree, ERROR_CANT_RESOLVE_FILENAME, ERROR_DIRECTORY, ERROR_FILE_NOT_FOUND, ERROR_INVALID_PARAMETER, HANDLE, INVALID_HANDLE_VALUE},
+    Security::{
+        Authorization::{GetSecurityInfo, SE_FILE_OBJECT},
+        GetLengthSid, GetTokenInformation, LookupAccountSidW, TokenPrimaryGroup, TokenUser, GROUP_SECURITY_INFORMATION, OWNER_SECURITY_INFORMATION, PSECURITY_DESCRIPTOR, PSID, SID_NAME_USE, TOKEN_INFORMATION_CLASS,
+        TOKEN_PRIMARY_GROUP, TOKEN_QUERY, TOKEN_USER,
+    },
+    Storage::FileSystem::{
+        CreateFileW, GetFileInformationByHandle, BY_HANDLE_FILE_INFORMATION, FILE_ATTRIBUTE_READONLY, FILE_FLAG_BACKUP_SEMANTICS, FILE_FLAG_OPEN_REPARSE_POINT, FILE_READ_ATTRIBUTES, FILE_SHARE_DELETE, FILE_SHARE_READ, FILE_SHARE_WRITE, OPEN_EXISTING, READ_CONTROL,
+    },
+    System::{
+        Console::{GetConsoleScreenBufferInfo, GetStdHandle, CONSOLE_SCREEN_BUFFER_INFO, STD_OUTPUT_HANDLE},
+        Threading::{GetCurrentProcess, OpenProcessToken},
+    },
+};
+
+use super::OsError;
+use crate::file_system::{FileType, Metadata};
+
+/// Extensions of files Windows runs as programs, which are shown as executables
+const EXECUTABLE_EXTENSIONS: &[&str] = &["exe", "com", "bat", "cmd", "ps1"];
+
+/// Every owner and group SID met so far; a SID's index stands in for a uid or gid
+static SIDS: Mutex<Vec<Vec<u8>>> = Mutex::new(Vec::new());
+
+pub fn metadata(path: &Path) -> io::Result<Metadata> {
+    describe(path, true)
+}
+
+pub fn symlink_metadata(path: &Path) -> io::Result<Metadata> {
+    describe(path, false)
+}
+
+/// Reads what `std::fs` reports about an entry, then its file id, link count and
+/// owners through a handle, leaving them out if it cannot be opened
+fn describe(path: &Path, follow: bool) -> io::Result<Metadata> {
+    let metadata = if follow { fs::metadata(path)? } else { fs::symlink_metadata(path)? };
+    let mut metadata = from_std(&metadata);
+    let extension = path.extension().and_then(|extension| extension.to_str()).unwrap_or_default();
+    if metadata.is_file() && EXECUTABLE_EXTENSIONS.iter().any(|executable| executable.eq_ignore_ascii_case(extension)) {
+        metadata.mode |= 0o111;
+    }
+    let Some(handle) = Handle::open(path, follow) else {
+        return Ok(metadata);
+    };
+    if let Some(information) = handle.information() {
+        metadata.dev = information.dwVolumeSerialNumber.into();
+        metadata.ino = u64::from(information.nFileIndexHigh) << 32 | u64::from(information.nFileIndexLow);
+        metadata.nlink = information.nNumberOfLinks.into();
+    }
+    if let Some((owner, group)) = handle.owners() {
+        metadata.uid = owner;
+        metadata.gid = group;
+    }
+    Ok(metadata)
+}
+
+pub fn from_std(metadata: &fs::Metadata) -> Metadata {
+    let attributes = metadata.file_attributes();
+    // Junctions are reparse points that std reports as symlinks too
+    let (file_type, type_bits) = if metadata.is_symlink() {
+        (FileType::Symlink, 0o120000)
+    } else if metadata.is_dir() {
+        (FileType::Directory, 0o040000)
+    } else {
+        (FileType::File, 0o100000)
+    };
+    // Windows has no permission bits: anyone may write unless the entry is read-only
+    let mut permissions = if attributes & FILE_ATTRIBUTE_READONLY != 0 { 0o444 } else { 0o666 };
+    if file_type != FileType::File {
+        permissions |= 0o111;
+    }
+    Metadata {
+        file_type,
+        mode: type_bits | permissions,
+        len: metadata.len(),
+        blocks: metadata.len().div_ceil(512),
+        nlink: 1,
+        attributes,
+        modified: metadata.modified().ok(),
+        accessed: metadata.accessed().ok(),
+        created: metadata.created().ok(),
+        ..Default::default()
+    }
+}
+
+pub fn user_name(uid: u32) -> Option<String> {
+    account_name(uid)
+}
+
+pub fn group_name(gid: u32) -> Option<String> {
+    account_name(gid)
+}
+
+pub fn current_uid() -> u32 {
+    token_sid(TokenUser).unwrap_or(u32::MAX)
+}
+
+pub fn current_gid() -> u32 {
+    token_sid(TokenPrimaryGroup).unwrap_or(u32::MAX)
+}
+
+pub fn terminal_width() -> Option<usize> {
+    // SAFETY: an all-zero CONSOLE_SCREEN_BUFFER_INFO is valid, and the call only writes into it
+    let mut information: CONSOLE_SCREEN_BUFFER_INFO = unsafe { mem::zeroed() };
+    let found = unsafe { GetConsoleScreenBufferInfo(GetStdHandle(STD_OUTPUT_HANDLE), &mut information) } != 0;
+    let width = information.srWindow.Right - information.srWindow.Left + 1;
+    (found && width > 0).then_some(width as usize)
+}
+
+pub fn os_string_from_bytes(bytes: Vec<u8>) -> OsString {
+    OsString::from(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+pub fn error(error: OsError) -> io::Error {
+    let code = match error {
+        OsError::NotFound => ERROR_FILE_NOT_FOUND,
+        OsError::NotADirectory => ERROR_DIRECTORY,
+        OsError::InvalidArgument => ERROR_INVALID_PARAMETER,
+        OsError::TooManyLinks => ERROR_CANT_RESOLVE_FILENAME,
+    };
+    io::Error::from_raw_os_error(code as i32)
+}
+
+/// An open handle, closed when dropped
+struct Handle(HANDLE);
+
+impl Handle {
+    /// Opens an entry only to query it, with the right to read its owner if that is granted
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The entry to open
+    /// * `follow` - Whether a symlink or junction is followed rather than opened itself
+    fn open(path: &Path, follow: bool) -> Option<Handle> {
+        let path: Vec<u16> = path.as_os_str().encode_wide().chain(iter::once(0)).collect();
+        // Directories can only be opened with backup semantics
+        let flags = if follow { FILE_FLAG_BACKUP_SEMANTICS } else { FILE_FLAG_BACKUP_SEMANTICS | FILE_FLAG_OPEN_REPARSE_POINT };
+        [FILE_READ_ATTRIBUTES | READ_CONTROL, FILE_READ_ATTRIBUTES].into_iter().find_map(|access| {
+            // SAFETY: the path is NUL-terminated and outlives the call
+            let handle = unsafe { CreateFileW(path.as_ptr(), access, FILE_SHARE_READ | FILE_SHARE_WRITE | FILE_SHARE_DELETE, ptr::null(), OPEN_EXISTING, flags, ptr::null_mut()) };
+            (handle != INVALID_HANDLE_VALUE).then_some(Handle(handle))
+        })
+    }
+
+    /// Reads the volume serial number, file index and link count
+    fn information(&self) -> Option<BY_HANDLE_FILE_INFORMATION> {
+        // SAFETY: an all-zero BY_HANDLE_FILE_INFORMATION is valid, and the call only writes into it
+        let mut information: BY_HANDLE_FILE_INFORMATION = unsafe { mem::zeroed() };
+        (unsafe { GetFileInformationByHandle(self.0, &mut information) } != 0).then_some(information)
+    }
+
+    /// Finds the entry's owner and group, as indexes into the table of SIDs
+    fn owners(&self) -> Option<(u32, u32)> {
+        let mut owner: PSID = ptr::null_mut();
+        let mut group: PSID = ptr::null_mut();
+        let mut descriptor: PSECURITY_DESCRIPTOR = ptr::null_mut();
+        // SAFETY: the SIDs point into the descriptor, which is only freed once they are copied
+        unsafe {
+            let status = GetSecurityInfo(self.0, SE_FILE_OBJECT, OWNER_SECURITY_INFORMATION | GROUP_SECURITY_INFORMATION, &mut owner, &mut group, ptr::null_mut(), ptr::null_mut(), &mut descriptor);
+            if status != 0 {
+                return None;
+            }
+            let ids = (sid_id(owner), sid_id(group));
+            LocalFree(descriptor);
+            Some(ids)
+        }
+    }
+}
+
+impl Drop for Handle {
+    fn drop(&mut self) {
+        // SAFETY: the handle was opened by this wrapper and is closed only here
+        unsafe { CloseHandle(self.0) };
+    }
+}
+
+/// Records a SID in the table
+///
+/// # Safety
+///
+/// `sid` must point to a valid SID.
+///
+/// # Returns
+///
+/// The SID's index, the same for every copy of it
+unsafe fn sid_id(sid: PSID) -> u32 {
+    let sid = slice::from_raw_parts(sid.cast::<u8>(), GetLengthSid(sid) as usize).to_vec();
+    let mut sids = SIDS.lock().unwrap_or_else(|err| err.into_inner());
+    let index = sids.iter().position(|known| *known == sid).unwrap_or_else(|| {
+        sids.push(sid);
+        sids.len() - 1
+    });
+    index as u32
+}
+
+/// Looks up the account name of a SID in the table, such as `Administrators`
+fn account_name(id: u32) -> Option<String> {
+    let sid = SIDS.lock().unwrap_or_else(|err| err.into_inner()).get(id as usize)?.clone();
+    // SIDs are read as 32-bit sub-authorities, so they are copied somewhere aligned
+    let mut aligned = vec![0u32; sid.len().div_ceil(4)];
+    let mut name = [0u16; 256];
+    let mut domain = [0u16; 256];
+    let (mut name_len, mut domain_len) = (name.len() as u32, domain.len() as u32);
+    let mut kind: SID_NAME_USE = 0;
+    // SAFETY: the SID is a copy of a valid one, and each buffer is passed with its length
+    let found = unsafe {
+        ptr::copy_nonoverlapping(sid.as_ptr(), aligned.as_mut_ptr().cast::<u8>(), sid.len());
+        LookupAccountSidW(ptr::null(), aligned.as_mut_ptr().cast(), name.as_mut_ptr(), &mut name_len, domain.as_mut_ptr(), &mut domain_len, &mut kind)
+    };
+    (found != 0).then(|| String::from_utf16_lossy(&name[..name_len as usize]))
+}
+
+/// Finds the user or primary group the listing runs as, as an index into the table of SIDs
+///
+/// # Arguments
+///
+/// * `class` - `TokenUser` or `TokenPrimaryGroup`
+fn token_sid(class: TOKEN_INFORMATION_CLASS) -> Option<u32> {
+    let mut token: HANDLE = ptr::null_mut();
+    // SAFETY: the token handle is only written by the call, and closed by the wrapper
+    if unsafe { OpenProcessToken(GetCurrentProcess(), TOKEN_QUERY, &mut token) } == 0 {
+        return None;
+    }
+    let token = Handle(token);
+    // Large and aligned enough for either structure and the SID it points into
+    let mut buffer = [0u64; 64];
+    let mut len = 0;
+    // SAFETY: the buffer is passed with its size, and the SID read from it lies inside it
+    unsafe {
+        if GetTokenInformation(token.0, class, buffer.as_mut_ptr().cast(), mem::size_of_val(&buffer) as u32, &mut len) == 0 {
+            return None;
+        }
+        let sid = if class == TokenUser {
+            (*buffer.as_ptr().cast::<TOKEN_USER>()).User.Sid
+        } else {
+            (*buffer.as_ptr().cast::<TOKEN_PRIMARY_GROUP>()).PrimaryGroup
+        };
+        Some(sid_id(sid))
+    }
+}
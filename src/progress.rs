@@ -0,0 +1,86 @@
+use std::{
+    io::{self, IsTerminal, Write},
+    time::{Duration, Instant},
+};
+
+use humansize::{format_size, BINARY};
+
+/// Minimum time between two terminal title updates
+const UPDATE_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Reports scan progress in the terminal title using OSC escape sequences
+pub struct Progress {
+    enabled: bool,
+    entries: u64,
+    bytes: u64,
+    last_update: Option<Instant>,
+}
+
+impl Progress {
+    /// Creates a progress reporter; it stays silent unless `enabled` is set
+    /// and stderr is a terminal
+    pub fn new(enabled: bool) -> Self {
+        Progress {
+            enabled: enabled && io::stderr().is_terminal(),
+            entries: 0,
+            bytes: 0,
+            last_update: None,
+        }
+    }
+
+    /// Records a scanned entry of `size` bytes, refreshing the title if due
+    pub fn record(&mut self, size: u64) {
+        self.entries += 1;
+        self.bytes += size;
+
+        if !self.enabled {
+            return;
+        }
+        let due = self
+            .last_update
+            .is_none_or(|last| last.elapsed() >= UPDATE_INTERVAL);
+        if due {
+            self.last_update = Some(Instant::now());
+            set_title(&self.status());
+        }
+    }
+
+    /// Builds the status line shown in the terminal title
+    ///
+    /// # Returns
+    ///
+    /// A string like "ls_oxide: 42k entries, 1.8 GiB scanned"
+    pub fn status(&self) -> String {
+        format!(
+            "ls_oxide: {} entries, {} scanned",
+            format_count(self.entries),
+            format_size(self.bytes, BINARY.decimal_places(1))
+        )
+    }
+
+    /// Clears the terminal title once the scan is complete
+    pub fn finish(&mut self) {
+        if self.enabled && self.last_update.is_some() {
+            set_title("");
+        }
+        self.last_update = None;
+    }
+}
+
+/// Writes an OSC 0 sequence setting the terminal title to stderr
+fn set_title(title: &str) {
+    let mut stderr = io::stderr().lock();
+    let _ = write!(stderr, "\x1b]0;{}\x07", title);
+    let _ = stderr.flush();
+}
+
+/// Formats an entry count compactly (e.g. 950, 42k, 3.1M)
+pub fn format_count(count: u64) -> String {
+    if count >= 1_000_000 {
+        format!("{:.1}M", count as f64 / 1_000_000.0)
+    } else if count >= 1_000 {
+        format!("{}k", count / 1_000)
+    } else {
+        count.to_string()
+    }
+}
@@ -0,0 +1,231 @@
+use std::{borrow::Cow, ffi::OsStr, fmt::Write as _};
+
+use clap::ValueEnum;
+
+use crate::ascii;
+
+/// How file names are quoted and escaped when printed, following GNU `ls --quoting-style`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum QuotingStyle {
+    /// Print names as they are
+    #[default]
+    Literal,
+    /// Quote names in single quotes when the shell would need them
+    Shell,
+    /// Always quote names in single quotes
+    ShellAlways,
+    /// Like shell, writing control characters as $'\n' so the name can be pasted into a shell
+    ShellEscape,
+    /// Like shell-always, writing control characters as $'\n'
+    ShellEscapeAlways,
+    /// Enclose names in double quotes with C escapes, as -Q does
+    C,
+    /// Write C escapes without quotes, as -b does
+    Escape,
+}
+
+impl QuotingStyle {
+    /// Whether the style writes control characters as escapes itself, so `-q` has nothing to hide
+    pub fn escapes_control(&self) -> bool {
+        matches!(self, QuotingStyle::ShellEscape | QuotingStyle::ShellEscapeAlways | QuotingStyle::C | QuotingStyle::Escape)
+    }
+}
+
+/// Characters a shell would treat specially anywhere in a word
+const SHELL_SPECIAL: &str = " \t\n'\"\\`$&|;<>()*?[]{}!^";
+
+/// Characters a shell only treats specially at the start of a word
+const SHELL_SPECIAL_FIRST: &str = "#~=%";
+
+/// A piece of a file name: a character, or a byte that is not part of valid UTF-8
+#[derive(Clone, Copy)]
+enum Piece {
+    Char(char),
+    Byte(u8),
+}
+
+/// Splits a name as stored by the file system into characters and stray bytes
+fn pieces(name: &OsStr) -> impl Iterator<Item = Piece> + '_ {
+    name.as_encoded_bytes()
+        .utf8_chunks()
+        .flat_map(|chunk| chunk.valid().chars().map(Piece::Char).chain(chunk.invalid().iter().map(|&byte| Piece::Byte(byte))))
+}
+
+/// Quotes a file name in the given style
+///
+/// Bytes that are not valid UTF-8 are written as backslash-octal: as `$'\351'` in
+/// the shell-escape styles, and as a single `\351` escape otherwise.
+///
+/// # Arguments
+///
+/// * `name` - The name (or path) to print, as stored by the file system
+/// * `style` - The quoting style to apply
+///
+/// # Returns
+///
+/// The name unchanged if the style leaves it alone, otherwise a quoted copy
+pub fn quote(name: &OsStr, style: QuotingStyle) -> Cow<'_, str> {
+    match style {
+        QuotingStyle::Literal => ascii::os_text(name),
+        QuotingStyle::Shell => shell_quote(name, false, false),
+        QuotingStyle::ShellAlways => shell_quote(name, true, false),
+        QuotingStyle::ShellEscape => shell_quote(name, false, true),
+        QuotingStyle::ShellEscapeAlways => shell_quote(name, true, true),
+        QuotingStyle::C => {
+            let mut quoted = String::with_capacity(name.len() + 2);
+            quoted.push('"');
+            c_escape(&mut quoted, name, false);
+            quoted.push('"');
+            Cow::Owned(quoted)
+        }
+        QuotingStyle::Escape => {
+            if let Some(name) = name.to_str().filter(|name| !name.chars().any(|c| c == '\\' || c == ' ' || c.is_control())) {
+                return Cow::Borrowed(name);
+            }
+            let mut escaped = String::with_capacity(name.len() * 2);
+            c_escape(&mut escaped, name, true);
+            Cow::Owned(escaped)
+        }
+    }
+}
+
+/// Appends a name with C escapes for backslashes, control characters, stray bytes and,
+/// when quoted, double quotes; `escape_spaces` writes spaces as `\ ` for unquoted output
+fn c_escape(out: &mut String, name: &OsStr, escape_spaces: bool) {
+    for piece in pieces(name) {
+        match piece {
+            Piece::Char('"') if !escape_spaces => out.push_str("\\\""),
+            Piece::Char(' ') if escape_spaces => out.push_str("\\ "),
+            Piece::Char('\\') => out.push_str("\\\\"),
+            Piece::Char(c) if c.is_control() => push_control(out, c),
+            Piece::Char(c) => out.push(c),
+            Piece::Byte(byte) => push_byte(out, byte),
+        }
+    }
+}
+
+/// Appends a byte as a backslash-octal escape
+fn push_byte(out: &mut String, byte: u8) {
+    let _ = write!(out, "\\{:03o}", byte);
+}
+
+/// Appends a control character as its C escape, or as backslash-octal UTF-8 bytes
+fn push_control(out: &mut String, c: char) {
+    match c {
+        '\x07' => out.push_str("\\a"),
+        '\x08' => out.push_str("\\b"),
+        '\x0c' => out.push_str("\\f"),
+        '\n' => out.push_str("\\n"),
+        '\r' => out.push_str("\\r"),
+        '\t' => out.push_str("\\t"),
+        '\x0b' => out.push_str("\\v"),
+        c => {
+            let mut bytes = [0; 4];
+            for byte in c.encode_utf8(&mut bytes).bytes() {
+                push_byte(out, byte);
+            }
+        }
+    }
+}
+
+/// Quotes a name in single quotes for the shell
+///
+/// # Arguments
+///
+/// * `name` - The name to quote
+/// * `always` - Whether to quote names the shell would accept bare
+/// * `escape` - Whether to write control characters and stray bytes as `$'\n'` instead
+///   of as they are
+fn shell_quote(name: &OsStr, always: bool, escape: bool) -> Cow<'_, str> {
+    let plain = name.to_str().filter(|name| {
+        !name.is_empty() && !name.starts_with(|c| SHELL_SPECIAL_FIRST.contains(c)) && !name.chars().any(|c| SHELL_SPECIAL.contains(c) || c.is_control())
+    });
+    if let Some(name) = plain.filter(|_| !always) {
+        return Cow::Borrowed(name);
+    }
+
+    let mut quoted = String::with_capacity(name.len() + 2);
+    quoted.push('\'');
+    // Whether the last piece went into an ANSI-C quoted piece, which the next escape joins
+    let mut in_escape = false;
+    for piece in pieces(name) {
+        let escaped = escape && (matches!(piece, Piece::Byte(_)) || matches!(piece, Piece::Char(c) if c.is_control()));
+        if escaped {
+            if in_escape {
+                quoted.truncate(quoted.len() - 2);
+            } else {
+                // Leave the single quotes for an ANSI-C quoted piece, then resume them
+                quoted.push_str("'$'");
+            }
+            match piece {
+                Piece::Char(c) => push_control(&mut quoted, c),
+                Piece::Byte(byte) => push_byte(&mut quoted, byte),
+            }
+            quoted.push_str("''");
+        } else {
+            match piece {
+                Piece::Char('\'') => quoted.push_str("'\\''"),
+                Piece::Char(c) => quoted.push(c),
+                Piece::Byte(byte) => push_byte(&mut quoted, byte),
+            }
+        }
+        in_escape = escaped;
+    }
+    quoted.push('\'');
+    // Drop the empty '' pairs left where escapes meet the start or end of the name
+    if quoted.len() > 2 {
+        if let Some(trimmed) = quoted.strip_prefix("''") {
+            quoted = trimmed.to_string();
+        }
+        if let Some(trimmed) = quoted.strip_suffix("''") {
+            quoted = trimmed.to_string();
+        }
+    }
+    Cow::Owned(quoted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quote() {
+        assert_eq!(quote(OsStr::new("a b"), QuotingStyle::Literal), "a b");
+        assert_eq!(quote(OsStr::new("plain.txt"), QuotingStyle::C), "\"plain.txt\"");
+        assert_eq!(quote(OsStr::new("say \"hi\"\n"), QuotingStyle::C), "\"say \\\"hi\\\"\\n\"");
+        assert_eq!(quote(OsStr::new("a b\\c\t"), QuotingStyle::Escape), "a\\ b\\\\c\\t");
+        assert_eq!(quote(OsStr::new("plain.txt"), QuotingStyle::Escape), "plain.txt");
+        assert_eq!(quote(OsStr::new("\u{1b}"), QuotingStyle::Escape), "\\033");
+    }
+
+    #[test]
+    fn test_shell_quote() {
+        assert_eq!(quote(OsStr::new("plain.txt"), QuotingStyle::Shell), "plain.txt");
+        assert_eq!(quote(OsStr::new("plain.txt"), QuotingStyle::ShellAlways), "'plain.txt'");
+        assert_eq!(quote(OsStr::new("a b"), QuotingStyle::Shell), "'a b'");
+        assert_eq!(quote(OsStr::new("it's"), QuotingStyle::ShellEscape), "'it'\\''s'");
+        assert_eq!(quote(OsStr::new("'"), QuotingStyle::Shell), "\\'");
+        assert_eq!(quote(OsStr::new("~home"), QuotingStyle::Shell), "'~home'");
+        assert_eq!(quote(OsStr::new("a~b"), QuotingStyle::Shell), "a~b");
+        assert_eq!(quote(OsStr::new("a\nb"), QuotingStyle::ShellEscape), "'a'$'\\n''b'");
+        assert_eq!(quote(OsStr::new("\nb"), QuotingStyle::ShellEscape), "$'\\n''b'");
+        assert_eq!(quote(OsStr::new("a\n"), QuotingStyle::ShellEscape), "'a'$'\\n'");
+        assert_eq!(quote(OsStr::new("a\n\tb"), QuotingStyle::ShellEscape), "'a'$'\\n\\t''b'");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_quote_invalid_utf8() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let name = OsStr::from_bytes(b"caf\xe9");
+        assert_eq!(quote(name, QuotingStyle::Literal), "caf\\351");
+        assert_eq!(quote(name, QuotingStyle::ShellEscape), "'caf'$'\\351'");
+        assert_eq!(quote(name, QuotingStyle::ShellEscapeAlways), "'caf'$'\\351'");
+        assert_eq!(quote(OsStr::from_bytes(b"\xe9\xea"), QuotingStyle::ShellEscape), "$'\\351\\352'");
+        assert_eq!(quote(name, QuotingStyle::Escape), "caf\\351");
+        assert_eq!(quote(name, QuotingStyle::C), "\"caf\\351\"");
+        // A real backslash is still escaped, unlike the one written for the byte
+        assert_eq!(quote(OsStr::from_bytes(b"a\\\xe9"), QuotingStyle::Escape), "a\\\\\\351");
+    }
+}
@@ -0,0 +1,235 @@
+use std::{
+    ffi::{OsStr, OsString},
+    fs, io,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+#[cfg(unix)]
+use std::os::unix::ffi::OsStrExt;
+
+#[cfg(unix)]
+use nix::{
+    dir::{Dir, OwningIter},
+    errno::Errno,
+    fcntl::OFlag,
+    sys::{
+        stat::Mode,
+        statvfs::{statvfs, FsFlags},
+    },
+};
+
+/// Whether `--assert-readonly` is in effect for this process
+static ASSERT_READONLY: AtomicBool = AtomicBool::new(false);
+
+/// Turns on read-only assurance: directories are then opened without updating their atime
+pub fn enable() {
+    ASSERT_READONLY.store(true, Ordering::Relaxed);
+}
+
+/// Checks whether read-only assurance was requested
+pub fn is_enabled() -> bool {
+    ASSERT_READONLY.load(Ordering::Relaxed)
+}
+
+/// A single entry yielded while reading a directory
+pub struct DirItem {
+    name: OsString,
+    path: PathBuf,
+    entry: Option<fs::DirEntry>,
+}
+
+impl DirItem {
+    /// The entry's file name
+    pub fn file_name(&self) -> &OsStr {
+        &self.name
+    }
+
+    /// The entry's full path
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Fetches the entry's metadata without following symlinks, like `fs::DirEntry::metadata`
+    pub fn metadata(&self) -> io::Result<fs::Metadata> {
+        match &self.entry {
+            Some(entry) => entry.metadata(),
+            None => fs::symlink_metadata(&self.path),
+        }
+    }
+}
+
+/// Iterator over a directory's entries, excluding `.` and `..`
+pub enum Entries {
+    Std(fs::ReadDir),
+    #[cfg(unix)]
+    NoAtime { dir: OwningIter, path: PathBuf },
+}
+
+impl Iterator for Entries {
+    type Item = io::Result<DirItem>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            Entries::Std(read_dir) => read_dir.next().map(|entry| {
+                entry.map(|entry| DirItem {
+                    name: entry.file_name(),
+                    path: entry.path(),
+                    entry: Some(entry),
+                })
+            }),
+            #[cfg(unix)]
+            Entries::NoAtime { dir, path } => loop {
+                let entry = match dir.next()? {
+                    Ok(entry) => entry,
+                    Err(errno) => return Some(Err(errno.into())),
+                };
+                let name = OsStr::from_bytes(entry.file_name().to_bytes());
+                if name == "." || name == ".." {
+                    continue;
+                }
+                return Some(Ok(DirItem {
+                    name: name.to_os_string(),
+                    path: path.join(name),
+                    entry: None,
+                }));
+            },
+        }
+    }
+}
+
+/// Opens a directory for listing
+///
+/// With read-only assurance enabled the directory is opened with `O_NOATIME`, or
+/// plainly if its filesystem is mounted read-only or `noatime`. If neither is
+/// possible, reading it could update its access time and an error is returned.
+///
+/// # Arguments
+///
+/// * `path` - The directory to read
+///
+/// # Returns
+///
+/// An iterator over the directory's entries
+pub fn read_dir<P: AsRef<Path>>(path: P) -> io::Result<Entries> {
+    let path = path.as_ref();
+    if !is_enabled() {
+        return fs::read_dir(path).map(Entries::Std);
+    }
+
+    #[cfg(not(unix))]
+    return Err(unsupported(path));
+    #[cfg(unix)]
+    {
+        let flags = OFlag::O_RDONLY | OFlag::O_DIRECTORY | OFlag::O_CLOEXEC;
+        #[cfg(target_os = "linux")]
+        let dir = match Dir::open(path, flags | OFlag::O_NOATIME, Mode::empty()) {
+            // O_NOATIME is only permitted on files we own
+            Err(Errno::EPERM) => open_on_atime_safe_mount(path, flags),
+            other => other.map_err(io::Error::from),
+        }?;
+        #[cfg(not(target_os = "linux"))]
+        let dir = open_on_atime_safe_mount(path, flags)?;
+
+        Ok(Entries::NoAtime {
+            dir: dir.into_iter(),
+            path: path.to_path_buf(),
+        })
+    }
+}
+
+/// Opens a file to read its contents
+///
+/// With read-only assurance enabled the file is opened with `O_NOATIME`, or plainly
+/// if its filesystem is mounted read-only or `noatime`, and otherwise refused, just
+/// like directories in [`read_dir`].
+///
+/// # Arguments
+///
+/// * `path` - The file to open
+///
+/// # Returns
+///
+/// The open file
+pub fn open_file(path: &Path) -> io::Result<fs::File> {
+    if !is_enabled() {
+        return fs::File::open(path);
+    }
+
+    #[cfg(not(unix))]
+    return Err(unsupported(path));
+    #[cfg(target_os = "linux")]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        match fs::OpenOptions::new().read(true).custom_flags(nix::libc::O_NOATIME).open(path) {
+            // O_NOATIME is only permitted on files we own
+            Err(err) if err.raw_os_error() == Some(nix::libc::EPERM) => {}
+            other => return other,
+        }
+    }
+    #[cfg(unix)]
+    {
+        let mount_flags = statvfs(path)?.flags();
+        if mount_flags.intersects(FsFlags::ST_RDONLY | FsFlags::ST_NOATIME) {
+            return fs::File::open(path);
+        }
+        Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("cannot read '{}' without updating its access time: {}", path.display(), Errno::EPERM),
+        ))
+    }
+}
+
+/// Opens a directory only if its filesystem never records access times
+#[cfg(unix)]
+fn open_on_atime_safe_mount(path: &Path, flags: OFlag) -> io::Result<Dir> {
+    let mount_flags = statvfs(path)?.flags();
+    if mount_flags.intersects(FsFlags::ST_RDONLY | FsFlags::ST_NOATIME) {
+        return Dir::open(path, flags, Mode::empty()).map_err(io::Error::from);
+    }
+    Err(io::Error::new(
+        io::ErrorKind::PermissionDenied,
+        format!(
+            "cannot read '{}' without updating its access time: {}",
+            path.display(),
+            Errno::EPERM
+        ),
+    ))
+}
+
+/// The error read-only assurance gives where there is no way to leave access times alone
+#[cfg(not(unix))]
+fn unsupported(path: &Path) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!("cannot read '{}' without updating its access time on this platform", path.display()),
+    )
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_noatime_read_dir_matches_std() {
+        let temp_dir = tempdir().expect("Unable to create temporary directory");
+        fs::write(temp_dir.path().join("a.txt"), "").expect("Unable to create a.txt");
+        fs::create_dir(temp_dir.path().join("sub")).expect("Unable to create sub");
+
+        // Exercise the nix-backed iterator directly, without flipping the global switch
+        let dir = Dir::open(temp_dir.path(), OFlag::O_RDONLY | OFlag::O_DIRECTORY, Mode::empty())
+            .expect("Unable to open directory");
+        let entries = Entries::NoAtime { dir: dir.into_iter(), path: temp_dir.path().to_path_buf() };
+
+        let mut names: Vec<_> = entries
+            .map(|entry| {
+                let entry = entry.expect("Unable to read entry");
+                assert!(entry.metadata().is_ok());
+                entry.file_name().to_os_string()
+            })
+            .collect();
+        names.sort();
+        assert_eq!(names, vec![OsString::from("a.txt"), OsString::from("sub")]);
+    }
+}
@@ -0,0 +1,290 @@
+use humansize::{format_size, BINARY};
+use tabled::{builder::Builder, Table};
+
+use crate::{
+    ascii,
+    color::Painter,
+    columns::Column,
+    dir_utils::{format_time, DirContents, FileInfo, TimeStyle},
+    git::{self, Commit},
+    mounts,
+};
+
+#[cfg(windows)]
+use crate::file_system;
+#[cfg(target_os = "macos")]
+use crate::platform;
+
+/// How the long format turns entries into cells
+#[derive(Clone, Copy, Default)]
+pub struct RenderOptions<'a> {
+    /// Show byte sizes like `1.5Ki`, as `-h` asks
+    pub human_readable: bool,
+    /// Escape non-ASCII characters, as `--ascii` asks; the name is escaped as it is quoted
+    pub ascii: bool,
+    /// Colors for the cells, if the output is colored; the name is painted beforehand
+    pub painter: Option<&'a Painter>,
+    /// How timestamps are shown, as `--time-style` asks
+    pub time_style: TimeStyle,
+}
+
+/// Builds a long format table showing only the given columns, in order
+///
+/// # Arguments
+///
+/// * `files` - Entries to render, one per row
+/// * `columns` - Columns to show
+/// * `options` - How the cells are formatted
+///
+/// # Returns
+///
+/// A table with a header row followed by one row per entry
+pub fn table(files: &[FileInfo], columns: &[Column], options: &RenderOptions) -> Table {
+    let mut builder = Builder::default();
+    builder.push_record(columns.iter().map(Column::header));
+    for file in files {
+        builder.push_record(row(file, columns, options));
+    }
+    builder.build()
+}
+
+/// Renders one entry's cells for the given columns, escaped and colored as asked
+pub fn row(file: &FileInfo, columns: &[Column], options: &RenderOptions) -> Vec<String> {
+    // One lookup serves every git history column
+    let history = columns.iter().any(|column| matches!(column, Column::Commit | Column::Author | Column::Committed));
+    let commit = if history { git::last_commit(&file.path) } else { None };
+    columns
+        .iter()
+        .map(|&column| {
+            let text = cell(column, file, commit.as_ref(), options);
+            if column == Column::Name {
+                return text;
+            }
+            let text = if options.ascii { ascii::escape(&text).into_owned() } else { text };
+            match options.painter {
+                Some(painter) => painter.paint_cell(column, file, &text),
+                None => text,
+            }
+        })
+        .collect()
+}
+
+/// Renders a single column's cell for an entry, as plain text
+///
+/// # Arguments
+///
+/// * `column` - The column to render
+/// * `file` - The entry
+/// * `commit` - The entry's last commit, for the git history columns
+/// * `options` - How byte sizes and timestamps are shown
+pub fn cell(column: Column, file: &FileInfo, commit: Option<&Commit>, options: &RenderOptions) -> String {
+    let human_readable = options.human_readable;
+    let time = |time| format_time(time, options.time_style);
+    match column {
+        Column::Permissions => permissions(file),
+        Column::Octal => format!("{:04o}", file.mode & 0o7777),
+        Column::Links => file.link_count.to_string(),
+        Column::Owner => file.owner.to_string(),
+        Column::Group => file.group.to_string(),
+        Column::Size => size(file, human_readable),
+        Column::Bytes if file.is_dir => "-".to_string(),
+        Column::Bytes => file.file_size.to_string(),
+        Column::Allocated if file.is_sparse() => format!("{} (sparse)", bytes(file.allocated_size, human_readable)),
+        Column::Allocated => bytes(file.allocated_size, human_readable),
+        Column::Modified => time(file.modified_time),
+        Column::Changed => time(file.changed_time),
+        Column::Accessed => time(file.accessed_time),
+        Column::Created => file.created_time.map(time).unwrap_or_default(),
+        Column::Inode => file.inode.to_string(),
+        Column::Filesystem => mounts::describe(&file.path, file.device),
+        Column::Hash => file.hash.clone().unwrap_or_default(),
+        Column::Kind => file.content_kind.clone().unwrap_or_default(),
+        Column::Media => file.media.clone().unwrap_or_default(),
+        Column::Git => git::status(&file.path).unwrap_or_default().to_string(),
+        Column::Commit => commit.map(|commit| commit.hash.clone()).unwrap_or_default(),
+        Column::Author => commit.map(|commit| commit.author.clone()).unwrap_or_default(),
+        Column::Committed => commit.map(|commit| time(commit.time)).unwrap_or_default(),
+        #[cfg(windows)]
+        Column::Attributes => file_system::attribute_letters(file.attributes),
+        #[cfg(target_os = "macos")]
+        Column::Flags => platform::file_flags(&file.path).unwrap_or_default(),
+        #[cfg(target_os = "macos")]
+        Column::Xattrs => platform::xattr_count(&file.path).map(|count| count.to_string()).unwrap_or_default(),
+        #[cfg(target_os = "macos")]
+        Column::Quarantine => platform::quarantine(&file.path).unwrap_or_default(),
+        Column::Name => file.name.clone(),
+    }
+}
+
+/// Renders the permission string, like `drwxr-xr-x`
+pub fn permissions(file: &FileInfo) -> String {
+    format!("{}{}", type_char(file.mode), format_mode(file.mode))
+}
+
+/// The character the permission string starts with for the file type bits of a mode:
+/// `d`, `l`, `p`, `s`, `b` or `c`, and `-` for a regular file
+fn type_char(mode: u32) -> char {
+    match mode & 0o170000 {
+        0o040000 => 'd',
+        0o120000 => 'l',
+        0o010000 => 'p',
+        0o140000 => 's',
+        0o060000 => 'b',
+        0o020000 => 'c',
+        _ => '-',
+    }
+}
+
+/// Renders the size column: the length of files, and `-` for directories unless
+/// `--dir-size` or `--total-size` gave them something to show
+///
+/// # Arguments
+///
+/// * `file` - The entry
+/// * `human_readable` - Whether byte sizes are shown like `1.5Ki`
+pub fn size(file: &FileInfo, human_readable: bool) -> String {
+    match file.dir_contents {
+        Some(DirContents::Entries(Some(count))) => count.to_string(),
+        // Directories that cannot be read have no count to show
+        Some(DirContents::Entries(None)) => "?".to_string(),
+        Some(DirContents::Bytes(size)) => bytes(size, human_readable),
+        None if file.is_dir => "-".to_string(),
+        None => bytes(file.file_size, human_readable),
+    }
+}
+
+/// Renders a byte count, in human-readable form if asked
+fn bytes(size: u64, human_readable: bool) -> String {
+    if human_readable {
+        format_size(size, BINARY)
+    } else {
+        size.to_string()
+    }
+}
+
+/// Formats Unix file permissions mode into rwx string representation
+///
+/// The setuid, setgid and sticky bits replace the matching execute character
+/// with `s`/`t`, or `S`/`T` when the execute bit itself is unset.
+///
+/// # Arguments
+///
+/// * `mode` - The Unix permissions mode as a u32 bitmask
+///
+/// # Returns
+///
+/// A string containing the rwx permissions for user, group and other (e.g. "rwsr-xr-t")
+fn format_mode(mode: u32) -> String {
+    let user = (mode >> 6) & 0o7;
+    let group = (mode >> 3) & 0o7;
+    let other = mode & 0o7;
+
+    format!(
+        "{}{}{}",
+        format_rwx(user, mode & 0o4000 != 0, 's'),
+        format_rwx(group, mode & 0o2000 != 0, 's'),
+        format_rwx(other, mode & 0o1000 != 0, 't')
+    )
+}
+
+/// Formats a 3-bit Unix permission set into rwx string notation
+///
+/// # Arguments
+///
+/// * `bits` - 3 bits representing read, write, execute permissions
+/// * `special` - Whether the special bit (setuid, setgid or sticky) for this set is on
+/// * `special_char` - Character shown in the execute slot when `special` is set
+///
+/// # Returns
+///
+/// A 3-character string containing 'r', 'w', 'x' for set bits or '-' for unset bits
+fn format_rwx(bits: u32, special: bool, special_char: char) -> String {
+    let r = if bits & 0b100 != 0 { 'r' } else { '-' };
+    let w = if bits & 0b010 != 0 { 'w' } else { '-' };
+    let x = match (bits & 0b001 != 0, special) {
+        (true, false) => 'x',
+        (false, false) => '-',
+        (true, true) => special_char,
+        (false, true) => special_char.to_ascii_uppercase(),
+    };
+    format!("{}{}{}", r, w, x)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        dir_utils::{list_files_detailed, DirSize, ListOptions},
+        file_system::StdFileSystem,
+    };
+    use std::fs::{self, File};
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_permissions() {
+        let temp_dir = tempdir().expect("Unable to create temporary directory");
+        File::create(temp_dir.path().join("test_file.txt")).expect("Unable to create test file");
+        fs::create_dir(temp_dir.path().join("dir")).expect("Unable to create dir");
+
+        let files = list_files_detailed(temp_dir.path(), &ListOptions::default()).unwrap();
+        assert!(permissions(&files[0]).starts_with('d'));
+        // Regular files start with '-', not '.'
+        assert!(permissions(&files[1]).starts_with('-'));
+        assert_eq!(cell(Column::Octal, &files[0], None, &RenderOptions::default()).len(), 4);
+
+        assert_eq!(type_char(0o010644), 'p');
+        assert_eq!(type_char(0o140755), 's');
+        assert_eq!(type_char(0o060660), 'b');
+        assert_eq!(type_char(0o020666), 'c');
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlink_permissions() {
+        let temp_dir = tempdir().expect("Unable to create temporary directory");
+        File::create(temp_dir.path().join("target.txt")).expect("Unable to create target.txt");
+        std::os::unix::fs::symlink("target.txt", temp_dir.path().join("link")).expect("Unable to create link");
+
+        let files = list_files_detailed(temp_dir.path(), &ListOptions::default()).unwrap();
+        assert!(permissions(&files[0]).starts_with('l'));
+        assert!(permissions(&files[1]).starts_with('-'));
+    }
+
+    #[test]
+    fn test_time_style() {
+        let temp_dir = tempdir().unwrap();
+        File::create(temp_dir.path().join("file")).unwrap();
+
+        let files = list_files_detailed(temp_dir.path(), &ListOptions::default()).unwrap();
+        let full = RenderOptions { time_style: TimeStyle::FullIso, ..Default::default() };
+        assert_eq!(cell(Column::Modified, &files[0], None, &full), format_time(files[0].modified_time, TimeStyle::FullIso));
+        assert_eq!(cell(Column::Modified, &files[0], None, &RenderOptions::default()), format_time(files[0].modified_time, TimeStyle::Default));
+    }
+
+    #[test]
+    fn test_format_mode_special_bits() {
+        assert_eq!(format_mode(0o755), "rwxr-xr-x");
+        assert_eq!(format_mode(0o4755), "rwsr-xr-x");
+        assert_eq!(format_mode(0o2745), "rwxr-Sr-x");
+        assert_eq!(format_mode(0o1777), "rwxrwxrwt");
+        assert_eq!(format_mode(0o1776), "rwxrwxrwT");
+    }
+
+    #[test]
+    fn test_size() {
+        let temp_dir = tempdir().unwrap();
+        fs::create_dir(temp_dir.path().join("dir")).unwrap();
+        fs::write(temp_dir.path().join("file"), vec![0; 2048]).unwrap();
+        File::create(temp_dir.path().join("image")).unwrap().set_len(1 << 20).unwrap();
+
+        let mut files = list_files_detailed(temp_dir.path(), &ListOptions::default()).unwrap();
+        assert_eq!(size(&files[0], false), "-");
+        assert_eq!(size(&files[1], false), "2048");
+        assert_eq!(size(&files[1], true), "2 KiB");
+        assert_eq!(cell(Column::Allocated, &files[2], None, &RenderOptions::default()), format!("{} (sparse)", files[2].allocated_size));
+        DirSize::Entries.apply(&mut files, &StdFileSystem);
+        assert_eq!(size(&files[0], false), "0");
+        files[0].dir_contents = Some(DirContents::Entries(None));
+        assert_eq!(size(&files[0], false), "?");
+    }
+}
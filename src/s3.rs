@@ -0,0 +1,281 @@
+use std::{
+    collections::HashMap,
+    ffi::OsString,
+    io::{self, Read},
+    path::{Component, Path, PathBuf},
+    process::{Child, Command, Stdio},
+    sync::Mutex,
+    time::SystemTime,
+};
+
+use chrono::{Local, NaiveDateTime, TimeZone};
+
+use crate::{
+    file_system::{DirEntry, FileReader, FileSystem, FileType, Metadata, Rewindable},
+    platform::{self, OsError},
+};
+
+/// Whether a path is an `s3://bucket/prefix` URI
+pub fn is_uri(path: &Path) -> bool {
+    path.as_os_str().as_encoded_bytes().starts_with(b"s3://")
+}
+
+/// Splits an `s3://` URI into its bucket and key, with repeated and trailing slashes dropped
+///
+/// # Returns
+///
+/// The bucket and the key, empty for the bucket itself, or None if the path is not an
+/// S3 URI or is not valid UTF-8
+fn split(path: &Path) -> Option<(&str, String)> {
+    if !is_uri(path) {
+        return None;
+    }
+    let mut components = path.components().skip(1).map(|component| match component {
+        Component::Normal(name) => name.to_str(),
+        _ => None,
+    });
+    let bucket = components.next()??;
+    let key: Option<Vec<&str>> = components.collect();
+    Some((bucket, key?.join("/")))
+}
+
+/// One line of `aws s3 ls` output
+#[derive(Debug, PartialEq, Eq)]
+enum Listed {
+    /// A common prefix, listed as a directory
+    Prefix(String),
+    Object { name: String, size: u64, modified: Option<SystemTime> },
+}
+
+/// Parses the output of `aws s3 ls s3://bucket/prefix/`, whose times are in local time
+///
+/// # Arguments
+///
+/// * `output` - Lines such as `PRE photos/` and `2024-05-01 10:20:30       1234 notes.txt`
+///
+/// # Returns
+///
+/// The prefixes and objects listed, without the marker object some tools create for the prefix itself
+fn parse_listing(output: &str) -> Vec<Listed> {
+    output
+        .lines()
+        .filter_map(|line| {
+            if let Some(prefix) = line.trim_start().strip_prefix("PRE ") {
+                return Some(Listed::Prefix(prefix.trim_end_matches('/').to_string()));
+            }
+            let (date, rest) = line.split_once(' ')?;
+            let (time, rest) = rest.split_once(' ')?;
+            let (size, name) = rest.trim_start().split_once(' ')?;
+            let modified = NaiveDateTime::parse_from_str(&format!("{} {}", date, time), "%Y-%m-%d %H:%M:%S")
+                .ok()
+                .and_then(|time| Local.from_local_datetime(&time).earliest())
+                .map(SystemTime::from);
+            Some(Listed::Object { name: name.to_string(), size: size.parse().ok()?, modified })
+        })
+        .filter(|listed| !matches!(listed, Listed::Object { name, .. } if name.is_empty()))
+        .collect()
+}
+
+/// What has been listed of a bucket so far
+#[derive(Debug, Default)]
+struct Cache {
+    /// Every entry seen, by path
+    entries: HashMap<PathBuf, Metadata>,
+    /// The names under each prefix listed, in the order S3 returned them
+    children: HashMap<PathBuf, Vec<OsString>>,
+}
+
+/// Objects and common prefixes in S3 buckets, read with the AWS CLI
+///
+/// `s3://bucket/prefix` URIs list the objects directly under the prefix as files and
+/// the common prefixes one level down as directories, so buckets get the grid, long
+/// and recursive listings. Credentials, region and endpoint come from the AWS CLI's
+/// own configuration. Each prefix is listed once and remembered.
+#[derive(Debug, Default)]
+pub struct S3FileSystem {
+    cache: Mutex<Cache>,
+}
+
+impl S3FileSystem {
+    /// Lists the names directly under a prefix, asking S3 the first time
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The `s3://bucket/prefix` URI of the prefix
+    ///
+    /// # Returns
+    ///
+    /// The names, or an error if nothing is stored under the prefix or the AWS CLI failed
+    fn list(&self, path: &Path) -> io::Result<Vec<OsString>> {
+        if let Some(names) = self.lock().children.get(path) {
+            return Ok(names.clone());
+        }
+        let (bucket, key) = split(path).ok_or_else(not_found)?;
+        let uri = if key.is_empty() { format!("s3://{}/", bucket) } else { format!("s3://{}/{}/", bucket, key) };
+        let output = Command::new("aws")
+            .args(["s3", "ls"])
+            .arg(&uri)
+            .output()
+            .map_err(|err| io::Error::new(err.kind(), format!("cannot run the AWS CLI: {}", err)))?;
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if !output.status.success() {
+            // A prefix nothing is stored under fails without a message
+            return Err(match stderr.trim() {
+                "" => not_found(),
+                message => io::Error::other(message.to_string()),
+            });
+        }
+
+        let mut cache = self.lock();
+        let mut names = Vec::new();
+        for listed in parse_listing(&String::from_utf8_lossy(&output.stdout)) {
+            let (name, metadata) = match listed {
+                Listed::Prefix(name) => (name, cache.metadata(FileType::Directory, 0, None)),
+                Listed::Object { name, size, modified } => (name, cache.metadata(FileType::File, size, modified)),
+            };
+            cache.entries.insert(path.join(&name), metadata);
+            names.push(OsString::from(name));
+        }
+        // Anything listed under the prefix makes it a directory, without listing the one above it
+        let directory = cache.metadata(FileType::Directory, 0, None);
+        cache.entries.entry(path.to_path_buf()).or_insert(directory);
+        cache.children.insert(path.to_path_buf(), names.clone());
+        Ok(names)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Cache> {
+        self.cache.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+impl Cache {
+    /// Describes an object or prefix, owned by whoever runs the listing
+    ///
+    /// Each gets its own inode number, so recursive listings see no cycles.
+    fn metadata(&self, file_type: FileType, len: u64, modified: Option<SystemTime>) -> Metadata {
+        let mode = if file_type == FileType::Directory { 0o040755 } else { 0o100644 };
+        Metadata {
+            file_type,
+            mode,
+            len,
+            blocks: len.div_ceil(512),
+            nlink: 1,
+            ino: self.entries.len() as u64 + 1,
+            uid: platform::current_uid(),
+            gid: platform::current_gid(),
+            modified,
+            accessed: modified,
+            changed: modified,
+            ..Default::default()
+        }
+    }
+}
+
+impl FileSystem for S3FileSystem {
+    fn read_dir(&self, path: &Path) -> io::Result<Box<dyn Iterator<Item = io::Result<DirEntry>> + '_>> {
+        let names = self.list(path)?;
+        let path = path.to_path_buf();
+        Ok(Box::new(names.into_iter().map(move |name| Ok(DirEntry { path: path.join(&name), name }))))
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<Metadata> {
+        self.symlink_metadata(path)
+    }
+
+    fn symlink_metadata(&self, path: &Path) -> io::Result<Metadata> {
+        let (bucket, key) = split(path).ok_or_else(not_found)?;
+        if key.is_empty() {
+            let mut cache = self.lock();
+            let root = cache.metadata(FileType::Directory, 0, None);
+            return Ok(cache.entries.entry(path.to_path_buf()).or_insert(root).clone());
+        }
+        if let Some(metadata) = self.lock().entries.get(path) {
+            return Ok(metadata.clone());
+        }
+        // Entries are only known from the listing of the prefix above them
+        let parent = PathBuf::from(format!("s3://{}", bucket)).join(Path::new(&key).parent().unwrap_or(Path::new("")));
+        self.list(&parent)?;
+        self.lock().entries.get(path).cloned().ok_or_else(not_found)
+    }
+
+    fn read_link(&self, _path: &Path) -> io::Result<PathBuf> {
+        // S3 has no symlinks
+        Err(platform::error(OsError::InvalidArgument))
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        self.symlink_metadata(path).map(|_| path.to_path_buf())
+    }
+
+    /// Streams an object through `aws s3 cp`, downloading only as much as is read
+    fn open(&self, path: &Path) -> io::Result<Box<dyn FileReader>> {
+        let (bucket, key) = split(path).ok_or_else(not_found)?;
+        let child = Command::new("aws")
+            .args(["s3", "cp"])
+            .arg(format!("s3://{}/{}", bucket, key))
+            .arg("-")
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|err| io::Error::new(err.kind(), format!("cannot run the AWS CLI: {}", err)))?;
+        Ok(Box::new(Rewindable::new(Download(child))))
+    }
+}
+
+/// An object being downloaded by the AWS CLI to its standard output
+struct Download(Child);
+
+impl Read for Download {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let Some(stdout) = self.0.stdout.as_mut() else {
+            return Ok(0);
+        };
+        let len = stdout.read(buf)?;
+        // A failed download ends early, which is only told apart from the object's end by the exit status
+        if len == 0 && !buf.is_empty() && !self.0.wait()?.success() {
+            return Err(io::Error::other("cannot download the object"));
+        }
+        Ok(len)
+    }
+}
+
+impl Drop for Download {
+    fn drop(&mut self) {
+        // Stops a download that was not read to the end
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+/// The error a missing path gets, worded as the local file system words it
+fn not_found() -> io::Error {
+    platform::error(OsError::NotFound)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split() {
+        assert_eq!(split(Path::new("s3://bucket")), Some(("bucket", String::new())));
+        assert_eq!(split(Path::new("s3://bucket/")), Some(("bucket", String::new())));
+        assert_eq!(split(Path::new("s3://bucket/photos//2024/")), Some(("bucket", "photos/2024".to_string())));
+        assert_eq!(split(Path::new("bucket/photos")), None);
+        assert!(is_uri(&Path::new("s3://bucket").join("notes.txt")));
+    }
+
+    #[test]
+    fn test_parse_listing() {
+        let output = "                           PRE photos/\n2024-05-01 10:20:30       1234 my notes.txt\n2024-05-01 10:20:30          0 \n";
+        let listed = parse_listing(output);
+        assert_eq!(listed.len(), 2);
+        assert_eq!(listed[0], Listed::Prefix("photos".to_string()));
+        let Listed::Object { name, size, modified } = &listed[1] else {
+            panic!("expected an object, got {:?}", listed[1]);
+        };
+        assert_eq!((name.as_str(), *size), ("my notes.txt", 1234));
+        let expected = Local.with_ymd_and_hms(2024, 5, 1, 10, 20, 30).unwrap();
+        assert_eq!(*modified, Some(SystemTime::from(expected)));
+    }
+}
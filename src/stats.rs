@@ -0,0 +1,119 @@
+use std::{
+    fmt,
+    time::{Duration, SystemTime},
+};
+
+use humansize::{format_size, BINARY};
+
+use crate::dir_utils::FileInfo;
+
+const DAY: u64 = 24 * 60 * 60;
+
+/// Upper bounds (exclusive) of the age buckets, oldest bucket catches the rest
+const AGE_BUCKETS: [(&str, u64); 4] = [
+    ("<1d", DAY),
+    ("<1w", 7 * DAY),
+    ("<1m", 30 * DAY),
+    ("<1y", 365 * DAY),
+];
+
+/// Upper bounds (exclusive) of the size buckets, largest bucket catches the rest
+const SIZE_BUCKETS: [(&str, u64); 4] = [
+    ("empty", 1),
+    ("<1K", 1 << 10),
+    ("<1M", 1 << 20),
+    ("<1G", 1 << 30),
+];
+
+/// Summary of a directory listing's entries
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Stats {
+    pub directories: usize,
+    pub files: usize,
+    pub total_size: u64,
+    /// Entry counts per age bucket, with one extra slot for "older"
+    pub age: [usize; AGE_BUCKETS.len() + 1],
+    /// File counts per size bucket, with one extra slot for the largest files
+    pub size: [usize; SIZE_BUCKETS.len() + 1],
+}
+
+impl Stats {
+    /// Summarizes the given entries relative to `now`
+    ///
+    /// # Arguments
+    ///
+    /// * `files` - Entries gathered for the long format
+    /// * `now` - Reference time used to compute entry ages
+    ///
+    /// # Returns
+    ///
+    /// The counts, total size and age/size histograms of the entries
+    pub fn from_files(files: &[FileInfo], now: SystemTime) -> Self {
+        let mut stats = Stats::default();
+        for file in files {
+            let age = now
+                .duration_since(file.modified_time)
+                .unwrap_or(Duration::ZERO)
+                .as_secs();
+            stats.age[bucket(&AGE_BUCKETS, age)] += 1;
+
+            if file.is_dir {
+                stats.directories += 1;
+            } else {
+                stats.files += 1;
+                stats.total_size += file.file_size;
+                stats.size[bucket(&SIZE_BUCKETS, file.file_size)] += 1;
+            }
+        }
+        stats
+    }
+}
+
+/// Finds the index of the first bucket whose bound exceeds `value`
+fn bucket(buckets: &[(&str, u64)], value: u64) -> usize {
+    buckets
+        .iter()
+        .position(|&(_, bound)| value < bound)
+        .unwrap_or(buckets.len())
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Entries: {} ({} directories, {} files)",
+            self.directories + self.files,
+            self.directories,
+            self.files
+        )?;
+        writeln!(f, "Total size: {}", format_size(self.total_size, BINARY))?;
+
+        writeln!(f, "Age:")?;
+        let age_labels = AGE_BUCKETS.iter().map(|&(label, _)| label).chain(["older"]);
+        for (label, count) in age_labels.zip(self.age) {
+            writeln!(f, "  {:<6} {}", label, count)?;
+        }
+
+        writeln!(f, "Size:")?;
+        let size_labels = SIZE_BUCKETS.iter().map(|&(label, _)| label).chain([">=1G"]);
+        for (label, count) in size_labels.zip(self.size) {
+            writeln!(f, "  {:<6} {}", label, count)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bucket() {
+        assert_eq!(bucket(&SIZE_BUCKETS, 0), 0);
+        assert_eq!(bucket(&SIZE_BUCKETS, 1023), 1);
+        assert_eq!(bucket(&SIZE_BUCKETS, 1024), 2);
+        assert_eq!(bucket(&SIZE_BUCKETS, 1 << 40), SIZE_BUCKETS.len());
+        assert_eq!(bucket(&AGE_BUCKETS, 2 * DAY), 1);
+        assert_eq!(bucket(&AGE_BUCKETS, 400 * DAY), AGE_BUCKETS.len());
+    }
+}
@@ -0,0 +1,113 @@
+use std::env;
+
+use unicode_width::UnicodeWidthStr;
+
+use crate::platform;
+
+/// Finds the width short listings wrap at, like GNU ls: `--width`, then the `COLUMNS`
+/// environment variable, then the width of the terminal stdout is connected to
+///
+/// # Arguments
+///
+/// * `requested` - The width given with `--width`, if any; 0 means no limit
+///
+/// # Returns
+///
+/// The width in columns, or None if lines should not be wrapped
+pub fn width(requested: Option<usize>) -> Option<usize> {
+    if let Some(width) = requested {
+        return (width > 0).then_some(width);
+    }
+    let columns = env::var("COLUMNS").ok().and_then(|columns| columns.trim().parse::<usize>().ok());
+    if let Some(width) = columns.filter(|&width| width > 0) {
+        return Some(width);
+    }
+    platform::terminal_width()
+}
+
+/// Measures how many columns text takes up on the terminal, skipping the escape
+/// sequences that color names and turn them into hyperlinks
+///
+/// Wide characters such as CJK and most emoji count as two columns, and combining
+/// marks as none.
+///
+/// # Arguments
+///
+/// * `text` - The text as it will be written
+///
+/// # Returns
+///
+/// The number of terminal columns
+pub fn visible_width(text: &str) -> usize {
+    if !text.contains('\x1b') {
+        return text.width();
+    }
+
+    let mut visible = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            visible.push(c);
+            continue;
+        }
+        match chars.next() {
+            // CSI sequences such as colors end with a byte in @..~
+            Some('[') => {
+                for c in chars.by_ref() {
+                    if ('@'..='~').contains(&c) {
+                        break;
+                    }
+                }
+            }
+            // OSC sequences such as hyperlinks end with BEL or ESC \
+            Some(']') => {
+                while let Some(c) = chars.next() {
+                    if c == '\x07' || (c == '\x1b' && chars.next_if_eq(&'\\').is_some()) {
+                        break;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    visible.width()
+}
+
+/// Pads text with spaces so it takes up at least `width` terminal columns
+///
+/// # Arguments
+///
+/// * `text` - The text to pad
+/// * `width` - The number of columns to fill
+pub fn pad(text: &str, width: usize) -> String {
+    let padding = width.saturating_sub(visible_width(text));
+    format!("{}{}", text, " ".repeat(padding))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_width_override() {
+        assert_eq!(width(Some(40)), Some(40));
+        assert_eq!(width(Some(0)), None);
+    }
+
+    #[test]
+    fn test_visible_width() {
+        assert_eq!(visible_width("plain.txt"), 9);
+        assert_eq!(visible_width("\x1b[1;34msrc\x1b[0m"), 3);
+        assert_eq!(visible_width("\x1b]8;;file:///tmp/a\x1b\\a\x1b]8;;\x1b\\"), 1);
+        assert_eq!(visible_width("日本語.txt"), 10);
+        assert_eq!(visible_width("e\u{301}te"), 3);
+        assert_eq!(visible_width("\x1b[31m😀\x1b[0m"), 2);
+    }
+
+    #[test]
+    fn test_pad() {
+        assert_eq!(pad("ab", 4), "ab  ");
+        assert_eq!(pad("日本", 5), "日本 ");
+        assert_eq!(pad("long", 2), "long");
+    }
+}
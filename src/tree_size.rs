@@ -0,0 +1,101 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use rayon::iter::{IntoParallelRefIterator, IntoParallelRefMutIterator, ParallelIterator};
+
+use crate::{
+    dir_utils::{DirContents, FileInfo},
+    file_system::FileSystem,
+};
+
+/// Sizes of directory trees already walked, by path, so a recursive listing sizes
+/// each subtree only once
+static SIZES: Mutex<Option<HashMap<PathBuf, u64>>> = Mutex::new(None);
+
+/// Shows the cumulative size of each directory's contents in the size column, as
+/// `--total-size` asks
+///
+/// # Arguments
+///
+/// * `files` - The long format entries to update
+/// * `file_system` - Where the directories are read from
+pub fn apply(files: &mut [FileInfo], file_system: &dyn FileSystem) {
+    files.par_iter_mut().filter(|file| file.is_dir).for_each(|file| {
+        file.dir_contents = Some(DirContents::Bytes(total_size(&file.path, file_system)));
+    });
+}
+
+/// Adds up the sizes of everything below a directory, without following symlinks
+///
+/// Subdirectories are walked in parallel. Entries that cannot be read count as empty.
+///
+/// # Arguments
+///
+/// * `path` - The directory to size; a symlink to one is followed
+/// * `file_system` - Where the directory is read from
+///
+/// # Returns
+///
+/// The total size in bytes, or the entry's own size if it is not a directory
+pub fn total_size(path: &Path, file_system: &dyn FileSystem) -> u64 {
+    match file_system.metadata(path) {
+        Ok(metadata) if metadata.is_dir() => subtree_size(path, file_system),
+        Ok(metadata) => metadata.len,
+        Err(_) => 0,
+    }
+}
+
+/// Sizes one directory's contents, reusing and filling the cache
+fn subtree_size(path: &Path, file_system: &dyn FileSystem) -> u64 {
+    if let Some(size) = lock().as_ref().and_then(|sizes| sizes.get(path).copied()) {
+        return size;
+    }
+
+    let Ok(entries) = file_system.read_dir(path) else {
+        return 0;
+    };
+    let children: Vec<_> = entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| Some((file_system.symlink_metadata(&entry.path).ok()?, entry.path)))
+        .collect();
+    let size = children
+        .par_iter()
+        .map(|(metadata, path)| if metadata.is_dir() { subtree_size(path, file_system) } else { metadata.len })
+        .sum();
+    lock().get_or_insert_with(HashMap::new).insert(path.to_path_buf(), size);
+    size
+}
+
+/// Locks the cache, recovering it if a thread panicked while holding it
+fn lock() -> std::sync::MutexGuard<'static, Option<HashMap<PathBuf, u64>>> {
+    SIZES.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::file_system::StdFileSystem;
+    use std::{fs, os::unix::fs::symlink};
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_total_size() {
+        let temp_dir = tempdir().unwrap();
+        let nested = temp_dir.path().join("a").join("b");
+        fs::create_dir_all(&nested).unwrap();
+        fs::write(temp_dir.path().join("a").join("one"), "1").unwrap();
+        fs::write(nested.join("three"), "333").unwrap();
+        symlink("/", nested.join("root")).unwrap();
+
+        let link_size = fs::symlink_metadata(nested.join("root")).unwrap().len();
+        assert_eq!(total_size(&nested, &StdFileSystem), 3 + link_size);
+        assert_eq!(total_size(&temp_dir.path().join("a"), &StdFileSystem), 4 + link_size);
+        assert_eq!(total_size(&nested.join("three"), &StdFileSystem), 3);
+        // Served from the cache, so a change below is not seen again
+        fs::write(nested.join("more"), "22").unwrap();
+        assert_eq!(total_size(&nested, &StdFileSystem), 3 + link_size);
+    }
+}